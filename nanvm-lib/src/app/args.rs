@@ -0,0 +1,265 @@
+use core::fmt::{self, Display};
+
+pub const USAGE: &str = "\
+Usage:
+  nanvm convert INPUT OUTPUT [--to json|cjs|mjs] [--pretty]
+  nanvm check INPUT...
+  nanvm fmt INPUT [OUTPUT]
+  nanvm eval INPUT [--to json|cjs|mjs] [--pretty]
+  nanvm INPUT OUTPUT
+
+Commands:
+  convert  Converts a module with its imports into one file
+  check    Checks modules, or directories of modules, and their imports for all errors
+  fmt      Writes a module back indented, with its const names and imports
+  eval     Prints the value of a module
+
+Options:
+  --to FORMAT  The output format, by default from the OUTPUT extension for `convert`
+               and `json` for `eval`
+  --pretty     Indents the output
+  -h, --help   Prints this help
+
+`-` as INPUT reads stdin and as OUTPUT writes stdout. `fmt` writes stdout by default.";
+
+/// An output format of `convert` and `eval`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Cjs,
+    Mjs,
+}
+
+impl Format {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "json" => Some(Format::Json),
+            "cjs" => Some(Format::Cjs),
+            "mjs" => Some(Format::Mjs),
+            _ => None,
+        }
+    }
+
+    /// The format of a file by its extension.
+    pub fn from_path(path: &str) -> Option<Self> {
+        if path.ends_with(".json") {
+            return Some(Format::Json);
+        }
+        if path.ends_with(".d.cjs") {
+            return Some(Format::Cjs);
+        }
+        if path.ends_with(".d.mjs") {
+            return Some(Format::Mjs);
+        }
+        None
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Json => "json",
+            Format::Cjs => "cjs",
+            Format::Mjs => "mjs",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Convert {
+        input: String,
+        output: String,
+        /// Overrides the format of the `output` extension.
+        to: Option<Format>,
+    },
+    /// Files or directories to check.
+    Check {
+        inputs: Vec<String>,
+    },
+    Fmt {
+        input: String,
+        output: String,
+    },
+    Eval {
+        input: String,
+        to: Format,
+    },
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Args {
+    pub command: Command,
+    pub pretty: bool,
+}
+
+/// Parses the command-line arguments without the program name. Two arguments without a command
+/// are `convert INPUT OUTPUT`, like in the first versions of `nanvm`.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut positional = Vec::new();
+    let mut to = None;
+    let mut pretty = false;
+    let mut help = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_owned())),
+            _ => (arg.as_str(), None),
+        };
+        match name {
+            "--to" => {
+                let value = value
+                    .or_else(|| args.next())
+                    .ok_or("`--to` requires a format")?;
+                to = Some(Format::parse(&value).ok_or(format!("unknown format `{value}`"))?);
+            }
+            "--pretty" => pretty = true,
+            "-h" | "--help" => help = true,
+            _ if name.starts_with('-') && name != "-" => {
+                return Err(format!("unknown option `{arg}`"))
+            }
+            _ => positional.push(arg),
+        }
+    }
+    if help {
+        return Ok(Args {
+            command: Command::Help,
+            pretty,
+        });
+    }
+    let (command, operands) = match positional.split_first() {
+        Some((command, rest))
+            if matches!(
+                command.as_str(),
+                "convert" | "check" | "fmt" | "eval" | "help"
+            ) =>
+        {
+            (command.as_str(), rest)
+        }
+        _ => ("convert", positional.as_slice()),
+    };
+    let operands = |min: usize, max: usize| match operands.len() {
+        len if len < min => Err(format!("`{command}` requires more arguments")),
+        len if len > max => Err(format!("too many arguments for `{command}`")),
+        _ => Ok(operands),
+    };
+    let no_to = || match to {
+        Some(_) => Err(format!("`{command}` doesn't accept `--to`")),
+        None => Ok(()),
+    };
+    let command = match command {
+        "convert" => match operands(2, 2)? {
+            [input, output] => {
+                if output == "-" && to.is_none() {
+                    return Err("`--to` is required to write to stdout".to_owned());
+                }
+                Command::Convert {
+                    input: input.clone(),
+                    output: output.clone(),
+                    to,
+                }
+            }
+            _ => unreachable!(),
+        },
+        "check" => {
+            no_to()?;
+            Command::Check {
+                inputs: operands(1, usize::MAX)?.to_vec(),
+            }
+        }
+        "fmt" => {
+            no_to()?;
+            let operands = operands(1, 2)?;
+            Command::Fmt {
+                input: operands[0].clone(),
+                output: operands.get(1).map_or("-", String::as_str).to_owned(),
+            }
+        }
+        "eval" => Command::Eval {
+            input: operands(1, 1)?[0].clone(),
+            to: to.unwrap_or(Format::Json),
+        },
+        _ => {
+            operands(0, 0)?;
+            Command::Help
+        }
+    };
+    Ok(Args { command, pretty })
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{parse_args, Args, Command, Format};
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_convert() {
+        assert_eq!(
+            parse(&["a.json", "b.d.mjs"]),
+            Ok(Args {
+                command: Command::Convert {
+                    input: "a.json".to_owned(),
+                    output: "b.d.mjs".to_owned(),
+                    to: None,
+                },
+                pretty: false,
+            })
+        );
+        assert_eq!(
+            parse(&["convert", "--pretty", "-", "--to=cjs", "b.txt"]),
+            Ok(Args {
+                command: Command::Convert {
+                    input: "-".to_owned(),
+                    output: "b.txt".to_owned(),
+                    to: Some(Format::Cjs),
+                },
+                pretty: true,
+            })
+        );
+        assert!(parse(&["convert", "a.json", "-"]).is_err());
+        assert!(parse(&["a.json"]).is_err());
+        assert!(parse(&[]).is_err());
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_commands() {
+        assert_eq!(
+            parse(&["eval", "a.d.mjs", "--to", "mjs"]).map(|a| a.command),
+            Ok(Command::Eval {
+                input: "a.d.mjs".to_owned(),
+                to: Format::Mjs,
+            })
+        );
+        assert_eq!(
+            parse(&["fmt", "a.d.mjs"]).map(|a| a.command),
+            Ok(Command::Fmt {
+                input: "a.d.mjs".to_owned(),
+                output: "-".to_owned(),
+            })
+        );
+        assert_eq!(
+            parse(&["check", "a.d.mjs", "--help"]).map(|a| a.command),
+            Ok(Command::Help)
+        );
+        assert_eq!(parse(&["help"]).map(|a| a.command), Ok(Command::Help));
+        assert_eq!(
+            parse(&["check", "a.d.mjs", "data"]).map(|a| a.command),
+            Ok(Command::Check {
+                inputs: vec!["a.d.mjs".to_owned(), "data".to_owned()],
+            })
+        );
+        assert!(parse(&["check"]).is_err());
+        assert!(parse(&["fmt", "a.d.mjs", "--to", "json"]).is_err());
+        assert!(parse(&["eval", "a.d.mjs", "--to", "yaml"]).is_err());
+        assert!(parse(&["eval", "a.d.mjs", "--to"]).is_err());
+        assert!(parse(&["eval", "a.d.mjs", "--verbose"]).is_err());
+    }
+}
@@ -1,68 +1,250 @@
-use io_trait::Io;
-use std::io::{self, Error};
+mod args;
+
+use core::fmt::{self, Display};
+use io_trait::{DirEntry, Io, Metadata};
+use std::io::{self, Read, Write};
 
 use crate::{
     common::default::default,
-    mem::global::GLOBAL,
+    js::any::Any,
+    mem::global::{Global, GLOBAL},
     parser::{
-        parser::{parse, Context},
-        shared::DataType,
+        check::Checker,
+        parser::{analyze, parse, parse_source, Context},
+        shared::ParseError,
+    },
+    serializer::{
+        format::FormatOptions,
+        to_djs::{to_djs_with_options, DjsOptions},
+        to_json::{to_json_with_options, JsonOptions},
+        to_module::{to_module, Unsupported},
     },
-    serializer::{to_djs::to_djs, to_json::to_json},
 };
 
-pub fn run(io: &impl Io) -> io::Result<()> {
-    let mut a = io.args();
-    a.next().unwrap();
-    let input = a.next().unwrap();
-    let output = a.next().unwrap();
+pub use args::{parse_args, Args, Command, Format, USAGE};
+
+/// The path of a module read from stdin, in error messages. Its imports are relative to the
+/// current directory.
+const STDIN: &str = "<stdin>";
+
+#[derive(Debug)]
+pub enum Error {
+    /// Wrong command-line arguments.
+    Usage(String),
+    /// A file or a standard stream can't be read or written.
+    Io(String, io::Error),
+    Parse(Box<ParseError>),
+    /// Errors found by `check` in the given number of modules.
+    Check(usize, Vec<ParseError>),
+    /// The value can't be written in the format, e.g. a bigint in JSON or a function.
+    Write(Format),
+    /// `fmt` would drop the comments of the module at the path.
+    Comments(String),
+    /// `fmt` can't write a construct of the module at the path.
+    Unsupported(String, Unsupported),
+}
 
-    let mc = &mut default();
-    let mut context = Context::new(GLOBAL, io, input, mc);
-    let output_data_type = file_to_data_type(&output);
-    match output_data_type {
-        Ok(data_type) => match parse(&mut context) {
-            Ok(parse_result) => match data_type {
-                DataType::Json => {
-                    let to_json_result = to_json(parse_result.any);
-                    match to_json_result {
-                        Ok(s) => io.write(&output, s.as_bytes()),
-                        Err(e) => Err(Error::other(e)),
-                    }
-                }
-                DataType::Cjs => {
-                    let to_json_result = to_djs(parse_result.any, true);
-                    match to_json_result {
-                        Ok(s) => io.write(&output, s.as_bytes()),
-                        Err(e) => Err(Error::other(e)),
-                    }
+impl Error {
+    /// `2` for wrong arguments and `1` for other errors.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Usage(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(message) => write!(f, "{message}\n\n{USAGE}"),
+            Error::Io(path, e) => write!(f, "{path}: {e}"),
+            Error::Parse(e) => write!(f, "{e}"),
+            Error::Check(modules, errors) => {
+                for e in errors {
+                    write!(f, "{e}\n\nerror: ")?;
                 }
-                DataType::Mjs => {
-                    let to_json_result = to_djs(parse_result.any, false);
-                    match to_json_result {
-                        Ok(s) => io.write(&output, s.as_bytes()),
-                        Err(e) => Err(Error::other(e)),
-                    }
+                let plural = |n: usize| if n == 1 { "" } else { "s" };
+                let n = errors.len();
+                write!(
+                    f,
+                    "{n} error{} in {modules} module{}",
+                    plural(n),
+                    plural(*modules)
+                )
+            }
+            Error::Write(format) => write!(f, "the value can't be written as {format}"),
+            Error::Comments(path) => write!(f, "{path}: comments can't be written back"),
+            Error::Unsupported(path, e) => write!(f, "{path}: {e}"),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(Box::new(e))
+    }
+}
+
+/// Runs the command-line interface and returns the exit code. Errors are printed to `stderr`.
+pub fn cli(io: &impl Io, stdin: &mut impl Read, stderr: &mut impl Write) -> u8 {
+    match run(io, stdin) {
+        Ok(()) => 0,
+        Err(e) => {
+            let _ = writeln!(stderr, "error: {e}");
+            e.exit_code()
+        }
+    }
+}
+
+/// Runs the command of `io.args()`. `stdin` is read for the input `-`.
+pub fn run(io: &impl Io, stdin: &mut impl Read) -> Result<(), Error> {
+    let args = parse_args(io.args().skip(1)).map_err(Error::Usage)?;
+    let format = match args.pretty {
+        true => FormatOptions::pretty(),
+        false => default(),
+    };
+    match args.command {
+        Command::Convert { input, output, to } => {
+            let to = match to {
+                Some(to) => to,
+                None => Format::from_path(&output).ok_or_else(|| {
+                    Error::Usage(format!(
+                        "unknown extension of `{output}`, use `--to` to set the format"
+                    ))
+                })?,
+            };
+            let any = evaluate(io, stdin, input)?;
+            write_output(io, &output, &serialize(any, to, format)?)
+        }
+        Command::Check { inputs } => check(io, stdin, &inputs),
+        Command::Fmt { input, output } => {
+            let source = read_input(io, stdin, &input)?;
+            let results = analyze(GLOBAL, input_path(&input), &source)?;
+            if results.comments > 0 {
+                return Err(Error::Comments(input_path(&input).to_owned()));
+            }
+            // `fmt` output is meant to be read, so it's always indented.
+            let s = to_module(&results, &FormatOptions::pretty())
+                .map_err(|e| Error::Unsupported(input_path(&input).to_owned(), e))?;
+            write_output(io, &output, &s)
+        }
+        Command::Eval { input, to } => {
+            let any = evaluate(io, stdin, input)?;
+            write_output(io, "-", &serialize(any, to, format)?)
+        }
+        Command::Help => write_output(io, "-", USAGE),
+    }
+}
+
+fn input_path(input: &str) -> &str {
+    match input {
+        "-" => STDIN,
+        _ => input,
+    }
+}
+
+fn read_input(io: &impl Io, stdin: &mut impl Read, input: &str) -> Result<String, Error> {
+    let mut s = String::new();
+    match input {
+        "-" => stdin.read_to_string(&mut s).map(|_| s),
+        _ => io.read_to_string(input),
+    }
+    .map_err(|e| Error::Io(input_path(input).to_owned(), e))
+}
+
+/// Parses the module `input` with its imports and returns its value.
+fn evaluate(io: &impl Io, stdin: &mut impl Read, input: String) -> Result<Any<Global>, Error> {
+    let mc = &mut default();
+    let result = match input.as_str() {
+        "-" => {
+            let source = read_input(io, stdin, &input)?;
+            parse_source(&mut Context::new(GLOBAL, io, STDIN.to_owned(), mc), &source)
+        }
+        _ => parse(&mut Context::new(GLOBAL, io, input, mc)),
+    };
+    Ok(result?.any)
+}
+
+/// Checks the modules `inputs`, the modules in the directories of `inputs` and their imports.
+fn check(io: &impl Io, stdin: &mut impl Read, inputs: &[String]) -> Result<(), Error> {
+    let mut checker = Checker::new(GLOBAL, io);
+    for input in inputs {
+        match input.as_str() {
+            "-" => checker.check_source(STDIN, read_input(io, stdin, input)?),
+            _ => {
+                for path in module_files(io, input)? {
+                    checker.check_file(&path);
                 }
-                _ => unreachable!(),
-            },
-            Err(parse_error) => Err(Error::other(parse_error.to_string())),
-        },
-        Err(e) => Err(e),
+            }
+        }
+    }
+    let results = checker.end();
+    match results.errors.is_empty() {
+        true => Ok(()),
+        false => Err(Error::Check(results.modules, results.errors)),
     }
 }
 
-fn file_to_data_type(s: &str) -> Result<DataType, Error> {
-    if s.ends_with(".json") {
-        return Ok(DataType::Json);
+/// `path` if it's a file, or the `.json`, `.d.cjs` and `.d.mjs` files in the directory `path`
+/// and its subdirectories, in the order of their paths.
+fn module_files(io: &impl Io, path: &str) -> Result<Vec<String>, Error> {
+    let is_dir = match io.metadata(path) {
+        Ok(metadata) => metadata.is_dir(),
+        // The checker reports the file as unreadable.
+        Err(_) => false,
+    };
+    if !is_dir {
+        return Ok(vec![path.to_owned()]);
     }
-    if s.ends_with(".d.cjs") {
-        return Ok(DataType::Cjs);
+    let entries = io
+        .read_dir(path)
+        .map_err(|e| Error::Io(path.to_owned(), e))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry_path = entry.path();
+        let is_dir = entry
+            .metadata()
+            .map_err(|e| Error::Io(entry_path.clone(), e))?
+            .is_dir();
+        if is_dir {
+            files.extend(module_files(io, &entry_path)?);
+        } else if Format::from_path(&entry_path).is_some() {
+            files.push(entry_path);
+        }
     }
-    if s.ends_with(".d.mjs") {
-        return Ok(DataType::Mjs);
+    files.sort();
+    Ok(files)
+}
+
+fn serialize(any: Any<Global>, to: Format, format: FormatOptions) -> Result<String, Error> {
+    match to {
+        Format::Json => to_json_with_options(
+            any,
+            &JsonOptions {
+                format,
+                ..default()
+            },
+        ),
+        Format::Cjs | Format::Mjs => to_djs_with_options(
+            any,
+            to == Format::Cjs,
+            &DjsOptions {
+                format,
+                ..default()
+            },
+        ),
+    }
+    .map_err(|_| Error::Write(to))
+}
+
+/// Writes `s` to the file `output`, or to stdout with a line break for `-`.
+fn write_output(io: &impl Io, output: &str, s: &str) -> Result<(), Error> {
+    match output {
+        "-" => writeln!(io.stdout(), "{s}"),
+        _ => io.write(output, s.as_bytes()),
     }
-    Err(Error::other("invalid output extension"))
+    .map_err(|e| Error::Io(output.to_owned(), e))
 }
 
 #[cfg(test)]
@@ -71,6 +253,8 @@ mod test {
     use io_trait::Io;
     use wasm_bindgen_test::wasm_bindgen_test;
 
+    use std::io;
+
     use super::run;
 
     #[test]
@@ -82,7 +266,7 @@ mod test {
         let main_path = "test_json.json";
         io.write(main_path, main.as_bytes()).unwrap();
 
-        let result = run(&io);
+        let result = run(&io, &mut io::empty());
         assert!(result.is_ok());
         let ouput_vec = io.read("output.json").unwrap();
         let vec = String::from_utf8(ouput_vec).unwrap();
@@ -98,7 +282,7 @@ mod test {
         let main_path = "test_json.json";
         io.write(main_path, main.as_bytes()).unwrap();
 
-        let result = run(&io);
+        let result = run(&io, &mut io::empty());
         assert!(result.is_ok());
         let ouput_vec = io.read("output.d.cjs").unwrap();
         let vec = String::from_utf8(ouput_vec).unwrap();
@@ -110,7 +294,7 @@ mod test {
         let main_path = "test_json.json";
         io.write(main_path, main.as_bytes()).unwrap();
 
-        let result = run(&io);
+        let result = run(&io, &mut io::empty());
         assert!(result.is_ok());
         let ouput_vec = io.read("output.d.mjs").unwrap();
         let vec = String::from_utf8(ouput_vec).unwrap();
@@ -126,7 +310,7 @@ mod test {
         let main_path = "test_djs.d.cjs";
         io.write(main_path, main.as_bytes()).unwrap();
 
-        let result = run(&io);
+        let result = run(&io, &mut io::empty());
         assert!(result.is_ok());
         let ouput_vec = io.read("output.d.cjs").unwrap();
         let vec = String::from_utf8(ouput_vec).unwrap();
@@ -142,7 +326,7 @@ mod test {
         let main_path = "test_djs.d.mjs";
         io.write(main_path, main.as_bytes()).unwrap();
 
-        let result = run(&io);
+        let result = run(&io, &mut io::empty());
         assert!(result.is_ok());
         let ouput_vec = io.read("output.d.mjs").unwrap();
         let vec = String::from_utf8(ouput_vec).unwrap();
@@ -162,7 +346,7 @@ mod test {
         let module_path = "test_import_main.d.cjs";
         io.write(module_path, module.as_bytes()).unwrap();
 
-        let result = run(&io);
+        let result = run(&io, &mut io::empty());
         assert!(result.is_ok());
         let ouput_vec = io.read("output.d.cjs").unwrap();
         let vec = String::from_utf8(ouput_vec).unwrap();
@@ -182,7 +366,7 @@ mod test {
         let module_path = "test_import_main.d.mjs";
         io.write(module_path, module.as_bytes()).unwrap();
 
-        let result = run(&io);
+        let result = run(&io, &mut io::empty());
         assert!(result.is_ok());
         let ouput_vec = io.read("output.d.mjs").unwrap();
         let vec = String::from_utf8(ouput_vec).unwrap();
@@ -198,7 +382,176 @@ mod test {
         let main_path = "test_json.json";
         io.write(main_path, main.as_bytes()).unwrap();
 
-        let result = run(&io);
+        let result = run(&io, &mut io::empty());
         assert!(result.is_err());
     }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_bigint() {
+        let io: VirtualIo = VirtualIo::new(&["test_bigint.d.mjs", "output.d.mjs"]);
+        io.write(
+            "test_bigint.d.mjs",
+            b"export default [1n, -18446744073709551616n]",
+        )
+        .unwrap();
+
+        let result = run(&io, &mut io::empty());
+        assert!(result.is_ok());
+        let ouput_vec = io.read("output.d.mjs").unwrap();
+        let vec = String::from_utf8(ouput_vec).unwrap();
+        assert_eq!(vec, r#"export default [1n,-18446744073709551616n]"#);
+
+        let io: VirtualIo = VirtualIo::new(&["test_bigint.d.mjs", "output.json"]);
+        io.write("test_bigint.d.mjs", b"export default [1n]")
+            .unwrap();
+
+        let result = run(&io, &mut io::empty());
+        assert!(result.is_err());
+    }
+
+    /// Runs `nanvm` with `args` and `stdin`, and returns the exit code and stderr.
+    fn cli(io: &VirtualIo, stdin: &str) -> (u8, String) {
+        let mut stderr = Vec::new();
+        let code = super::cli(io, &mut stdin.as_bytes(), &mut stderr);
+        (code, String::from_utf8(stderr).unwrap())
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_convert_to() {
+        let io = VirtualIo::new(&["convert", "input.json", "output.txt", "--to", "cjs"]);
+        io.write("input.json", b"[1]").unwrap();
+        assert_eq!(cli(&io, ""), (0, String::new()));
+        let output = io.read_to_string("output.txt").unwrap();
+        assert_eq!(output, "module.exports=[1]");
+
+        let io = VirtualIo::new(&["convert", "input.json", "output.txt"]);
+        io.write("input.json", b"[1]").unwrap();
+        let (code, stderr) = cli(&io, "");
+        assert_eq!(code, 2);
+        assert!(stderr.starts_with("error: unknown extension of `output.txt`"));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_stdin_stdout() {
+        let io = VirtualIo::new(&["convert", "-", "-", "--to=json", "--pretty"]);
+        assert_eq!(
+            cli(&io, "export default {\"a\": [1, 2]}"),
+            (0, String::new())
+        );
+        assert_eq!(io.stdout.to_stdout(), "{\n  \"a\": [1, 2]\n}\n");
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_check() {
+        let io = VirtualIo::new(&["check", "test_import_main.d.mjs"]);
+        let main = include_str!("../../test/test_import_main.d.mjs");
+        io.write("test_import_main.d.mjs", main.as_bytes()).unwrap();
+        let module = include_str!("../../test/test_import_module.d.mjs");
+        io.write("test_import_module.d.mjs", module.as_bytes())
+            .unwrap();
+        assert_eq!(cli(&io, ""), (0, String::new()));
+        assert_eq!(io.stdout.to_stdout(), "");
+
+        let io = VirtualIo::new(&["check", "test_import_main.d.mjs"]);
+        io.write("test_import_main.d.mjs", main.as_bytes()).unwrap();
+        let (code, stderr) = cli(&io, "");
+        assert_eq!(code, 1);
+        assert!(stderr.starts_with("error: CannotReadFile"));
+        assert!(stderr.contains("--> test_import_main.d.mjs:2:"));
+
+        let io = VirtualIo::new(&["check", "-"]);
+        let (code, stderr) = cli(&io, "[1 2]");
+        assert_eq!(code, 1);
+        assert!(stderr.contains("--> <stdin>:1:"));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_check_dir() {
+        let io = VirtualIo::new(&["check", "data", "other.d.cjs"]);
+        io.create_dir("data").unwrap();
+        io.create_dir("data/sub").unwrap();
+        io.write("data/a.json", b"[1 2]").unwrap();
+        io.write("data/notes.txt", b"[1 2]").unwrap();
+        io.write(
+            "data/sub/b.d.mjs",
+            b"import c from \"../c.d.mjs\"\nexport default c",
+        )
+        .unwrap();
+        io.write("other.d.cjs", b"module.exports = }").unwrap();
+        let (code, stderr) = cli(&io, "");
+        assert_eq!(code, 1);
+        let locations: Vec<_> = stderr.lines().filter(|l| l.starts_with(" --> ")).collect();
+        assert_eq!(
+            locations,
+            [
+                " --> data/a.json:1:4",
+                " --> data/sub/b.d.mjs:1:15",
+                " --> other.d.cjs:1:18"
+            ]
+        );
+        assert!(stderr.ends_with("3 errors in 3 modules\n"));
+
+        let io = VirtualIo::new(&["check", "data"]);
+        io.create_dir("data").unwrap();
+        io.write("data/a.json", b"[1, 2]").unwrap();
+        assert_eq!(cli(&io, ""), (0, String::new()));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_fmt() {
+        let io = VirtualIo::new(&["fmt", "-", "output.d.mjs"]);
+        let input = "import m from \"./m.d.mjs\"\nconst a = [1,m]\nexport default {a}";
+        assert_eq!(cli(&io, input), (0, String::new()));
+        assert_eq!(
+            io.read_to_string("output.d.mjs").unwrap(),
+            "import m from \"./m.d.mjs\";\nconst a = [1, m];\nexport default {\n  a\n}"
+        );
+
+        let io = VirtualIo::new(&["fmt", "-", "output.d.mjs"]);
+        let (code, stderr) = cli(&io, "// data\nexport default 1");
+        assert_eq!(code, 1);
+        assert_eq!(stderr, "error: <stdin>: comments can't be written back\n");
+        assert!(io.read_to_string("output.d.mjs").is_err());
+
+        let io = VirtualIo::new(&["fmt", "a.d.mjs"]);
+        io.write("a.d.mjs", b"export default [(a) => a]").unwrap();
+        let (code, stderr) = cli(&io, "");
+        assert_eq!(code, 1);
+        assert_eq!(
+            stderr,
+            "error: a.d.mjs: an arrow function can't be written back\n"
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_eval() {
+        let io = VirtualIo::new(&["eval", "test_import_main.d.cjs"]);
+        let main = include_str!("../../test/test_import_main.d.cjs");
+        io.write("test_import_main.d.cjs", main.as_bytes()).unwrap();
+        let module = include_str!("../../test/test_import_module.d.cjs");
+        io.write("test_import_module.d.cjs", module.as_bytes())
+            .unwrap();
+        assert_eq!(cli(&io, ""), (0, String::new()));
+        assert_eq!(io.stdout.to_stdout(), "[3]\n");
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_usage() {
+        let io = VirtualIo::new(&["input.json"]);
+        let (code, stderr) = cli(&io, "");
+        assert_eq!(code, 2);
+        assert!(stderr.contains("Usage:"));
+
+        let io = VirtualIo::new(&["--help"]);
+        assert_eq!(cli(&io, ""), (0, String::new()));
+        assert!(io.stdout.to_stdout().starts_with("Usage:"));
+    }
 }
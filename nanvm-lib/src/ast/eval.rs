@@ -0,0 +1,470 @@
+use std::{collections::BTreeMap, rc::Rc};
+
+use crate::{
+    bytecode::{
+        compiler::{compile_function, CompileError},
+        interpreter::{Interpreter, RuntimeError},
+        Program, ProgramRef,
+    },
+    common::default::default,
+    js::{
+        any::Any,
+        js_array::new_array,
+        js_function::{new_function, JsFunctionRef},
+        js_object::{new_ordered_object, Property},
+    },
+    mem::manager::{Dealloc, Manager},
+    tokenizer::span::Span,
+};
+
+use super::{Body, Expression, Function};
+
+#[derive(Debug, PartialEq)]
+pub enum EvaluationError {
+    /// `Expression::Void` has no value.
+    Void,
+    /// A reference to a local that is not evaluated yet.
+    LocalRef(u32),
+    /// A reference to an argument that is not passed.
+    ArgRef(u32),
+    /// A reference to a capture outside of a function or beyond its captures.
+    CaptureRef(u32),
+    /// The callee of the call at the span is not a function.
+    NotAFunction(Span),
+    /// The call at the span is nested deeper than `MAX_CALL_DEPTH`.
+    CallDepthLimit(Span),
+    /// A function used as data can't be compiled to bytecode.
+    Compile(CompileError),
+    /// The call of a function value at the span, e.g. of a function imported from another module,
+    /// failed.
+    Runtime(RuntimeError, Span),
+}
+
+impl EvaluationError {
+    /// The span of the call that failed.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            EvaluationError::NotAFunction(span)
+            | EvaluationError::CallDepthLimit(span)
+            | EvaluationError::Runtime(_, span) => Some(*span),
+            _ => None,
+        }
+    }
+}
+
+pub const MAX_CALL_DEPTH: u32 = 256;
+
+/// Creates an object from properties in the source order, see `new_ordered_object`.
+pub fn make_object<M: Manager>(
+    manager: M,
+    properties: impl IntoIterator<Item = Property<M::Dealloc>>,
+) -> Any<M::Dealloc> {
+    Any::move_from(new_ordered_object(manager, properties).to_ref())
+}
+
+/// A value during evaluation. Functions defined in the evaluated body are closures over the AST
+/// until they are used as data, then they are compiled to function values.
+enum Value<'a, D: Dealloc> {
+    Any(Any<D>),
+    Closure(Rc<Closure<'a, D>>),
+}
+
+impl<D: Dealloc> Clone for Value<'_, D> {
+    fn clone(&self) -> Self {
+        match self {
+            Value::Any(any) => Value::Any(any.clone()),
+            Value::Closure(closure) => Value::Closure(closure.clone()),
+        }
+    }
+}
+
+struct Closure<'a, D: Dealloc> {
+    function: &'a Function<D>,
+    captures: Vec<Value<'a, D>>,
+}
+
+struct Scope<'s, 'a, D: Dealloc> {
+    args: &'s [Value<'a, D>],
+    local: &'s [Value<'a, D>],
+    captures: &'s [Value<'a, D>],
+}
+
+fn get<'a, D: Dealloc>(
+    values: &[Value<'a, D>],
+    i: u32,
+    error: fn(u32) -> EvaluationError,
+) -> Result<Value<'a, D>, EvaluationError> {
+    values.get(i as usize).cloned().ok_or(error(i))
+}
+
+struct Evaluator<M: Manager> {
+    manager: M,
+    depth: u32,
+    /// The programs of the compiled functions, by their address in the AST.
+    compiled: BTreeMap<*const Function<M::Dealloc>, ProgramRef<M::Dealloc>>,
+}
+
+impl<M: Manager> Evaluator<M> {
+    /// Converts `value` to `Any`. A closure becomes a function value with its captures.
+    fn any(&mut self, value: Value<'_, M::Dealloc>) -> Result<Any<M::Dealloc>, EvaluationError> {
+        let closure = match value {
+            Value::Any(any) => return Ok(any),
+            Value::Closure(closure) => closure,
+        };
+        let program = self.compile(closure.function)?;
+        let captures = closure
+            .captures
+            .iter()
+            .map(|v| self.any(v.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let function = new_function(self.manager, program, 0, captures);
+        Ok(Any::move_from(function.to_ref()))
+    }
+
+    /// Compiles `function` into a program of its own, where it's the first function.
+    fn compile(
+        &mut self,
+        function: &Function<M::Dealloc>,
+    ) -> Result<ProgramRef<M::Dealloc>, EvaluationError> {
+        let key: *const _ = function;
+        if let Some(program) = self.compiled.get(&key) {
+            return Ok(program.clone());
+        }
+        let mut program = Program::default();
+        compile_function(&mut program, &function.body).map_err(EvaluationError::Compile)?;
+        let program = program.share(self.manager);
+        self.compiled.insert(key, program.clone());
+        Ok(program)
+    }
+
+    fn expression<'a>(
+        &mut self,
+        expression: &'a Expression<M::Dealloc>,
+        scope: &Scope<'_, 'a, M::Dealloc>,
+    ) -> Result<Value<'a, M::Dealloc>, EvaluationError> {
+        Ok(match expression {
+            Expression::Void => return Err(EvaluationError::Void),
+            Expression::LocalRef(i) => get(scope.local, *i, EvaluationError::LocalRef)?,
+            Expression::ArgRef(i) => get(scope.args, *i, EvaluationError::ArgRef)?,
+            Expression::CaptureRef(i) => get(scope.captures, *i, EvaluationError::CaptureRef)?,
+            Expression::Value(any) => Value::Any(any.clone()),
+            Expression::Object(properties) => {
+                let properties = properties
+                    .iter()
+                    .map(|(k, v)| {
+                        let v = self.expression(v, scope)?;
+                        Ok((k.clone(), self.any(v)?))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Value::Any(make_object(self.manager, properties))
+            }
+            Expression::Array(items) => {
+                let items = items
+                    .iter()
+                    .map(|e| {
+                        let v = self.expression(e, scope)?;
+                        self.any(v)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Value::Any(Any::move_from(new_array(self.manager, items).to_ref()))
+            }
+            Expression::Function(function) => Value::Closure(Rc::new(Closure {
+                function,
+                captures: function
+                    .captures
+                    .iter()
+                    .map(|e| self.expression(e, scope))
+                    .collect::<Result<_, _>>()?,
+            })),
+            Expression::Call(callee, args, span) => {
+                let callee = self.expression(callee, scope)?;
+                let args = args
+                    .iter()
+                    .map(|e| self.expression(e, scope))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if self.depth >= MAX_CALL_DEPTH {
+                    return Err(EvaluationError::CallDepthLimit(*span));
+                }
+                self.depth += 1;
+                let result = self.call(callee, args, *span);
+                self.depth -= 1;
+                result?
+            }
+        })
+    }
+
+    fn call<'a>(
+        &mut self,
+        callee: Value<'a, M::Dealloc>,
+        args: Vec<Value<'a, M::Dealloc>>,
+        span: Span,
+    ) -> Result<Value<'a, M::Dealloc>, EvaluationError> {
+        let any = match callee {
+            Value::Closure(closure) => {
+                return self.body(&closure.function.body, &args, &closure.captures)
+            }
+            Value::Any(any) => any,
+        };
+        // A function value, e.g. one imported from another module, runs as bytecode.
+        let function = any
+            .try_move::<JsFunctionRef<M::Dealloc>>()
+            .map_err(|_| EvaluationError::NotAFunction(span))?;
+        let args = args
+            .into_iter()
+            .map(|v| self.any(v))
+            .collect::<Result<Vec<_>, _>>()?;
+        let program = function.program().clone();
+        let interpreter = Interpreter::new(self.manager, program, &(), default());
+        let result = interpreter
+            .call_value(function, &args)
+            .map_err(|e| EvaluationError::Runtime(e, span))?;
+        Ok(Value::Any(result))
+    }
+
+    /// Evaluates the locals in order, each of them can refer to the previous ones, and then the
+    /// result.
+    fn body<'a>(
+        &mut self,
+        body: &'a Body<M::Dealloc>,
+        args: &[Value<'a, M::Dealloc>],
+        captures: &[Value<'a, M::Dealloc>],
+    ) -> Result<Value<'a, M::Dealloc>, EvaluationError> {
+        let mut local = Vec::with_capacity(body.local.len());
+        for e in &body.local {
+            let scope = Scope {
+                args,
+                local: &local,
+                captures,
+            };
+            let value = self.expression(e, &scope)?;
+            local.push(value);
+        }
+        let scope = Scope {
+            args,
+            local: &local,
+            captures,
+        };
+        self.expression(&body.result, &scope)
+    }
+}
+
+impl<D: Dealloc> Body<D> {
+    /// Evaluates the body of a module, `args` are the values of its imports. Functions in the
+    /// result are compiled to bytecode, each function value refers to its program.
+    pub fn evaluate<M: Manager<Dealloc = D>>(
+        &self,
+        manager: M,
+        args: &[Any<D>],
+    ) -> Result<Any<D>, EvaluationError> {
+        let args = args.iter().cloned().map(Value::Any).collect::<Vec<_>>();
+        let mut evaluator = Evaluator {
+            manager,
+            depth: 0,
+            compiled: BTreeMap::new(),
+        };
+        let result = evaluator.body(self, &args, &[])?;
+        evaluator.any(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        ast::{Body, Expression, Function},
+        bytecode::interpreter::Interpreter,
+        common::default::default,
+        js::{any::Any, js_array::JsArrayRef, js_function::JsFunctionRef},
+        mem::global::{Global, GLOBAL},
+        parser::shared::to_js_string,
+        serializer::to_json::to_json,
+        tokenizer::span::{Position, Span},
+    };
+
+    use super::EvaluationError;
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_body() {
+        let key = |s: &str| to_js_string(GLOBAL, s.to_owned());
+        let body = Body {
+            local: [
+                Expression::Object([(key("m2"), Expression::ArgRef(1))].into()),
+                Expression::Array(
+                    [
+                        Expression::Value(Any::move_from(3.0)),
+                        Expression::ArgRef(0),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+            result: Expression::Object(
+                [
+                    (key("b"), Expression::LocalRef(0)),
+                    (key("a"), Expression::LocalRef(1)),
+                    (key("b"), Expression::LocalRef(1)),
+                ]
+                .into(),
+            ),
+        };
+        let args = [Any::move_from(true), Any::move_from(key("x"))];
+        let result = body.evaluate(GLOBAL, &args).unwrap();
+        assert_eq!(to_json(result).unwrap(), r#"{"b":[3,true],"a":[3,true]}"#);
+
+        assert_eq!(
+            body.evaluate(GLOBAL, &args[..1]).unwrap_err(),
+            EvaluationError::ArgRef(1)
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_errors() {
+        let body = Body::<Global> {
+            local: [Expression::LocalRef(0)].into(),
+            result: Expression::Void,
+        };
+        assert_eq!(
+            body.evaluate(GLOBAL, &[]).unwrap_err(),
+            EvaluationError::LocalRef(0)
+        );
+
+        let body = Body::<Global> {
+            local: [].into(),
+            result: Expression::Void,
+        };
+        assert_eq!(
+            body.evaluate(GLOBAL, &[]).unwrap_err(),
+            EvaluationError::Void
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_functions() {
+        // const k = x => y => [x, y, m]
+        // export default k(1)(2)
+        let k = Expression::Function(Box::new(Function {
+            captures: [Expression::ArgRef(0)].into(),
+            params: 1,
+            body: Body {
+                local: [].into(),
+                result: Expression::Function(Box::new(Function {
+                    captures: [Expression::ArgRef(0), Expression::CaptureRef(0)].into(),
+                    params: 1,
+                    body: Body {
+                        local: [].into(),
+                        result: Expression::Array(
+                            [
+                                Expression::CaptureRef(0),
+                                Expression::ArgRef(0),
+                                Expression::CaptureRef(1),
+                            ]
+                            .into(),
+                        ),
+                    },
+                })),
+            },
+        }));
+        let call = |callee, arg: f64| {
+            Expression::Call(
+                Box::new(callee),
+                [Expression::Value(Any::move_from(arg))].into(),
+                default(),
+            )
+        };
+        let body = Body {
+            local: [k].into(),
+            result: call(call(Expression::LocalRef(0), 1.0), 2.0),
+        };
+        let args = [Any::move_from(to_js_string(GLOBAL, "m".to_owned()))];
+        let result = body.evaluate(GLOBAL, &args).unwrap();
+        assert_eq!(to_json(result).unwrap(), r#"[1,2,"m"]"#);
+
+        // A function in the result becomes a function value with its own program.
+        let body = Body {
+            local: body.local,
+            result: Expression::Array([call(Expression::LocalRef(0), 1.0)].into()),
+        };
+        let result = body
+            .evaluate(GLOBAL, &args)
+            .unwrap()
+            .try_move::<JsArrayRef<Global>>()
+            .unwrap();
+        let f = result.items()[0]
+            .clone()
+            .try_move::<JsFunctionRef<Global>>()
+            .unwrap();
+        assert_eq!(f.items().len(), 2);
+        let program = f.program().clone();
+        let result = Interpreter::new(GLOBAL, program, &(), Default::default())
+            .call_value(f.clone(), &[Any::move_from(3.0)])
+            .unwrap();
+        assert_eq!(to_json(result).unwrap(), r#"[1,3,"m"]"#);
+
+        // A function value can be called like a closure.
+        let body = Body {
+            local: [].into(),
+            result: Expression::Call(
+                Box::new(Expression::ArgRef(0)),
+                [Expression::Value(Any::move_from(4.0))].into(),
+                default(),
+            ),
+        };
+        let result = body.evaluate(GLOBAL, &[Any::move_from(f)]).unwrap();
+        assert_eq!(to_json(result).unwrap(), r#"[1,4,"m"]"#);
+    }
+
+    /// A span on the line `line`.
+    fn span(line: usize) -> Span {
+        let position = Position { line, ..default() };
+        Span {
+            begin: position,
+            end: position,
+        }
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_call_errors() {
+        let body = Body::<Global> {
+            local: [].into(),
+            result: Expression::Call(
+                Box::new(Expression::Value(Any::move_from(1.0))),
+                [].into(),
+                span(1),
+            ),
+        };
+        assert_eq!(
+            body.evaluate(GLOBAL, &[]).unwrap_err(),
+            EvaluationError::NotAFunction(span(1))
+        );
+
+        // (f => f(f))(f => f(f))
+        let omega = || {
+            Expression::Function(Box::new(Function {
+                captures: [].into(),
+                params: 1,
+                body: Body {
+                    local: [].into(),
+                    result: Expression::Call(
+                        Box::new(Expression::ArgRef(0)),
+                        [Expression::ArgRef(0)].into(),
+                        span(2),
+                    ),
+                },
+            }))
+        };
+        let body = Body::<Global> {
+            local: [].into(),
+            result: Expression::Call(Box::new(omega()), [omega()].into(), span(3)),
+        };
+        // The innermost call reached the limit.
+        assert_eq!(
+            body.evaluate(GLOBAL, &[]).unwrap_err(),
+            EvaluationError::CallDepthLimit(span(2))
+        );
+    }
+}
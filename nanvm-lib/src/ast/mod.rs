@@ -1,6 +1,9 @@
+pub mod eval;
+
 use crate::common::default::default;
 use crate::js::{any::Any, js_string::JsStringRef};
 use crate::mem::manager::Dealloc;
+use crate::tokenizer::span::Span;
 
 pub type Property<D> = (JsStringRef<D>, Expression<D>);
 
@@ -13,11 +16,26 @@ pub enum Expression<D: Dealloc> {
     Value(Any<D>),
     Object(Vec<Property<D>>),
     Array(Vec<Expression<D>>),
+    /// A reference to a value captured by the enclosing function.
+    CaptureRef(u32),
+    Function(Box<Function<D>>),
+    /// A callee, arguments and the span of the call in the source, to locate its errors.
+    Call(Box<Expression<D>>, Vec<Expression<D>>, Span),
+}
+
+/// An arrow function with an expression body.
+pub struct Function<D: Dealloc> {
+    /// Expressions of the enclosing scope the function refers to. `CaptureRef(i)` in `body`
+    /// refers to the value of `captures[i]` at the moment the function is created.
+    pub captures: Vec<Expression<D>>,
+    /// The number of parameters, `ArgRef(i)` in `body` refers to a parameter.
+    pub params: u32,
+    pub body: Body<D>,
 }
 
 pub struct Body<D: Dealloc> {
-    local: Vec<Expression<D>>,
-    result: Expression<D>,
+    pub local: Vec<Expression<D>>,
+    pub result: Expression<D>,
 }
 
 impl<D: Dealloc> Default for Body<D> {
@@ -30,8 +48,8 @@ impl<D: Dealloc> Default for Body<D> {
 }
 
 pub struct Module<D: Dealloc> {
-    import: Vec<JsStringRef<D>>,
-    body: Body<D>,
+    pub import: Vec<JsStringRef<D>>,
+    pub body: Body<D>,
 }
 
 impl<D: Dealloc> Default for Module<D> {
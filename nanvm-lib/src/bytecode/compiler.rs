@@ -0,0 +1,243 @@
+use crate::{
+    ast::{Body, Expression, Module},
+    js::any::Any,
+    mem::manager::Dealloc,
+};
+
+use super::{container::CompiledModule, instruction::Instruction, Program};
+
+#[derive(Debug, PartialEq)]
+pub enum CompileError {
+    /// `Expression::Void` has no value.
+    Void,
+    /// A reference to a local that is not defined before the reference.
+    LocalRef(u32),
+    /// The function has more locals, or an array or object has more items, than `u32` can count.
+    TooLarge,
+}
+
+/// Compiles `body` into a program with a single function.
+pub fn compile<D: Dealloc>(body: &Body<D>) -> Result<Program<D>, CompileError> {
+    let mut program = Program::default();
+    compile_function(&mut program, body)?;
+    Ok(program)
+}
+
+/// Compiles `module`. The entry point takes the values of the imports as arguments.
+pub fn compile_module<D: Dealloc>(module: &Module<D>) -> Result<CompiledModule<D>, CompileError> {
+    Ok(CompiledModule {
+        import: module
+            .import
+            .iter()
+            .map(|s| String::from_utf16_lossy(s.items()))
+            .collect(),
+        program: compile(&module.body)?,
+    })
+}
+
+/// Appends `body` as a new function of `program` and returns the index of the function. The
+/// functions defined in `body` are appended after it.
+///
+/// The locals are evaluated in order and each is moved to the locals of the call by `Define`.
+pub fn compile_function<D: Dealloc>(
+    program: &mut Program<D>,
+    body: &Body<D>,
+) -> Result<u32, CompileError> {
+    let index = reserve(program)?;
+    let mut pending = vec![(index, body)];
+    while let Some((index, body)) = pending.pop() {
+        program.functions[index as usize] = to_u32(program.code.len())?;
+        let mut compiler = Compiler {
+            program,
+            locals: 0,
+            pending: &mut pending,
+        };
+        for local in &body.local {
+            compiler.expression(local)?;
+            compiler.instruction(Instruction::Define);
+            compiler.locals += 1;
+        }
+        compiler.expression(&body.result)?;
+        compiler.instruction(Instruction::Return);
+    }
+    Ok(index)
+}
+
+/// Adds a function without code to `program` and returns its index.
+fn reserve<D: Dealloc>(program: &mut Program<D>) -> Result<u32, CompileError> {
+    let index = to_u32(program.functions.len())?;
+    program.functions.push(0);
+    Ok(index)
+}
+
+fn to_u32(n: usize) -> Result<u32, CompileError> {
+    n.try_into().map_err(|_| CompileError::TooLarge)
+}
+
+struct Compiler<'a, 'b, D: Dealloc> {
+    program: &'a mut Program<D>,
+    /// The number of locals defined so far.
+    locals: u32,
+    /// Functions that are referred to by `Closure` but not compiled yet.
+    pending: &'a mut Vec<(u32, &'b Body<D>)>,
+}
+
+impl<'b, D: Dealloc> Compiler<'_, 'b, D> {
+    fn instruction(&mut self, instruction: Instruction) {
+        instruction.write(&mut self.program.code);
+    }
+
+    fn constant(&mut self, any: Any<D>) -> Result<(), CompileError> {
+        let index = to_u32(self.program.constants.len())?;
+        self.program.constants.push(any);
+        self.instruction(Instruction::Const(index));
+        Ok(())
+    }
+
+    fn expression(&mut self, expression: &'b Expression<D>) -> Result<(), CompileError> {
+        match expression {
+            Expression::Void => return Err(CompileError::Void),
+            Expression::LocalRef(i) => {
+                if *i >= self.locals {
+                    return Err(CompileError::LocalRef(*i));
+                }
+                self.instruction(Instruction::Local(*i));
+            }
+            Expression::ArgRef(i) => self.instruction(Instruction::Arg(*i)),
+            Expression::Value(any) => self.constant(any.clone())?,
+            Expression::Object(properties) => {
+                for (k, v) in properties {
+                    self.constant(Any::move_from(k.clone()))?;
+                    self.expression(v)?;
+                }
+                self.instruction(Instruction::Object(to_u32(properties.len())?));
+            }
+            Expression::Array(items) => {
+                for item in items {
+                    self.expression(item)?;
+                }
+                self.instruction(Instruction::Array(to_u32(items.len())?));
+            }
+            Expression::CaptureRef(i) => self.instruction(Instruction::Capture(*i)),
+            Expression::Function(function) => {
+                for capture in &function.captures {
+                    self.expression(capture)?;
+                }
+                let index = reserve(self.program)?;
+                self.pending.push((index, &function.body));
+                self.instruction(Instruction::Closure {
+                    function: index,
+                    captures: to_u32(function.captures.len())?,
+                });
+            }
+            // The arguments are pushed before the callee, so the callee is popped first and the
+            // arguments are the cells of the call.
+            Expression::Call(callee, args, _) => {
+                for arg in args {
+                    self.expression(arg)?;
+                }
+                self.expression(callee)?;
+                self.instruction(Instruction::CallValue {
+                    args: to_u32(args.len())?,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        ast::{Body, Expression, Module},
+        bytecode::instruction::Instruction,
+        js::any::Any,
+        mem::global::{Global, GLOBAL},
+        parser::shared::to_js_string,
+    };
+
+    use super::{compile, compile_module, CompileError};
+
+    fn instructions(code: &[u8]) -> Vec<Instruction> {
+        let mut ip = 0;
+        let mut result = Vec::new();
+        while ip < code.len() {
+            result.push(Instruction::read(code, &mut ip).unwrap());
+        }
+        result
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_compile() {
+        let body = Body {
+            local: [Expression::Array(
+                [
+                    Expression::Value(Any::move_from(1.0)),
+                    Expression::ArgRef(0),
+                ]
+                .into(),
+            )]
+            .into(),
+            result: Expression::Object(
+                [(
+                    to_js_string(GLOBAL, "a".to_owned()),
+                    Expression::LocalRef(0),
+                )]
+                .into(),
+            ),
+        };
+        let program = compile(&body).unwrap();
+        assert_eq!(program.functions, [0]);
+        assert_eq!(program.constants.len(), 2);
+        assert_eq!(
+            instructions(&program.code),
+            [
+                Instruction::Const(0),
+                Instruction::Arg(0),
+                Instruction::Array(2),
+                Instruction::Define,
+                Instruction::Const(1),
+                Instruction::Local(0),
+                Instruction::Object(1),
+                Instruction::Return,
+            ]
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_module() {
+        let module = Module {
+            import: [to_js_string(GLOBAL, "./a.d.mjs".to_owned())].into(),
+            body: Body {
+                local: [].into(),
+                result: Expression::ArgRef(0),
+            },
+        };
+        let compiled = compile_module(&module).unwrap();
+        assert_eq!(compiled.import, ["./a.d.mjs"]);
+        assert_eq!(
+            instructions(&compiled.program.code),
+            [Instruction::Arg(0), Instruction::Return]
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_errors() {
+        let body = Body::<Global> {
+            local: [Expression::LocalRef(0)].into(),
+            result: Expression::LocalRef(0),
+        };
+        assert_eq!(compile(&body).err(), Some(CompileError::LocalRef(0)));
+
+        let body = Body::<Global> {
+            local: [].into(),
+            result: Expression::Void,
+        };
+        assert_eq!(compile(&body).err(), Some(CompileError::Void));
+    }
+}
@@ -0,0 +1,404 @@
+//! The binary format of compiled modules.
+//!
+//! ```text
+//! module    = magic version import constants functions code
+//! magic     = "NVMB"
+//! version   = u32
+//! import    = u32 string*
+//! constants = u32 constant*
+//! functions = u32 u32*
+//! code      = u32 byte*
+//! constant  = 0x00                       ; null
+//!           | 0x01 | 0x02                ; false, true
+//!           | 0x03 f64                   ; little endian
+//!           | 0x04 string
+//!           | 0x05 sign u32 u64*         ; sign is 0x00 or 0x01 (negative), little endian
+//!           | 0x06                       ; undefined
+//! string    = u32 u16*                   ; UTF-16, little endian
+//! ```
+//!
+//! `u32` numbers are unsigned LEB128, like instruction operands. The code contains no pointers, so
+//! the same module always has the same bytes and the hash of the bytes identifies the module.
+
+use std::io;
+
+use io_trait::Io;
+
+use crate::{
+    common::sha256::{sha256, to_hex, Hash},
+    js::{
+        any::Any,
+        js_bigint::{new_bigint, JsBigintRef, Sign},
+        js_string::{new_string, JsStringRef},
+        null::Null,
+        type_::Type,
+        undefined::Undefined,
+    },
+    mem::manager::{Dealloc, Manager},
+    parser::path::concat,
+};
+
+use super::{
+    instruction::{read_u32, write_u32},
+    Program,
+};
+
+pub const MAGIC: &[u8; 4] = b"NVMB";
+
+pub const VERSION: u32 = 1;
+
+pub const EXTENSION: &str = "nvmb";
+
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const NUMBER: u8 = 3;
+const STRING: u8 = 4;
+const BIGINT: u8 = 5;
+const UNDEFINED: u8 = 6;
+
+/// A module compiled to bytecode. The values of the imported modules are passed to the entry
+/// point of `program` as arguments, in the order of `import`.
+pub struct CompiledModule<D: Dealloc> {
+    pub import: Vec<String>,
+    pub program: Program<D>,
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_len(bytes: &mut Vec<u8>, len: usize) -> io::Result<()> {
+    write_u32(
+        bytes,
+        len.try_into()
+            .map_err(|_| invalid_data("the module is too large"))?,
+    );
+    Ok(())
+}
+
+fn write_utf16(bytes: &mut Vec<u8>, items: &[u16]) -> io::Result<()> {
+    write_len(bytes, items.len())?;
+    for c in items {
+        bytes.extend_from_slice(&c.to_le_bytes());
+    }
+    Ok(())
+}
+
+fn write_constant<D: Dealloc>(bytes: &mut Vec<u8>, any: &Any<D>) -> io::Result<()> {
+    match any.get_type() {
+        Type::Null => bytes.push(NULL),
+        Type::Undefined => bytes.push(UNDEFINED),
+        Type::Bool => bytes.push(match any.clone().try_move::<bool>().unwrap() {
+            false => FALSE,
+            true => TRUE,
+        }),
+        Type::Number => {
+            bytes.push(NUMBER);
+            let n = any.clone().try_move::<f64>().unwrap();
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        Type::String => {
+            bytes.push(STRING);
+            let s = any.clone().try_move::<JsStringRef<D>>().unwrap();
+            write_utf16(bytes, s.items())?;
+        }
+        Type::Bigint => {
+            bytes.push(BIGINT);
+            let b = any.clone().try_move::<JsBigintRef<D>>().unwrap();
+            bytes.push(match b.sign() {
+                Sign::Positive => 0,
+                Sign::Negative => 1,
+            });
+            write_len(bytes, b.items().len())?;
+            for item in b.items() {
+                bytes.extend_from_slice(&item.to_le_bytes());
+            }
+        }
+        Type::Object | Type::Array | Type::Function => {
+            return Err(invalid_data(
+                "objects, arrays and functions can't be constants",
+            ))
+        }
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let end = self.position.checked_add(len);
+        let result = end
+            .and_then(|end| self.bytes.get(self.position..end))
+            .ok_or_else(|| invalid_data("unexpected end of the module"))?;
+        self.position += len;
+        Ok(result)
+    }
+
+    /// Takes `len` items of `size` bytes. Lengths come from the module, so they can overflow
+    /// `usize` on 32-bit targets.
+    fn take_items(&mut self, len: usize, size: usize) -> io::Result<&'a [u8]> {
+        let len = len
+            .checked_mul(size)
+            .ok_or_else(|| invalid_data("unexpected end of the module"))?;
+        self.take(len)
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        read_u32(self.bytes, &mut self.position).ok_or_else(|| invalid_data("invalid number"))
+    }
+
+    fn len(&mut self) -> io::Result<usize> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn utf16(&mut self) -> io::Result<Vec<u16>> {
+        let len = self.len()?;
+        Ok(self
+            .take_items(len, 2)?
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect())
+    }
+
+    fn constant<M: Manager>(&mut self, manager: M) -> io::Result<Any<M::Dealloc>> {
+        Ok(match self.byte()? {
+            NULL => Any::move_from(Null()),
+            UNDEFINED => Any::move_from(Undefined()),
+            FALSE => Any::move_from(false),
+            TRUE => Any::move_from(true),
+            NUMBER => {
+                let bytes = self.take(8)?;
+                Any::move_from(f64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            STRING => {
+                let items = self.utf16()?;
+                Any::move_from(new_string(manager, items).to_ref())
+            }
+            BIGINT => {
+                let sign = match self.byte()? {
+                    0 => Sign::Positive,
+                    1 => Sign::Negative,
+                    _ => return Err(invalid_data("invalid bigint sign")),
+                };
+                let len = self.len()?;
+                let items = self
+                    .take_items(len, 8)?
+                    .chunks_exact(8)
+                    .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+                    .collect::<Vec<_>>();
+                Any::move_from(new_bigint(manager, sign, items).to_ref())
+            }
+            _ => return Err(invalid_data("invalid constant")),
+        })
+    }
+}
+
+impl<D: Dealloc> CompiledModule<D> {
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut bytes = MAGIC.to_vec();
+        write_u32(&mut bytes, VERSION);
+        write_len(&mut bytes, self.import.len())?;
+        for import in &self.import {
+            write_utf16(&mut bytes, &import.encode_utf16().collect::<Vec<_>>())?;
+        }
+        let program = &self.program;
+        write_len(&mut bytes, program.constants.len())?;
+        for constant in &program.constants {
+            write_constant(&mut bytes, constant)?;
+        }
+        write_len(&mut bytes, program.functions.len())?;
+        for offset in &program.functions {
+            write_u32(&mut bytes, *offset);
+        }
+        write_len(&mut bytes, program.code.len())?;
+        bytes.extend_from_slice(&program.code);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes<M: Manager<Dealloc = D>>(manager: M, bytes: &[u8]) -> io::Result<Self> {
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid_data("not a compiled module"));
+        }
+        if reader.u32()? != VERSION {
+            return Err(invalid_data("unsupported version"));
+        }
+        let mut import = Vec::new();
+        for _ in 0..reader.len()? {
+            import.push(
+                String::from_utf16(&reader.utf16()?)
+                    .map_err(|_| invalid_data("invalid import path"))?,
+            );
+        }
+        let mut program = Program::default();
+        for _ in 0..reader.len()? {
+            program.constants.push(reader.constant(manager)?);
+        }
+        for _ in 0..reader.len()? {
+            program.functions.push(reader.u32()?);
+        }
+        let len = reader.len()?;
+        program.code = reader.take(len)?.to_vec();
+        if reader.position != bytes.len() {
+            return Err(invalid_data("unexpected data after the module"));
+        }
+        Ok(CompiledModule { import, program })
+    }
+
+    /// The content hash of the module, the SHA-256 of its bytes.
+    pub fn hash(&self) -> io::Result<Hash> {
+        Ok(sha256(&self.to_bytes()?))
+    }
+
+    pub fn write<I: Io>(&self, io: &I, path: &str) -> io::Result<()> {
+        io.write(path, &self.to_bytes()?)
+    }
+
+    pub fn read<M: Manager<Dealloc = D>, I: Io>(
+        manager: M,
+        io: &I,
+        path: &str,
+    ) -> io::Result<Self> {
+        Self::from_bytes(manager, &io.read(path)?)
+    }
+}
+
+/// The path of the compiled module for `source` in `cache_dir`. The file name is the hash of the
+/// source, so a build can skip compiling a source if the file exists.
+pub fn cache_path(cache_dir: &str, source: &[u8]) -> String {
+    concat(
+        cache_dir,
+        &format!("{}.{EXTENSION}", to_hex(&sha256(source))),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use io_test::VirtualIo;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        bytecode::{
+            instruction::Instruction,
+            interpreter::{Interpreter, RuntimeError},
+            Program,
+        },
+        js::{
+            any::Any,
+            js_bigint::{from_u64, Sign},
+            null::Null,
+            undefined::Undefined,
+        },
+        mem::global::{Global, GLOBAL},
+        parser::shared::to_js_string,
+        serializer::to_json::{to_json_with_options, BigintPolicy, JsonOptions},
+    };
+
+    use super::{cache_path, CompiledModule, Reader};
+
+    fn module() -> CompiledModule<Global> {
+        let mut code = Vec::new();
+        for i in [
+            Instruction::Const(0),
+            Instruction::Const(1),
+            Instruction::Const(2),
+            Instruction::Const(3),
+            Instruction::Const(4),
+            Instruction::Const(5),
+            Instruction::Arg(0),
+            Instruction::Array(7),
+            Instruction::Return,
+        ] {
+            i.write(&mut code);
+        }
+        CompiledModule {
+            import: ["./a.d.mjs".to_owned()].into(),
+            program: Program {
+                constants: [
+                    Any::move_from(Null()),
+                    Any::move_from(true),
+                    Any::move_from(-2.5),
+                    Any::move_from(to_js_string(GLOBAL, "é😀".to_owned())),
+                    Any::move_from(from_u64(GLOBAL, Sign::Negative, 1 << 40).to_ref()),
+                    Any::move_from(Undefined()),
+                ]
+                .into(),
+                functions: [0].into(),
+                code,
+            },
+        }
+    }
+
+    fn run(module: CompiledModule<Global>) -> Result<String, RuntimeError> {
+        let program = module.program.share(GLOBAL);
+        let result = Interpreter::new(GLOBAL, program, &(), Default::default())
+            .run(&[Any::move_from(false)])?;
+        let options = JsonOptions {
+            bigint: BigintPolicy::Digits,
+            ..Default::default()
+        };
+        Ok(to_json_with_options(result, &options).unwrap())
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_round_trip() {
+        let module = module();
+        let bytes = module.to_bytes().unwrap();
+        assert_eq!(&bytes[..5], b"NVMB\x01");
+        let read = CompiledModule::from_bytes(GLOBAL, &bytes).unwrap();
+        assert_eq!(read.import, module.import);
+        assert_eq!(read.program.functions, module.program.functions);
+        assert_eq!(read.program.code, module.program.code);
+        assert_eq!(read.to_bytes().unwrap(), bytes);
+        assert_eq!(read.hash().unwrap(), module.hash().unwrap());
+        assert_eq!(
+            run(read).unwrap(),
+            r#"[null,true,-2.5,"\u00E9\uD83D\uDE00",-1099511627776,null,false]"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_io() {
+        let io = VirtualIo::new(&[]);
+        let module = module();
+        let path = cache_path("", b"source");
+        module.write(&io, &path).unwrap();
+        let read = CompiledModule::read(GLOBAL, &io, &path).unwrap();
+        assert_eq!(read.hash().unwrap(), module.hash().unwrap());
+        assert_ne!(cache_path("", b"source"), cache_path("", b"source2"));
+        assert!(CompiledModule::read(GLOBAL, &io, &cache_path("", b"source2")).is_err());
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_invalid() {
+        let bytes = module().to_bytes().unwrap();
+        for len in 0..bytes.len() {
+            assert!(CompiledModule::from_bytes(GLOBAL, &bytes[..len]).is_err());
+        }
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert!(CompiledModule::from_bytes(GLOBAL, &extra).is_err());
+        let mut version = bytes.clone();
+        version[4] = 2;
+        assert!(CompiledModule::from_bytes(GLOBAL, &version).is_err());
+        let mut reader = Reader {
+            bytes: &bytes,
+            position: 1,
+        };
+        assert!(reader.take(usize::MAX).is_err());
+        assert!(reader.take_items(usize::MAX / 2, 4).is_err());
+        assert_eq!(reader.position, 1);
+    }
+}
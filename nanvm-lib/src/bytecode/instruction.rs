@@ -0,0 +1,199 @@
+/// A decoded bytecode instruction.
+///
+/// Instructions don't contain pointers, so the code is position independent and can be hashed and
+/// stored as is. In the encoded form an instruction is an opcode byte followed by its operands,
+/// each operand is an unsigned LEB128 number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// Pushes a value from the constant pool.
+    Const(u32),
+    /// Pushes an argument of the current call.
+    Arg(u32),
+    /// Pushes a local of the current call.
+    Local(u32),
+    /// Pops a cell and makes it the next local of the current call.
+    Define,
+    /// Pushes a value captured by the function value of the current call.
+    Capture(u32),
+    /// Pops `n` items and pushes an array of them.
+    Array(u32),
+    /// Pops `n` key-value pairs and pushes an object of them. Keys have to be strings.
+    Object(u32),
+    /// Pops `args` arguments, calls the intrinsic `id` and pushes its result.
+    Intrinsic { id: u32, args: u32 },
+    /// Calls the bytecode function `function`, taking `args` cells from the top of the stack as
+    /// its arguments.
+    Call { function: u32, args: u32 },
+    /// Pops `captures` cells and pushes a function value of the bytecode function `function` that
+    /// captures them.
+    Closure { function: u32, captures: u32 },
+    /// Pops a function value and calls it, taking `args` cells from the top of the stack as its
+    /// arguments.
+    CallValue { args: u32 },
+    /// Pops the result, removes the cells of the current call and pushes the result back.
+    Return,
+}
+
+const CONST: u8 = 0;
+const ARG: u8 = 1;
+const LOCAL: u8 = 2;
+const ARRAY: u8 = 3;
+const OBJECT: u8 = 4;
+const INTRINSIC: u8 = 5;
+const CALL: u8 = 6;
+const RETURN: u8 = 7;
+const DEFINE: u8 = 8;
+const CAPTURE: u8 = 9;
+const CLOSURE: u8 = 10;
+const CALL_VALUE: u8 = 11;
+
+pub fn write_u32(code: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            code.push(byte);
+            return;
+        }
+        code.push(byte | 0x80);
+    }
+}
+
+/// Reads a number at `ip` and moves `ip` past it. Returns `None` if the code ends in the middle of
+/// the number or the number doesn't fit `u32`.
+pub fn read_u32(code: &[u8], ip: &mut usize) -> Option<u32> {
+    let mut result = 0u32;
+    let mut shift = 0;
+    loop {
+        let byte = *code.get(*ip)?;
+        *ip += 1;
+        let bits = (byte & 0x7F) as u32;
+        if shift == 28 && bits > 0xF {
+            return None;
+        }
+        result |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+        shift += 7;
+        if shift > 28 {
+            return None;
+        }
+    }
+}
+
+impl Instruction {
+    pub fn write(&self, code: &mut Vec<u8>) {
+        let (opcode, operands): (_, &[u32]) = match self {
+            Instruction::Const(i) => (CONST, &[*i]),
+            Instruction::Arg(i) => (ARG, &[*i]),
+            Instruction::Local(i) => (LOCAL, &[*i]),
+            Instruction::Define => (DEFINE, &[]),
+            Instruction::Capture(i) => (CAPTURE, &[*i]),
+            Instruction::Array(n) => (ARRAY, &[*n]),
+            Instruction::Object(n) => (OBJECT, &[*n]),
+            Instruction::Intrinsic { id, args } => (INTRINSIC, &[*id, *args]),
+            Instruction::Call { function, args } => (CALL, &[*function, *args]),
+            Instruction::Closure { function, captures } => (CLOSURE, &[*function, *captures]),
+            Instruction::CallValue { args } => (CALL_VALUE, &[*args]),
+            Instruction::Return => (RETURN, &[]),
+        };
+        code.push(opcode);
+        for operand in operands {
+            write_u32(code, *operand);
+        }
+    }
+
+    /// Decodes the instruction at `ip` and moves `ip` to the next one.
+    pub fn read(code: &[u8], ip: &mut usize) -> Option<Self> {
+        let opcode = *code.get(*ip)?;
+        *ip += 1;
+        let mut operand = || read_u32(code, ip);
+        Some(match opcode {
+            CONST => Instruction::Const(operand()?),
+            ARG => Instruction::Arg(operand()?),
+            LOCAL => Instruction::Local(operand()?),
+            DEFINE => Instruction::Define,
+            CAPTURE => Instruction::Capture(operand()?),
+            ARRAY => Instruction::Array(operand()?),
+            OBJECT => Instruction::Object(operand()?),
+            INTRINSIC => Instruction::Intrinsic {
+                id: operand()?,
+                args: operand()?,
+            },
+            CALL => Instruction::Call {
+                function: operand()?,
+                args: operand()?,
+            },
+            CLOSURE => Instruction::Closure {
+                function: operand()?,
+                captures: operand()?,
+            },
+            CALL_VALUE => Instruction::CallValue { args: operand()? },
+            RETURN => Instruction::Return,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{read_u32, write_u32, Instruction};
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_u32() {
+        for (value, len) in [(0, 1), (0x7F, 1), (0x80, 2), (0x3FFF, 2), (u32::MAX, 5)] {
+            let mut code = Vec::new();
+            write_u32(&mut code, value);
+            assert_eq!(code.len(), len);
+            let mut ip = 0;
+            assert_eq!(read_u32(&code, &mut ip), Some(value));
+            assert_eq!(ip, len);
+        }
+        let mut ip = 0;
+        assert_eq!(read_u32(&[0x80], &mut ip), None);
+        let mut ip = 0;
+        assert_eq!(read_u32(&[0xFF, 0xFF, 0xFF, 0xFF, 0x1F], &mut ip), None);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_instructions() {
+        let instructions = [
+            Instruction::Const(0),
+            Instruction::Arg(1),
+            Instruction::Local(300),
+            Instruction::Define,
+            Instruction::Capture(4),
+            Instruction::Array(2),
+            Instruction::Object(1),
+            Instruction::Intrinsic { id: 3, args: 2 },
+            Instruction::Call {
+                function: 1,
+                args: 0,
+            },
+            Instruction::Closure {
+                function: 2,
+                captures: 1,
+            },
+            Instruction::CallValue { args: 3 },
+            Instruction::Return,
+        ];
+        let mut code = Vec::new();
+        for i in &instructions {
+            i.write(&mut code);
+        }
+        assert_eq!(code.len(), 26);
+        let mut ip = 0;
+        for i in &instructions {
+            assert_eq!(Instruction::read(&code, &mut ip).as_ref(), Some(i));
+        }
+        assert_eq!(ip, code.len());
+        assert_eq!(Instruction::read(&code, &mut ip), None);
+        assert_eq!(Instruction::read(&[42], &mut 0), None);
+        assert_eq!(Instruction::read(&[5, 3], &mut 0), None);
+    }
+}
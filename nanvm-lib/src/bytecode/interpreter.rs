@@ -0,0 +1,568 @@
+use crate::{
+    ast::eval::make_object,
+    js::{
+        any::Any,
+        js_array::new_array,
+        js_function::{new_function, JsFunctionRef},
+        js_string::JsStringRef,
+    },
+    mem::manager::{Dealloc, Manager},
+};
+
+use super::{instruction::Instruction, ProgramRef};
+
+#[derive(Debug, PartialEq)]
+pub enum RuntimeError {
+    /// The number of in-flight calls reached `InterpreterParameters::max_call_depth`.
+    CallDepthLimit,
+    /// The code at the offset is not a valid instruction.
+    InvalidInstruction(usize),
+    /// An instruction takes more cells than the current call has on the stack.
+    StackUnderflow,
+    ConstRef(u32),
+    ArgRef(u32),
+    LocalRef(u32),
+    CaptureRef(u32),
+    UnknownFunction(u32),
+    UnknownIntrinsic(u32),
+    /// The callee of `CallValue` is not a function value.
+    NotAFunction,
+    /// An object key is not a string.
+    InvalidKey,
+}
+
+/// Native functions provided by the host of the interpreter.
+pub trait Intrinsics<M: Manager> {
+    /// Calls the intrinsic `id`. The arguments are the cells taken from the top of the data stack.
+    fn call(
+        &self,
+        manager: M,
+        id: u32,
+        args: &[Any<M::Dealloc>],
+    ) -> Result<Any<M::Dealloc>, RuntimeError>;
+}
+
+/// No intrinsics.
+impl<M: Manager> Intrinsics<M> for () {
+    fn call(
+        &self,
+        _manager: M,
+        id: u32,
+        _args: &[Any<M::Dealloc>],
+    ) -> Result<Any<M::Dealloc>, RuntimeError> {
+        Err(RuntimeError::UnknownIntrinsic(id))
+    }
+}
+
+pub struct InterpreterParameters {
+    /// The maximum number of in-flight calls, including the call of the entry point. It stops
+    /// infinite recursion.
+    pub max_call_depth: usize,
+}
+
+impl Default for InterpreterParameters {
+    fn default() -> Self {
+        Self {
+            max_call_depth: 1024,
+        }
+    }
+}
+
+struct Frame<D: Dealloc> {
+    /// Where the caller continues, `None` for the entry point.
+    return_ip: Option<usize>,
+    /// The program with the code of the call.
+    program: ProgramRef<D>,
+    /// The function value of the call, `None` for a call by index.
+    closure: Option<JsFunctionRef<D>>,
+    /// Index of the first argument on the data stack.
+    base: usize,
+    args: usize,
+    /// Index of the first local on the locals stack.
+    locals: usize,
+}
+
+pub struct Interpreter<'a, M: Manager, I: Intrinsics<M>> {
+    manager: M,
+    program: ProgramRef<M::Dealloc>,
+    intrinsics: &'a I,
+    parameters: InterpreterParameters,
+}
+
+impl<'a, M: Manager, I: Intrinsics<M>> Interpreter<'a, M, I> {
+    pub fn new(
+        manager: M,
+        program: ProgramRef<M::Dealloc>,
+        intrinsics: &'a I,
+        parameters: InterpreterParameters,
+    ) -> Self {
+        Interpreter {
+            manager,
+            program,
+            intrinsics,
+            parameters,
+        }
+    }
+
+    /// Calls the entry point of the program.
+    pub fn run(&self, args: &[Any<M::Dealloc>]) -> Result<Any<M::Dealloc>, RuntimeError> {
+        self.call(0, args)
+    }
+
+    pub fn call(
+        &self,
+        function: u32,
+        args: &[Any<M::Dealloc>],
+    ) -> Result<Any<M::Dealloc>, RuntimeError> {
+        self.execute(self.program.clone(), function, None, args)
+    }
+
+    /// Calls a function value, e.g. a function exported by a module. It runs in the program it
+    /// refers to, which doesn't have to be the program of the interpreter.
+    pub fn call_value(
+        &self,
+        closure: JsFunctionRef<M::Dealloc>,
+        args: &[Any<M::Dealloc>],
+    ) -> Result<Any<M::Dealloc>, RuntimeError> {
+        let program = closure.program().clone();
+        self.execute(program, closure.function(), Some(closure), args)
+    }
+
+    fn execute(
+        &self,
+        program: ProgramRef<M::Dealloc>,
+        function: u32,
+        closure: Option<JsFunctionRef<M::Dealloc>>,
+        args: &[Any<M::Dealloc>],
+    ) -> Result<Any<M::Dealloc>, RuntimeError> {
+        let mut stack = args.to_vec();
+        let mut locals: Vec<Any<M::Dealloc>> = Vec::new();
+        let mut frames = Vec::new();
+        let frame = Frame {
+            return_ip: None,
+            program,
+            closure,
+            base: 0,
+            args: args.len(),
+            locals: 0,
+        };
+        let mut ip = self.enter(&mut frames, function, frame)?;
+        loop {
+            let at = ip;
+            let frame = frames.last().unwrap();
+            let program = &frame.program.0;
+            let instruction = Instruction::read(&program.code, &mut ip)
+                .ok_or(RuntimeError::InvalidInstruction(at))?;
+            match instruction {
+                Instruction::Const(i) => stack.push(
+                    program
+                        .constants
+                        .get(i as usize)
+                        .ok_or(RuntimeError::ConstRef(i))?
+                        .clone(),
+                ),
+                Instruction::Arg(i) => {
+                    if i as usize >= frame.args {
+                        return Err(RuntimeError::ArgRef(i));
+                    }
+                    stack.push(stack[frame.base + i as usize].clone());
+                }
+                Instruction::Local(i) => {
+                    // The locals of callers are below `frame.locals`, and callees have returned.
+                    let local = locals[frame.locals..]
+                        .get(i as usize)
+                        .ok_or(RuntimeError::LocalRef(i))?;
+                    stack.push(local.clone());
+                }
+                Instruction::Define => {
+                    let local = pop(&mut stack, frame, 1)?.pop().unwrap();
+                    locals.push(local);
+                }
+                Instruction::Capture(i) => {
+                    let capture = frame
+                        .closure
+                        .as_ref()
+                        .and_then(|closure| closure.items().get(i as usize))
+                        .ok_or(RuntimeError::CaptureRef(i))?;
+                    stack.push(capture.clone());
+                }
+                Instruction::Array(n) => {
+                    let items = pop(&mut stack, frame, n as usize)?;
+                    stack.push(Any::move_from(new_array(self.manager, items).to_ref()));
+                }
+                Instruction::Object(n) => {
+                    let count = (n as usize)
+                        .checked_mul(2)
+                        .ok_or(RuntimeError::StackUnderflow)?;
+                    let cells = pop(&mut stack, frame, count)?;
+                    let mut properties = Vec::with_capacity(n as usize);
+                    let mut cells = cells.into_iter();
+                    while let (Some(k), Some(v)) = (cells.next(), cells.next()) {
+                        let k = k
+                            .try_move::<JsStringRef<M::Dealloc>>()
+                            .map_err(|_| RuntimeError::InvalidKey)?;
+                        properties.push((k, v));
+                    }
+                    stack.push(make_object(self.manager, properties));
+                }
+                Instruction::Intrinsic { id, args } => {
+                    let args = pop(&mut stack, frame, args as usize)?;
+                    stack.push(self.intrinsics.call(self.manager, id, &args)?);
+                }
+                Instruction::Call { function, args } => {
+                    let args = args as usize;
+                    if stack.len() < frame.base + args {
+                        return Err(RuntimeError::StackUnderflow);
+                    }
+                    let frame = Frame {
+                        return_ip: Some(ip),
+                        program: frame.program.clone(),
+                        closure: None,
+                        base: stack.len() - args,
+                        args,
+                        locals: locals.len(),
+                    };
+                    ip = self.enter(&mut frames, function, frame)?;
+                }
+                Instruction::Closure { function, captures } => {
+                    let captures = pop(&mut stack, frame, captures as usize)?;
+                    let program = frame.program.clone();
+                    let closure = new_function(self.manager, program, function, captures);
+                    stack.push(Any::move_from(closure.to_ref()));
+                }
+                Instruction::CallValue { args } => {
+                    let closure = pop(&mut stack, frame, 1)?
+                        .pop()
+                        .unwrap()
+                        .try_move::<JsFunctionRef<M::Dealloc>>()
+                        .map_err(|_| RuntimeError::NotAFunction)?;
+                    let args = args as usize;
+                    if stack.len() < frame.base + args {
+                        return Err(RuntimeError::StackUnderflow);
+                    }
+                    let function = closure.function();
+                    let frame = Frame {
+                        return_ip: Some(ip),
+                        program: closure.program().clone(),
+                        closure: Some(closure),
+                        base: stack.len() - args,
+                        args,
+                        locals: locals.len(),
+                    };
+                    ip = self.enter(&mut frames, function, frame)?;
+                }
+                Instruction::Return => {
+                    let result = pop(&mut stack, frame, 1)?.pop().unwrap();
+                    let frame = frames.pop().unwrap();
+                    stack.truncate(frame.base);
+                    locals.truncate(frame.locals);
+                    match frame.return_ip {
+                        None => return Ok(result),
+                        Some(return_ip) => {
+                            stack.push(result);
+                            ip = return_ip;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Pushes `frame` for a call of `function` and returns the offset of its code.
+    fn enter(
+        &self,
+        frames: &mut Vec<Frame<M::Dealloc>>,
+        function: u32,
+        frame: Frame<M::Dealloc>,
+    ) -> Result<usize, RuntimeError> {
+        if frames.len() >= self.parameters.max_call_depth {
+            return Err(RuntimeError::CallDepthLimit);
+        }
+        let offset = *frame
+            .program
+            .0
+            .functions
+            .get(function as usize)
+            .ok_or(RuntimeError::UnknownFunction(function))?;
+        frames.push(frame);
+        Ok(offset as usize)
+    }
+}
+
+/// Takes `n` cells from the top of the stack, without touching the cells of callers.
+fn pop<D: Dealloc>(
+    stack: &mut Vec<Any<D>>,
+    frame: &Frame<D>,
+    n: usize,
+) -> Result<Vec<Any<D>>, RuntimeError> {
+    if stack.len() < frame.base + n {
+        return Err(RuntimeError::StackUnderflow);
+    }
+    Ok(stack.split_off(stack.len() - n))
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        ast::{Body, Expression, Function},
+        bytecode::{compiler::compile, instruction::Instruction, Program},
+        js::{any::Any, js_array::JsArrayRef, js_function::JsFunctionRef},
+        mem::{
+            global::{Global, GLOBAL},
+            manager::Manager,
+        },
+        parser::shared::to_js_string,
+        serializer::to_json::to_json,
+    };
+
+    use super::{Interpreter, InterpreterParameters, Intrinsics, RuntimeError};
+
+    fn program(functions: &[&[Instruction]]) -> Program<Global> {
+        let mut program = Program::default();
+        for function in functions {
+            program.functions.push(program.code.len() as u32);
+            for i in *function {
+                i.write(&mut program.code);
+            }
+        }
+        program
+    }
+
+    /// Intrinsic `0` returns the number of its arguments.
+    struct Count;
+
+    impl<M: Manager> Intrinsics<M> for Count {
+        fn call(
+            &self,
+            _manager: M,
+            id: u32,
+            args: &[Any<M::Dealloc>],
+        ) -> Result<Any<M::Dealloc>, RuntimeError> {
+            match id {
+                0 => Ok(Any::move_from(args.len() as f64)),
+                _ => Err(RuntimeError::UnknownIntrinsic(id)),
+            }
+        }
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_compiled() {
+        let key = |s: &str| to_js_string(GLOBAL, s.to_owned());
+        let body = Body {
+            local: [Expression::Array(
+                [
+                    Expression::Value(Any::move_from(1.0)),
+                    Expression::ArgRef(0),
+                ]
+                .into(),
+            )]
+            .into(),
+            result: Expression::Object(
+                [
+                    (key("b"), Expression::LocalRef(0)),
+                    (key("a"), Expression::ArgRef(1)),
+                ]
+                .into(),
+            ),
+        };
+        let program = compile(&body).unwrap();
+        let interpreter = Interpreter::new(GLOBAL, program.share(GLOBAL), &(), Default::default());
+        let args = [Any::move_from(true), Any::move_from(key("x"))];
+        let result = interpreter.run(&args).unwrap();
+        assert_eq!(to_json(result).unwrap(), r#"{"b":[1,true],"a":"x"}"#);
+        assert_eq!(
+            interpreter.run(&args[..1]).unwrap_err(),
+            RuntimeError::ArgRef(1)
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_calls() {
+        let program = program(&[
+            &[
+                Instruction::Arg(0),
+                Instruction::Arg(0),
+                Instruction::Call {
+                    function: 1,
+                    args: 2,
+                },
+                Instruction::Define,
+                Instruction::Local(0),
+                Instruction::Local(0),
+                Instruction::Array(2),
+                Instruction::Return,
+            ],
+            &[
+                Instruction::Arg(1),
+                Instruction::Arg(0),
+                Instruction::Intrinsic { id: 0, args: 2 },
+                Instruction::Array(2),
+                Instruction::Return,
+            ],
+        ]);
+        let interpreter =
+            Interpreter::new(GLOBAL, program.share(GLOBAL), &Count, Default::default());
+        let result = interpreter.run(&[Any::move_from(5.0)]).unwrap();
+        assert_eq!(to_json(result).unwrap(), "[[5,2],[5,2]]");
+        assert_eq!(
+            interpreter.call(1, &[]).unwrap_err(),
+            RuntimeError::ArgRef(1)
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_closures() {
+        // const k = x => y => [x, y, m]
+        // export default [k(1)(2), k(3)]
+        let k = Expression::Function(Box::new(Function {
+            captures: [Expression::ArgRef(0)].into(),
+            params: 1,
+            body: Body {
+                local: [].into(),
+                result: Expression::Function(Box::new(Function {
+                    captures: [Expression::ArgRef(0), Expression::CaptureRef(0)].into(),
+                    params: 1,
+                    body: Body {
+                        local: [].into(),
+                        result: Expression::Array(
+                            [
+                                Expression::CaptureRef(0),
+                                Expression::ArgRef(0),
+                                Expression::CaptureRef(1),
+                            ]
+                            .into(),
+                        ),
+                    },
+                })),
+            },
+        }));
+        let call = |callee, arg: f64| {
+            Expression::Call(
+                Box::new(callee),
+                [Expression::Value(Any::move_from(arg))].into(),
+                Default::default(),
+            )
+        };
+        let body = Body {
+            local: [k].into(),
+            result: Expression::Array(
+                [
+                    call(call(Expression::LocalRef(0), 1.0), 2.0),
+                    call(Expression::LocalRef(0), 3.0),
+                ]
+                .into(),
+            ),
+        };
+        let program = compile(&body).unwrap();
+        assert_eq!(program.functions.len(), 3);
+        let interpreter = Interpreter::new(GLOBAL, program.share(GLOBAL), &(), Default::default());
+        let result = interpreter
+            .run(&[Any::move_from(to_js_string(GLOBAL, "m".to_owned()))])
+            .unwrap()
+            .try_move::<JsArrayRef<Global>>()
+            .unwrap();
+        assert_eq!(to_json(result.items()[0].clone()).unwrap(), r#"[1,2,"m"]"#);
+        // A function value returned by the program can be called by the host.
+        let f = result.items()[1]
+            .clone()
+            .try_move::<JsFunctionRef<Global>>()
+            .unwrap();
+        let result = interpreter
+            .call_value(f.clone(), &[Any::move_from(4.0)])
+            .unwrap();
+        assert_eq!(to_json(result).unwrap(), r#"[3,4,"m"]"#);
+        // It keeps its program, so it runs after the interpreter is gone, in any other one.
+        drop(interpreter);
+        let other = Interpreter::new(
+            GLOBAL,
+            Program::default().share(GLOBAL),
+            &(),
+            Default::default(),
+        );
+        let result = other.call_value(f, &[Any::move_from(5.0)]).unwrap();
+        assert_eq!(to_json(result).unwrap(), r#"[3,5,"m"]"#);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_call_depth_limit() {
+        let program = program(&[&[
+            Instruction::Call {
+                function: 0,
+                args: 0,
+            },
+            Instruction::Return,
+        ]]);
+        let interpreter = Interpreter::new(
+            GLOBAL,
+            program.share(GLOBAL),
+            &(),
+            InterpreterParameters { max_call_depth: 10 },
+        );
+        assert_eq!(
+            interpreter.run(&[]).unwrap_err(),
+            RuntimeError::CallDepthLimit
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_errors() {
+        let run = |functions: &[&[Instruction]]| {
+            let program = program(functions);
+            Interpreter::new(GLOBAL, program.share(GLOBAL), &(), Default::default())
+                .run(&[])
+                .unwrap_err()
+        };
+        assert_eq!(
+            run(&[&[Instruction::Array(1)]]),
+            RuntimeError::StackUnderflow
+        );
+        assert_eq!(run(&[&[Instruction::Return]]), RuntimeError::StackUnderflow);
+        assert_eq!(
+            run(&[&[Instruction::Object(u32::MAX)]]),
+            RuntimeError::StackUnderflow
+        );
+        assert_eq!(run(&[&[Instruction::Const(0)]]), RuntimeError::ConstRef(0));
+        assert_eq!(run(&[&[Instruction::Local(0)]]), RuntimeError::LocalRef(0));
+        // A temporary cell isn't a local until `Define` moves it.
+        assert_eq!(
+            run(&[&[Instruction::Array(0), Instruction::Local(0)]]),
+            RuntimeError::LocalRef(0)
+        );
+        assert_eq!(run(&[&[Instruction::Define]]), RuntimeError::StackUnderflow);
+        assert_eq!(
+            run(&[&[Instruction::Capture(0)]]),
+            RuntimeError::CaptureRef(0)
+        );
+        assert_eq!(
+            run(&[&[Instruction::Array(0), Instruction::CallValue { args: 0 }]]),
+            RuntimeError::NotAFunction
+        );
+        assert_eq!(
+            run(&[&[Instruction::Intrinsic { id: 7, args: 0 }]]),
+            RuntimeError::UnknownIntrinsic(7)
+        );
+        assert_eq!(
+            run(&[&[Instruction::Call {
+                function: 1,
+                args: 0
+            }]]),
+            RuntimeError::UnknownFunction(1)
+        );
+        assert_eq!(
+            run(&[&[
+                Instruction::Array(0),
+                Instruction::Array(0),
+                Instruction::Object(1)
+            ]]),
+            RuntimeError::InvalidKey
+        );
+        assert_eq!(run(&[&[]]), RuntimeError::InvalidInstruction(0));
+    }
+}
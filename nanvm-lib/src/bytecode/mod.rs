@@ -0,0 +1,44 @@
+pub mod compiler;
+pub mod container;
+pub mod instruction;
+pub mod interpreter;
+
+use crate::{
+    js::any::Any,
+    mem::{
+        fixed::Fixed,
+        manager::{Dealloc, Manager},
+        ref_::Ref,
+    },
+};
+
+/// Compiled bytecode, see `notes/bytecode.md`.
+///
+/// The code of all functions is placed in one continuous array. Values are not embedded into the
+/// code, instructions refer to them by an index in `constants`.
+pub struct Program<D: Dealloc> {
+    pub constants: Vec<Any<D>>,
+    /// Offsets of the functions in `code`. The first function is the entry point.
+    pub functions: Vec<u32>,
+    pub code: Vec<u8>,
+}
+
+/// A program allocated by a memory manager, shared by the function values that refer to it.
+pub type ProgramRef<D> = Ref<Fixed<Program<D>>, D>;
+
+impl<D: Dealloc> Program<D> {
+    /// Moves the program to `manager`, so function values can refer to it.
+    pub fn share<M: Manager<Dealloc = D>>(self, manager: M) -> ProgramRef<D> {
+        manager.new(Fixed(self)).to_ref()
+    }
+}
+
+impl<D: Dealloc> Default for Program<D> {
+    fn default() -> Self {
+        Self {
+            constants: Vec::new(),
+            functions: Vec::new(),
+            code: Vec::new(),
+        }
+    }
+}
@@ -3,5 +3,6 @@ pub mod bit_subset64;
 pub mod cast;
 pub mod default;
 pub mod ref_mut;
+pub mod sha256;
 pub mod usize;
 pub mod vec;
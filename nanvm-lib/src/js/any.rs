@@ -3,12 +3,15 @@ use crate::mem::{block::Block, manager::Dealloc, optional_ref::OptionalRef};
 use super::{
     any_cast::AnyCast,
     any_internal::AnyInternal,
-    bitset::{ref_type, REF_SUBSET_SUPERPOSITION},
-    js_array::JsArrayRef,
-    js_object::JsObjectRef,
+    bitset::{ref_type, FUNCTION, REF_SUBSET_SUPERPOSITION},
+    js_array::JsArray,
+    js_object::{array_index, JsObject, Property},
+    js_string::JsStringRef,
     null::Null,
+    persistent::{JsPersistent, Persistent},
     ref_cast::RefCast,
     type_::Type,
+    undefined::Undefined,
 };
 
 pub type Any<D> = OptionalRef<AnyInternal<D>>;
@@ -49,8 +52,16 @@ impl<D: Dealloc> Any<D> {
     }
     //
     pub fn get_type(&self) -> Type {
-        if self.is_ref() {
-            match ref_type(unsafe { self.internal().0 }) {
+        let v = unsafe { self.u64() };
+        if FUNCTION.has(v) {
+            Type::Function
+        } else if let Some(p) = self.persistent() {
+            match p {
+                Persistent::Array(_) => Type::Array,
+                Persistent::Object(_) => Type::Object,
+            }
+        } else if self.is_ref() {
+            match ref_type(v) {
                 0b00 => Type::String,
                 0b01 => Type::Object,
                 0b10 => Type::Array,
@@ -61,6 +72,8 @@ impl<D: Dealloc> Any<D> {
             Type::Number
         } else if self.is::<Null>() {
             Type::Null
+        } else if self.is::<Undefined>() {
+            Type::Undefined
         } else {
             Type::Bool
         }
@@ -91,27 +104,105 @@ impl<D: Dealloc> Any<D> {
         Err(())
     }
 
+    fn persistent(&self) -> Option<&Persistent<D>> {
+        Some(&self.try_ref::<JsPersistent<D>>().ok()?.object().0)
+    }
+
+    /// The properties of an object in the property order, whether it's a plain or a persistent
+    /// one.
+    pub fn properties(&self) -> Option<Box<dyn Iterator<Item = &Property<D>> + '_>> {
+        if let Ok(o) = self.try_ref::<JsObject<D>>() {
+            return Some(Box::new(o.object().items().iter()));
+        }
+        match self.persistent()? {
+            Persistent::Object(o) => Some(Box::new(o.iter())),
+            Persistent::Array(_) => None,
+        }
+    }
+
+    /// The items of an array, whether it's a plain or a persistent one.
+    pub fn items(&self) -> Option<Box<dyn Iterator<Item = &Any<D>> + '_>> {
+        if let Ok(a) = self.try_ref::<JsArray<D>>() {
+            return Some(Box::new(a.object().items().iter()));
+        }
+        match self.persistent()? {
+            Persistent::Array(a) => Some(Box::new(a.iter())),
+            Persistent::Object(_) => None,
+        }
+    }
+
+    /// The value of the property `key` of an object, or the item of an array if `key` is an array
+    /// index. `None` if there is no such property or `self` is neither an object nor an array.
+    pub fn get(&self, key: &[u16]) -> Option<Any<D>> {
+        if let Ok(o) = self.try_ref::<JsObject<D>>() {
+            return o.object().get(key).cloned();
+        }
+        if let Some(Persistent::Object(o)) = self.persistent() {
+            return o.get(key).cloned();
+        }
+        self.get_index(array_index(key)? as usize)
+    }
+
+    /// The item `i` of an array.
+    pub fn get_index(&self, i: usize) -> Option<Any<D>> {
+        if let Some(Persistent::Array(a)) = self.persistent() {
+            return a.get(i).cloned();
+        }
+        self.try_ref::<JsArray<D>>()
+            .ok()?
+            .object()
+            .get_index(i)
+            .cloned()
+    }
+
+    pub fn has(&self, key: &[u16]) -> bool {
+        if let Ok(o) = self.try_ref::<JsObject<D>>() {
+            return o.object().has(key);
+        }
+        if let Some(Persistent::Object(o)) = self.persistent() {
+            return o.has(key);
+        }
+        self.get(key).is_some()
+    }
+
+    /// The number of properties of an object or items of an array.
+    pub fn len(&self) -> Option<usize> {
+        if let Ok(o) = self.try_ref::<JsObject<D>>() {
+            return Some(o.object().len());
+        }
+        if let Ok(a) = self.try_ref::<JsArray<D>>() {
+            return Some(a.object().len());
+        }
+        match self.persistent()? {
+            Persistent::Array(a) => Some(a.len()),
+            Persistent::Object(o) => Some(o.len()),
+        }
+    }
+
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    /// The keys of an object in the property order.
+    pub fn keys(&self) -> Option<Vec<JsStringRef<D>>> {
+        Some(self.properties()?.map(|(k, _)| k.clone()).collect())
+    }
+
     /// Iterate trough children of this `Any`. Any type other than array / object yields nothing.
     /// For an object, first argument of `f` is the key string. For an array, it is the index.
     pub fn for_each<E>(
         &self,
         mut f: impl FnMut(/*k*/ Any<D>, /*v*/ &Any<D>) -> Result<(), E>,
     ) -> Result<(), E> {
-        match self.get_type() {
-            Type::Object => {
-                let o = self.clone().try_move::<JsObjectRef<D>>().unwrap();
-                for (k, v) in o.items() {
-                    f(k.clone().move_to_any(), v)?;
-                }
+        if let Some(properties) = self.properties() {
+            for (k, v) in properties {
+                f(k.clone().move_to_any(), v)?;
             }
-            Type::Array => {
-                let o = self.clone().try_move::<JsArrayRef<D>>().unwrap();
-                for (k, v) in o.items().iter().enumerate() {
-                    f((k as f64).move_to_any(), v)?;
-                }
+        } else if let Some(items) = self.items() {
+            for (k, v) in items.enumerate() {
+                f((k as f64).move_to_any(), v)?;
             }
-            _ => {}
-        };
+        }
         Ok(())
     }
 }
@@ -123,14 +214,16 @@ mod test {
     use wasm_bindgen_test::wasm_bindgen_test;
 
     use crate::{
+        bytecode::Program,
         js::{
             js_array::{new_array, JsArrayRef},
             js_bigint::{new_bigint, JsBigintRef, Sign},
+            js_function::{new_function, JsFunction, JsFunctionRef},
             js_object::{new_object, JsObjectRef},
             js_string::{new_string, JsString, JsStringRef},
             null::Null,
         },
-        mem::global::Global,
+        mem::{global::Global, local::Local},
     };
 
     use super::*;
@@ -184,6 +277,19 @@ mod test {
         assert!(!A::move_from(false).is::<Null>());
     }
 
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_undefined() {
+        type A = Any<Global>;
+        assert!(A::move_from(Undefined()).is::<Undefined>());
+        //
+        assert!(!A::move_from(Undefined()).is::<Null>());
+        assert!(!A::move_from(Undefined()).is::<bool>());
+        assert!(!A::move_from(Null()).is::<Undefined>());
+        assert!(!A::move_from(false).is::<Undefined>());
+        assert!(!A::move_from(f64::NAN).is::<Undefined>());
+    }
+
     #[test]
     #[wasm_bindgen_test]
     fn test_type() {
@@ -191,6 +297,7 @@ mod test {
         assert_eq!(A::move_from(15.0).get_type(), Type::Number);
         assert_eq!(A::move_from(true).get_type(), Type::Bool);
         assert_eq!(A::move_from(Null()).get_type(), Type::Null);
+        assert_eq!(A::move_from(Undefined()).get_type(), Type::Undefined);
     }
 
     #[test]
@@ -299,6 +406,79 @@ mod test {
         }
     }
 
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_function() {
+        type A = Any<Global>;
+        type FunctionRef = JsFunctionRef<Global>;
+        assert!(!A::move_from(Null()).is::<FunctionRef>());
+        assert!(!A::move_from(true).is::<FunctionRef>());
+        assert!(!A::move_from(15.0).is::<FunctionRef>());
+
+        let s = new_string(Global(), [0x20]).to_ref();
+        let program = Program::default().share(Global());
+        let f: FunctionRef =
+            new_function(Global(), program, 3, [A::move_from(s), A::move_from(1.0)]).to_ref();
+        let u = A::move_from(f);
+        assert!(u.is_ref());
+        assert_eq!(u.get_type(), Type::Function);
+        assert!(!u.is::<JsStringRef<Global>>());
+        assert!(!u.is::<Null>());
+        {
+            let f = u.try_ref::<JsFunction<Global>>().unwrap().object();
+            assert_eq!(f.function(), 3);
+            assert_eq!(f.items().len(), 2);
+            assert_eq!(f.items()[0].get_type(), Type::String);
+        }
+        let f = u.try_move::<FunctionRef>().unwrap();
+        assert_eq!(f.items()[1].clone().try_move(), Ok(1.0));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_function_drop() {
+        let local = Local::default();
+        {
+            let s = new_string(&local, [0x20]).to_ref();
+            let program = Program::default().share(&local);
+            let f = new_function(&local, program, 0, [Any::move_from(s)]).to_ref();
+            let _u = Any::move_from(f);
+            assert_ne!(local.size(), 0);
+        }
+        assert_eq!(local.size(), 0);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_accessors() {
+        type A = Any<Global>;
+        let key = |s: &str| s.encode_utf16().collect::<Vec<_>>();
+        let k = new_string(Global(), key("k")).to_ref();
+        let array =
+            A::move_from(new_array(Global(), [A::move_from(1.0), A::move_from(true)]).to_ref());
+        let object = A::move_from(new_object(Global(), [(k, array.clone())]).to_ref());
+        assert_eq!(object.len(), Some(1));
+        assert_eq!(object.is_empty(), Some(false));
+        assert!(object.has(&key("k")));
+        assert!(!object.has(&key("0")));
+        assert_eq!(object.get(&key("k")), Some(array.clone()));
+        assert_eq!(object.get(&key("x")), None);
+        assert_eq!(object.get_index(0), None);
+        assert_eq!(object.keys().unwrap()[0].items(), key("k"));
+
+        assert_eq!(array.len(), Some(2));
+        assert_eq!(array.get_index(1).unwrap().try_move(), Ok(true));
+        assert_eq!(array.get_index(2), None);
+        assert_eq!(array.get(&key("0")).unwrap().try_move(), Ok(1.0));
+        assert_eq!(array.get(&key("00")), None);
+        assert!(array.has(&key("1")));
+        assert!(!array.has(&key("2")));
+        assert!(array.keys().is_none());
+
+        assert_eq!(A::move_from(1.0).len(), None);
+        assert_eq!(A::move_from(Null()).get(&key("k")), None);
+    }
+
     #[test]
     #[wasm_bindgen_test]
     fn test_eq() {
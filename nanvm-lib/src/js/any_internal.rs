@@ -11,10 +11,13 @@ use crate::{
 };
 
 use super::{
-    bitset::{ref_type, REF, REF_SUBSET_SUPERPOSITION},
+    bitset::{is_ref, ref_type, FUNCTION, PERSISTENT, REF_SUBSET_SUPERPOSITION},
     js_array::JsArray,
+    js_bigint::JsBigint,
+    js_function::JsFunction,
     js_object::JsObject,
     js_string::JsString,
+    persistent::JsPersistent,
 };
 
 #[repr(transparent)]
@@ -54,7 +57,7 @@ impl<D: Dealloc> OptionalBlock for AnyInternal<D> {
     type BlockHeader = D::BlockHeader;
     #[inline(always)]
     fn is_ref(self) -> bool {
-        REF.has(self.0)
+        is_ref(self.0)
     }
     #[inline(always)]
     unsafe fn try_get_block_header(self) -> Option<*const Self::BlockHeader> {
@@ -67,10 +70,17 @@ impl<D: Dealloc> OptionalBlock for AnyInternal<D> {
     #[inline(always)]
     unsafe fn delete(self, block_header: *mut Self::BlockHeader) {
         let p = &mut *block_header;
+        if FUNCTION.has(self.0) {
+            return p.block::<JsFunction<D>, D>().delete();
+        }
+        if PERSISTENT.has(self.0) {
+            return p.block::<JsPersistent<D>, D>().delete();
+        }
         match ref_type(self.0) {
             bitset::REF_TYPE_STRING => p.block::<JsString, D>().delete(),
             bitset::REF_TYPE_OBJECT => p.block::<JsObject<D>, D>().delete(),
             bitset::REF_TYPE_ARRAY => p.block::<JsArray<D>, D>().delete(),
+            bitset::REF_TYPE_BIGINT => p.block::<JsBigint, D>().delete(),
             _ => unreachable!(),
         }
     }
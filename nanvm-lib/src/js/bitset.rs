@@ -1,6 +1,6 @@
 use crate::common::bit_subset64::BitSubset64;
 
-use super::null::Null;
+use super::{null::Null, undefined::Undefined};
 
 // EXTENSION: 1111_1111_1111_1.X.X
 
@@ -11,20 +11,40 @@ const EXTENSION_SPLIT: (BitSubset64, BitSubset64) = EXTENSION.split(0x0004_0000_
 pub const SIMPLE: BitSubset64 = EXTENSION_SPLIT.0;
 pub const REF: BitSubset64 = EXTENSION_SPLIT.1;
 
-// SIMPLE: 1111_1111_1111_1.0.X
+// SIMPLE: 1111_1111_1111_1.0.X.X
 
 pub const SIMPLE_SPLIT: (BitSubset64, BitSubset64) = SIMPLE.split(0x0002_0000_0000_0000);
 
-// BOOL: 1111_1111_1111_1.0.0
+const VALUE_SPLIT: (BitSubset64, BitSubset64) = SIMPLE_SPLIT.0.split(0x0001_0000_0000_0000);
 
-pub const BOOL: BitSubset64<bool> = SIMPLE_SPLIT.0.cast();
+// BOOL: 1111_1111_1111_1.0.0.0
+
+pub const BOOL: BitSubset64<bool> = VALUE_SPLIT.0.cast();
 
 pub const FALSE: u64 = BOOL.raw_value_to_subset_value(false as _);
 pub const TRUE: u64 = BOOL.raw_value_to_subset_value(true as _);
 
-// NULL: 1111_1111_1111_1.0.1
+const NULL_SPLIT: (BitSubset64, BitSubset64) = VALUE_SPLIT.1.split(0x0000_8000_0000_0000);
+
+// NULL: 1111_1111_1111_1.0.0.1.0
+
+pub const NULL: BitSubset64<Null> = NULL_SPLIT.0.cast();
+
+// UNDEFINED: 1111_1111_1111_1.0.0.1.1
+
+pub const UNDEFINED: BitSubset64<Undefined> = NULL_SPLIT.1.cast();
+
+// The four `REF` kinds are taken, so the function and persistent references live in `SIMPLE`.
 
-pub const NULL: BitSubset64<Null> = SIMPLE_SPLIT.1.cast();
+const EXTENSION_REF_SPLIT: (BitSubset64, BitSubset64) = SIMPLE_SPLIT.1.split(0x0001_0000_0000_0000);
+
+// FUNCTION: 1111_1111_1111_1.0.1.0
+
+pub const FUNCTION: BitSubset64 = EXTENSION_REF_SPLIT.0;
+
+// PERSISTENT: 1111_1111_1111_1.0.1.1
+
+pub const PERSISTENT: BitSubset64 = EXTENSION_REF_SPLIT.1;
 
 // REF: 1111_1111_1111_1.1.X.X
 
@@ -62,10 +82,17 @@ pub const fn ref_type(v: u64) -> u8 {
     ((v >> 48) & 0b11) as u8
 }
 
+/// `true` if `v` refers to a block, i.e. it's in `REF`, `FUNCTION` or `PERSISTENT`.
+pub const fn is_ref(v: u64) -> bool {
+    REF.has(v) || FUNCTION.has(v) || PERSISTENT.has(v)
+}
+
 #[cfg(test)]
 mod test {
     use crate::js::{
-        bitset::{BOOL, EXTENSION, FALSE, TRUE},
+        bitset::{
+            is_ref, BOOL, EXTENSION, FALSE, FUNCTION, NULL, PERSISTENT, REF, TRUE, UNDEFINED,
+        },
         number::test::NAN,
     };
 
@@ -74,4 +101,17 @@ mod test {
     const _: () = assert!(!BOOL.has(0));
     const _: () = assert!(!BOOL.has(NAN));
     const _: () = assert!(BOOL.has(EXTENSION.mask));
+    const _: () = assert!(!NULL.has(FUNCTION.tag));
+    const _: () = assert!(!REF.has(FUNCTION.tag));
+    const _: () = assert!(is_ref(FUNCTION.tag));
+    const _: () = assert!(!FUNCTION.has(PERSISTENT.tag));
+    const _: () = assert!(!PERSISTENT.has(FUNCTION.tag));
+    const _: () = assert!(!REF.has(PERSISTENT.tag));
+    const _: () = assert!(!UNDEFINED.has(PERSISTENT.tag));
+    const _: () = assert!(is_ref(PERSISTENT.tag));
+    const _: () = assert!(!is_ref(TRUE));
+    const _: () = assert!(!NULL.has(UNDEFINED.tag));
+    const _: () = assert!(!UNDEFINED.has(NULL.tag));
+    const _: () = assert!(!BOOL.has(UNDEFINED.tag));
+    const _: () = assert!(!is_ref(UNDEFINED.tag));
 }
@@ -0,0 +1,142 @@
+use core::{
+    borrow::Borrow,
+    hash::{Hash, Hasher},
+};
+use std::collections::HashSet;
+
+use crate::mem::manager::{Dealloc, Manager};
+
+use super::js_string::{new_string, JsStringRef};
+
+/// A string in an `Interner`, hashed and compared by its contents.
+struct Interned<D: Dealloc>(JsStringRef<D>);
+
+impl<D: Dealloc> Borrow<[u16]> for Interned<D> {
+    fn borrow(&self) -> &[u16] {
+        self.0.items()
+    }
+}
+
+impl<D: Dealloc> Hash for Interned<D> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.items().hash(state)
+    }
+}
+
+impl<D: Dealloc> PartialEq for Interned<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.items() == other.0.items()
+    }
+}
+
+impl<D: Dealloc> Eq for Interned<D> {}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InternerStats {
+    /// The number of distinct strings.
+    pub strings: usize,
+    /// The number of UTF-16 code units in the distinct strings.
+    pub units: usize,
+    /// The number of `intern` calls that returned an existing string.
+    pub hits: usize,
+    /// The number of UTF-16 code units the hits didn't allocate.
+    pub saved_units: usize,
+}
+
+/// Shares one `JsString` between all equal strings, e.g. repeated object keys in a parse session.
+///
+/// The interner keeps its strings alive until it's dropped.
+pub struct Interner<D: Dealloc> {
+    strings: HashSet<Interned<D>>,
+    stats: InternerStats,
+}
+
+impl<D: Dealloc> Default for Interner<D> {
+    fn default() -> Self {
+        Self {
+            strings: HashSet::new(),
+            stats: InternerStats::default(),
+        }
+    }
+}
+
+impl<D: Dealloc> Interner<D> {
+    /// Returns the string equal to `s`, allocating it only the first time.
+    pub fn intern<M: Manager<Dealloc = D>>(&mut self, m: M, s: &[u16]) -> JsStringRef<D> {
+        if let Some(Interned(r)) = self.strings.get(s) {
+            self.stats.hits += 1;
+            self.stats.saved_units += s.len();
+            return r.clone();
+        }
+        let r = new_string(m, s.iter().copied()).to_ref();
+        self.strings.insert(Interned(r.clone()));
+        self.stats.strings += 1;
+        self.stats.units += s.len();
+        r
+    }
+
+    pub fn stats(&self) -> InternerStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        js::js_string::new_string,
+        mem::{global::Global, local::Local},
+    };
+
+    use super::{Interner, InternerStats};
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_intern() {
+        let m = Global();
+        let mut interner = Interner::default();
+        let key: Vec<_> = "key".encode_utf16().collect();
+        let a = interner.intern(m, &key);
+        let b = interner.intern(m, &key);
+        let c = interner.intern(m, &[]);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(
+            interner.stats(),
+            InternerStats {
+                strings: 2,
+                units: 3,
+                hits: 1,
+                saved_units: 3,
+            }
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_size() {
+        let key: Vec<_> = "a_long_repeated_key".encode_utf16().collect();
+        let local = Local::default();
+        let fresh = {
+            let strings: Vec<_> = (0..1000)
+                .map(|_| new_string(&local, key.iter().copied()).to_ref())
+                .collect();
+            assert_eq!(strings.len(), 1000);
+            local.size()
+        };
+        let one = {
+            let _s = new_string(&local, key.iter().copied());
+            local.size()
+        };
+        let interned = {
+            let mut interner = Interner::default();
+            let strings: Vec<_> = (0..1000).map(|_| interner.intern(&local, &key)).collect();
+            assert_eq!(strings.len(), 1000);
+            local.size()
+        };
+        assert_eq!(fresh, one * 1000);
+        assert_eq!(interned, one);
+        assert_eq!(local.size(), 0);
+    }
+}
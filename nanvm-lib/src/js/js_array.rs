@@ -21,6 +21,20 @@ impl<D: Dealloc> RefCast<D> for JsArray<D> {
     const REF_SUBSET: BitSubset64<*const Block<Self, D>> = ARRAY.cast();
 }
 
+impl<D: Dealloc> JsArray<D> {
+    pub fn len(&self) -> usize {
+        self.header
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.header == 0
+    }
+
+    pub fn get_index(&self, i: usize) -> Option<&Any<D>> {
+        self.items().get(i)
+    }
+}
+
 pub fn new_array<M: Manager, I: ExactSizeIterator<Item = Any<M::Dealloc>>>(
     m: M,
     i: impl IntoIterator<IntoIter = I>,
@@ -249,7 +249,7 @@ pub fn div_mod<M: Manager>(
                 let mut q = new_resize(q_index + 1);
                 q[q_index] = q_digit;
                 let mut m = mul_vec(b, &q);
-                if a.cmp(&m) == Ordering::Less {
+                if cmp_vec(&a, &m) == Ordering::Less {
                     q[q_index] = q_digit - 1;
                     m = mul_vec(b, &q);
                 }
@@ -374,6 +374,61 @@ pub fn shr<M: Manager>(m: M, lhs: &JsBigint, rhs: &JsBigint) -> JsBigintMutRef<M
     }
 }
 
+/// The largest power of ten that fits into `u64`.
+const DECIMAL_CHUNK: u64 = 10_000_000_000_000_000_000;
+
+/// Formats `value` as decimal digits with a leading `-` for negative values, the same way as
+/// `BigInt.prototype.toString()` does.
+pub fn to_decimal_string(value: &JsBigint) -> String {
+    let mut rest = value.items().to_vec();
+    let mut chunks = Vec::new();
+    loop {
+        // Divides `rest` by `DECIMAL_CHUNK` in place, from the most significant digit.
+        let mut remainder = 0;
+        for digit in rest.iter_mut().rev() {
+            let n = (u128::from(remainder) << 64) | u128::from(*digit);
+            *digit = (n / u128::from(DECIMAL_CHUNK)) as u64;
+            remainder = (n % u128::from(DECIMAL_CHUNK)) as u64;
+        }
+        while rest.last() == Some(&0) {
+            rest.pop();
+        }
+        chunks.push(remainder);
+        if rest.is_empty() {
+            break;
+        }
+    }
+    let mut result = match value.sign() {
+        Sign::Positive => String::new(),
+        Sign::Negative => String::from("-"),
+    };
+    let (first, rest) = chunks.split_last().unwrap();
+    result.push_str(&first.to_string());
+    for chunk in rest.iter().rev() {
+        result.push_str(&format!("{chunk:019}"));
+    }
+    result
+}
+
+/// Formats `value` as lowercase hexadecimal digits with a leading `-` for negative values, the same
+/// way as `BigInt.prototype.toString(16)` does.
+pub fn to_hex_string(value: &JsBigint) -> String {
+    let mut result = match value.sign() {
+        Sign::Positive => String::new(),
+        Sign::Negative => String::from("-"),
+    };
+    match value.items().split_last() {
+        None => result.push('0'),
+        Some((last, rest)) => {
+            result.push_str(&format!("{last:x}"));
+            for digit in rest.iter().rev() {
+                result.push_str(&format!("{digit:016x}"));
+            }
+        }
+    }
+    result
+}
+
 pub fn equals(lhs: &JsBigint, rhs: &JsBigint) -> bool {
     if lhs.sign() != rhs.sign() {
         return false;
@@ -621,8 +676,8 @@ mod test {
     };
 
     use super::{
-        add, and, div_mod, from_u64, mul, new_bigint, not, or, pow, shl, shr, sub, zero,
-        JsBigintRef, Sign,
+        add, and, div_mod, from_u64, mul, new_bigint, not, or, pow, shl, shr, sub,
+        to_decimal_string, to_hex_string, zero, JsBigintRef, Sign,
     };
 
     #[test]
@@ -1781,4 +1836,50 @@ mod test {
             assert_eq!(o.items(), &[1]);
         }
     }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_div_multi_digit() {
+        let a = new_bigint(Global(), Sign::Positive, [0, 1]);
+        let b = from_u64(Global(), Sign::Positive, 10_000_000_000_000_000_000);
+        let (d, m) = div_mod(Global(), &a, &b);
+        assert_eq!(d.items(), &[1]);
+        assert_eq!(m.items(), &[8_446_744_073_709_551_616]);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_to_decimal_string() {
+        let f = |sign, items: &[u64]| {
+            to_decimal_string(&new_bigint(Global(), sign, items.iter().copied()))
+        };
+        assert_eq!(f(Sign::Positive, &[]), "0");
+        assert_eq!(f(Sign::Positive, &[123]), "123");
+        assert_eq!(f(Sign::Negative, &[123]), "-123");
+        assert_eq!(f(Sign::Positive, &[u64::MAX]), "18446744073709551615");
+        assert_eq!(
+            f(Sign::Positive, &[10_000_000_000_000_000_000]),
+            "10000000000000000000"
+        );
+        assert_eq!(f(Sign::Positive, &[0, 1]), "18446744073709551616");
+        assert_eq!(
+            f(Sign::Negative, &[0, 0, 1]),
+            "-340282366920938463463374607431768211456"
+        );
+        assert_eq!(
+            f(Sign::Positive, &[u64::MAX, u64::MAX, u64::MAX]),
+            "6277101735386680763835789423207666416102355444464034512895"
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_to_hex_string() {
+        let f =
+            |sign, items: &[u64]| to_hex_string(&new_bigint(Global(), sign, items.iter().copied()));
+        assert_eq!(f(Sign::Positive, &[]), "0");
+        assert_eq!(f(Sign::Positive, &[255]), "ff");
+        assert_eq!(f(Sign::Negative, &[255]), "-ff");
+        assert_eq!(f(Sign::Positive, &[1, 1]), "10000000000000001");
+    }
 }
@@ -0,0 +1,67 @@
+use crate::{
+    bytecode::ProgramRef,
+    common::bit_subset64::BitSubset64,
+    mem::{
+        block::Block,
+        flexible_array::{
+            constructor::FlexibleArrayConstructor, header::FlexibleArrayHeader, FlexibleArray,
+        },
+        manager::{Dealloc, Manager},
+        mut_ref::MutRef,
+        ref_::Ref,
+    },
+};
+
+use super::{any::Any, bitset::FUNCTION, ref_cast::RefCast};
+
+pub struct JsFunctionHeader<D: Dealloc> {
+    /// The program with the function code.
+    program: ProgramRef<D>,
+    /// The index of the function in `Program::functions` of `program`.
+    function: u32,
+    len: usize,
+}
+
+impl<D: Dealloc> FlexibleArrayHeader for JsFunctionHeader<D> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// A closure: a reference to the function code and the captured values.
+pub type JsFunction<D> = FlexibleArray<Any<D>, JsFunctionHeader<D>>;
+
+pub type JsFunctionRef<D> = Ref<JsFunction<D>, D>;
+
+pub type JsFunctionMutRef<D> = MutRef<JsFunction<D>, D>;
+
+impl<D: Dealloc> JsFunction<D> {
+    pub fn program(&self) -> &ProgramRef<D> {
+        &self.header.program
+    }
+
+    pub fn function(&self) -> u32 {
+        self.header.function
+    }
+}
+
+impl<D: Dealloc> RefCast<D> for JsFunction<D> {
+    const REF_SUBSET: BitSubset64<*const Block<Self, D>> = FUNCTION.cast();
+}
+
+pub fn new_function<M: Manager, I: ExactSizeIterator<Item = Any<M::Dealloc>>>(
+    m: M,
+    program: ProgramRef<M::Dealloc>,
+    function: u32,
+    captures: impl IntoIterator<IntoIter = I>,
+) -> JsFunctionMutRef<M::Dealloc> {
+    let captures = captures.into_iter();
+    m.new(FlexibleArrayConstructor::new(
+        JsFunctionHeader {
+            program,
+            function,
+            len: captures.len(),
+        },
+        captures,
+    ))
+}
@@ -1,9 +1,13 @@
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+
 use crate::{
     common::bit_subset64::BitSubset64,
     js::any::Any,
     mem::{
         block::Block,
-        flexible_array::FlexibleArray,
+        flexible_array::{
+            constructor::FlexibleArrayConstructor, header::FlexibleArrayHeader, FlexibleArray,
+        },
         manager::{Dealloc, Manager},
         mut_ref::MutRef,
         ref_::Ref,
@@ -14,7 +18,27 @@ use super::{bitset::OBJECT, js_string::JsStringRef, ref_cast::RefCast};
 
 pub type Property<D> = (JsStringRef<D>, Any<D>);
 
-pub type JsObject<D> = FlexibleArray<Property<D>>;
+/// Objects with more properties than this get a sorted index, so `JsObject::get` doesn't scan
+/// all properties.
+pub const INDEX_THRESHOLD: usize = 8;
+
+/// Positions of the properties sorted by key, allocated by the same manager as the object.
+pub type JsObjectIndex<D> = Ref<FlexibleArray<u32>, D>;
+
+#[derive(Debug)]
+pub struct JsObjectHeader<D: Dealloc> {
+    len: usize,
+    /// Built at construction time for objects with more than `INDEX_THRESHOLD` properties.
+    index: Option<JsObjectIndex<D>>,
+}
+
+impl<D: Dealloc> FlexibleArrayHeader for JsObjectHeader<D> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+pub type JsObject<D> = FlexibleArray<Property<D>, JsObjectHeader<D>>;
 
 pub type JsObjectRef<D> = Ref<JsObject<D>, D>;
 
@@ -24,9 +48,246 @@ impl<D: Dealloc> RefCast<D> for JsObject<D> {
     const REF_SUBSET: BitSubset64<*const Block<Self, D>> = OBJECT.cast();
 }
 
+impl<D: Dealloc> JsObject<D> {
+    pub fn len(&self) -> usize {
+        self.header.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.header.len == 0
+    }
+
+    /// The position of the property `key`, in O(log n) for indexed objects and O(n) otherwise.
+    pub fn position(&self, key: &[u16]) -> Option<usize> {
+        let items = self.items();
+        match &self.header.index {
+            Some(index) => index
+                .items()
+                .binary_search_by(|&i| items[i as usize].0.items().cmp(key))
+                .ok()
+                .map(|i| index.items()[i] as usize),
+            None => items.iter().position(|(k, _)| k.items() == key),
+        }
+    }
+
+    pub fn get(&self, key: &[u16]) -> Option<&Any<D>> {
+        self.position(key).map(|i| &self.items()[i].1)
+    }
+
+    pub fn has(&self, key: &[u16]) -> bool {
+        self.position(key).is_some()
+    }
+
+    /// The keys in the property order.
+    pub fn keys(&self) -> impl Iterator<Item = &JsStringRef<D>> {
+        self.items().iter().map(|(k, _)| k)
+    }
+}
+
+/// Creates an object from properties in the given order.
+///
+/// A duplicate key keeps the position of its first definition and the value of the last one,
+/// like in an object literal, so each key has exactly one property.
 pub fn new_object<M: Manager, I: ExactSizeIterator<Item = Property<M::Dealloc>>>(
     m: M,
     i: impl IntoIterator<IntoIter = I>,
 ) -> JsObjectMutRef<M::Dealloc> {
-    m.flexible_array_new(i)
+    let items = i.into_iter();
+    let mut properties: Vec<Property<M::Dealloc>> = Vec::with_capacity(items.len());
+    if items.len() <= INDEX_THRESHOLD {
+        for (k, v) in items {
+            match properties.iter_mut().find(|(p, _)| p.items() == k.items()) {
+                Some(property) => property.1 = v,
+                None => properties.push((k, v)),
+            }
+        }
+    } else {
+        let mut positions = HashMap::<_, usize>::new();
+        for (k, v) in items {
+            match positions.entry(k.items().to_vec()) {
+                Entry::Occupied(position) => properties[*position.get()].1 = v,
+                Entry::Vacant(position) => {
+                    position.insert(properties.len());
+                    properties.push((k, v));
+                }
+            }
+        }
+    }
+    let len = properties.len();
+    let index = (len > INDEX_THRESHOLD).then(|| {
+        let mut index: Vec<_> = (0..len as u32).collect();
+        index.sort_by(|&a, &b| {
+            properties[a as usize]
+                .0
+                .items()
+                .cmp(properties[b as usize].0.items())
+        });
+        m.flexible_array_new(index).to_ref()
+    });
+    m.new(FlexibleArrayConstructor::new(
+        JsObjectHeader { len, index },
+        properties.into_iter(),
+    ))
+}
+
+/// Creates an object from properties in the source order.
+///
+/// Properties follow ECMAScript `OwnPropertyKeys` order: array index keys ascending, then other
+/// keys in the order of their first definition. The last duplicate wins, like in an object
+/// literal.
+pub fn new_ordered_object<M: Manager>(
+    m: M,
+    properties: impl IntoIterator<Item = Property<M::Dealloc>>,
+) -> JsObjectMutRef<M::Dealloc> {
+    let mut indices = BTreeMap::new();
+    let mut names = Vec::new();
+    for (k, v) in properties {
+        match array_index(k.items()) {
+            Some(index) => {
+                indices.insert(index, (k, v));
+            }
+            // `new_object` merges the duplicates.
+            None => names.push((k, v)),
+        }
+    }
+    let properties: Vec<_> = indices.into_values().chain(names).collect();
+    new_object(m, properties)
+}
+
+/// Returns the numeric value of `key` if it's an array index, i.e. a canonical numeric string of
+/// an integer in `[0, 2^32 - 2]`. ECMAScript `OwnPropertyKeys` orders such keys first, ascending.
+/// See https://tc39.es/ecma262/#array-index
+pub fn array_index(key: &[u16]) -> Option<u32> {
+    match key {
+        [] => None,
+        [c] if *c == b'0' as u16 => Some(0),
+        [c, ..] if *c == b'0' as u16 => None,
+        _ => {
+            let mut result = 0u32;
+            for &c in key {
+                let digit = (c as u32).wrapping_sub(b'0' as u32);
+                if digit > 9 {
+                    return None;
+                }
+                result = result.checked_mul(10)?.checked_add(digit)?;
+            }
+            if result == u32::MAX {
+                return None;
+            }
+            Some(result)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        js::any::Any,
+        mem::{
+            global::{Global, GLOBAL},
+            local::Local,
+        },
+        parser::shared::to_js_string,
+    };
+
+    use super::{array_index, new_object, JsObjectRef, INDEX_THRESHOLD};
+
+    fn utf16(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    fn object(keys: &[String]) -> JsObjectRef<Global> {
+        new_object(
+            GLOBAL,
+            keys.iter()
+                .enumerate()
+                .map(|(i, k)| (to_js_string(GLOBAL, k.clone()), Any::move_from(i as f64))),
+        )
+        .to_ref()
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_lookup() {
+        for len in [0, 1, INDEX_THRESHOLD, INDEX_THRESHOLD + 1, 100] {
+            // Keys in reverse order, so the property order differs from the sorted order.
+            let keys: Vec<_> = (0..len).rev().map(|i| format!("k{i}")).collect();
+            let o = object(&keys);
+            assert_eq!(o.len(), len);
+            assert_eq!(o.is_empty(), len == 0);
+            for (i, k) in keys.iter().enumerate() {
+                assert_eq!(o.position(&utf16(k)), Some(i));
+                assert_eq!(o.get(&utf16(k)).unwrap().clone().try_move(), Ok(i as f64));
+            }
+            assert!(!o.has(&utf16("k")));
+            assert!(!o.has(&utf16(&format!("k{len}"))));
+            let names: Vec<_> = o
+                .keys()
+                .map(|k| String::from_utf16_lossy(k.items()))
+                .collect();
+            assert_eq!(names, keys);
+        }
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_duplicate_keys() {
+        for len in [3, INDEX_THRESHOLD + 3] {
+            // `k0` is defined at the start, the middle and the end.
+            let mut keys: Vec<_> = (0..len).map(|i| format!("k{i}")).collect();
+            keys[len / 2] = "k0".to_string();
+            keys[len - 1] = "k0".to_string();
+            let o = object(&keys);
+            assert_eq!(o.len(), len - 2);
+            assert_eq!(o.position(&utf16("k0")), Some(0));
+            assert_eq!(
+                o.get(&utf16("k0")).unwrap().clone().try_move(),
+                Ok((len - 1) as f64)
+            );
+            assert_eq!(o.keys().filter(|k| k.items() == utf16("k0")).count(), 1);
+            // The other keys keep their values and follow in the definition order.
+            for (i, k) in keys.iter().enumerate().filter(|(_, k)| *k != "k0") {
+                assert_eq!(o.get(&utf16(k)).unwrap().clone().try_move(), Ok(i as f64));
+            }
+        }
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_local() {
+        let local = Local::default();
+        {
+            let keys: Vec<_> = (0..INDEX_THRESHOLD + 1)
+                .map(|i| to_js_string(&local, format!("k{i}")))
+                .collect();
+            let before = local.size();
+            let o = new_object(&local, keys.into_iter().map(|k| (k, Any::move_from(0.0))));
+            // The object block and its index block.
+            assert!(local.size() > before);
+            assert!(o.has(&utf16("k0")));
+        }
+        assert_eq!(local.size(), 0);
+    }
+
+    fn f(s: &str) -> Option<u32> {
+        array_index(&s.encode_utf16().collect::<Vec<_>>())
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_array_index() {
+        assert_eq!(f("0"), Some(0));
+        assert_eq!(f("42"), Some(42));
+        assert_eq!(f("4294967294"), Some(4294967294));
+        assert_eq!(f("4294967295"), None);
+        assert_eq!(f("4294967296"), None);
+        assert_eq!(f("99999999999"), None);
+        assert_eq!(f(""), None);
+        assert_eq!(f("01"), None);
+        assert_eq!(f("-1"), None);
+        assert_eq!(f("1.5"), None);
+        assert_eq!(f("a"), None);
+    }
 }
@@ -3,14 +3,18 @@ pub mod any_cast;
 mod any_internal;
 mod bitset;
 mod bool;
+pub mod interner;
 pub mod js_array;
 pub mod js_bigint;
+pub mod js_function;
 pub mod js_object;
 pub mod js_string;
 pub mod new;
 pub mod null;
 mod number;
+pub mod persistent;
 mod ref_cast;
 pub mod type_;
+pub mod undefined;
 mod value_cast;
 pub mod visitor;
@@ -1,9 +1,10 @@
-use crate::mem::manager::Manager;
+use crate::{bytecode::ProgramRef, mem::manager::Manager};
 
 use super::{
     any::Any,
     any_cast::AnyCast,
     js_array::new_array,
+    js_function::new_function,
     js_object::new_object,
     js_string::{new_string, JsStringRef},
 };
@@ -29,6 +30,16 @@ pub trait New: Manager {
     ) -> Any<Self::Dealloc> {
         new_object(self, i).to_ref().move_to_any()
     }
+    fn new_js_function(
+        self,
+        program: ProgramRef<Self::Dealloc>,
+        function: u32,
+        captures: impl IntoIterator<IntoIter = impl ExactSizeIterator<Item = Any<Self::Dealloc>>>,
+    ) -> Any<Self::Dealloc> {
+        new_function(self, program, function, captures)
+            .to_ref()
+            .move_to_any()
+    }
 }
 
 impl<M: Manager> New for M {}
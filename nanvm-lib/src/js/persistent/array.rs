@@ -0,0 +1,373 @@
+use crate::{
+    js::{
+        any::Any,
+        js_array::{new_array, JsArray, JsArrayMutRef},
+    },
+    mem::{
+        flexible_array::FlexibleArray,
+        manager::{Dealloc, Manager},
+        ref_::Ref,
+    },
+};
+
+use super::{new_persistent, Persistent};
+
+const BITS: usize = 5;
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+/// The items of a leaf.
+type Leaf<T, D> = Ref<FlexibleArray<T>, D>;
+
+/// The children of a branch: leaves for the branches at `BITS`, and branches above it.
+type Branch<T, D> = Ref<FlexibleArray<Node<T, D>>, D>;
+
+enum Node<T, D: Dealloc> {
+    Branch(Branch<T, D>),
+    Leaf(Leaf<T, D>),
+}
+
+impl<T, D: Dealloc> Clone for Node<T, D> {
+    fn clone(&self) -> Self {
+        match self {
+            Node::Branch(b) => Node::Branch(b.clone()),
+            Node::Leaf(l) => Node::Leaf(l.clone()),
+        }
+    }
+}
+
+impl<T, D: Dealloc> Node<T, D> {
+    fn branch(&self) -> &FlexibleArray<Node<T, D>> {
+        match self {
+            Node::Branch(b) => b,
+            Node::Leaf(_) => unreachable!(),
+        }
+    }
+
+    fn leaf(&self) -> &[T] {
+        match self {
+            Node::Leaf(l) => l.items(),
+            Node::Branch(_) => unreachable!(),
+        }
+    }
+}
+
+fn new_leaf<M: Manager, T>(m: M, items: Vec<T>) -> Leaf<T, M::Dealloc> {
+    m.flexible_array_new(items).to_ref()
+}
+
+fn new_branch<M: Manager, T>(m: M, children: Vec<Node<T, M::Dealloc>>) -> Branch<T, M::Dealloc> {
+    m.flexible_array_new(children).to_ref()
+}
+
+/// A chain of single child branches from `level` down to `node`.
+fn new_path<M: Manager, T>(m: M, level: usize, node: Node<T, M::Dealloc>) -> Node<T, M::Dealloc> {
+    if level == 0 {
+        return node;
+    }
+    let parent = Node::Branch(new_branch(m, [node].into()));
+    new_path(m, level - BITS, parent)
+}
+
+/// A persistent vector: a 32-way trie of nodes with a tail, like Clojure's `PersistentVector`.
+///
+/// `push` and `set` copy at most one path from the root, so they take O(log32 n) time and memory.
+pub struct PersistentVector<T, D: Dealloc> {
+    len: usize,
+    /// The height of the trie in bits of the index.
+    shift: usize,
+    root: Branch<T, D>,
+    /// The last items, up to `WIDTH`. They are moved to the trie when the tail is full.
+    tail: Leaf<T, D>,
+}
+
+/// A persistent array of values.
+pub type PersistentArray<D> = PersistentVector<Any<D>, D>;
+
+impl<T, D: Dealloc> Clone for PersistentVector<T, D> {
+    fn clone(&self) -> Self {
+        Self {
+            len: self.len,
+            shift: self.shift,
+            root: self.root.clone(),
+            tail: self.tail.clone(),
+        }
+    }
+}
+
+impl<T: Clone, D: Dealloc> PersistentVector<T, D> {
+    pub fn new<M: Manager<Dealloc = D>>(m: M) -> Self {
+        Self {
+            len: 0,
+            shift: BITS,
+            root: new_branch(m, Vec::new()),
+            tail: new_leaf(m, Vec::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The index of the first item in the tail.
+    fn tail_offset(&self) -> usize {
+        if self.len < WIDTH {
+            0
+        } else {
+            ((self.len - 1) >> BITS) << BITS
+        }
+    }
+
+    pub fn get(&self, i: usize) -> Option<&T> {
+        if i >= self.len {
+            return None;
+        }
+        let offset = self.tail_offset();
+        if i >= offset {
+            return self.tail.items().get(i - offset);
+        }
+        let mut node: &FlexibleArray<Node<T, D>> = &self.root;
+        let mut level = self.shift;
+        while level > BITS {
+            node = node.items()[(i >> level) & MASK].branch();
+            level -= BITS;
+        }
+        node.items()[(i >> BITS) & MASK].leaf().get(i & MASK)
+    }
+
+    pub fn push<M: Manager<Dealloc = D>>(&self, m: M, value: T) -> Self {
+        if self.len - self.tail_offset() < WIDTH {
+            let mut tail = self.tail.items().to_vec();
+            tail.push(value);
+            return Self {
+                len: self.len + 1,
+                shift: self.shift,
+                root: self.root.clone(),
+                tail: new_leaf(m, tail),
+            };
+        }
+        let tail = Node::Leaf(self.tail.clone());
+        let (root, shift) = if (self.len >> BITS) > (1 << self.shift) {
+            let root = [
+                Node::Branch(self.root.clone()),
+                new_path(m, self.shift, tail),
+            ];
+            (new_branch(m, root.into()), self.shift + BITS)
+        } else {
+            (self.push_tail(m, self.shift, &self.root, tail), self.shift)
+        };
+        Self {
+            len: self.len + 1,
+            shift,
+            root,
+            tail: new_leaf(m, [value].into()),
+        }
+    }
+
+    /// Copies the path to the last leaf and appends the full `tail` as the new leaf.
+    fn push_tail<M: Manager<Dealloc = D>>(
+        &self,
+        m: M,
+        level: usize,
+        parent: &FlexibleArray<Node<T, D>>,
+        tail: Node<T, D>,
+    ) -> Branch<T, D> {
+        let mut children = parent.items().to_vec();
+        let sub = ((self.len - 1) >> level) & MASK;
+        let node = if level == BITS {
+            tail
+        } else if let Some(c) = children.get(sub) {
+            Node::Branch(self.push_tail(m, level - BITS, c.branch(), tail))
+        } else {
+            new_path(m, level - BITS, tail)
+        };
+        if sub < children.len() {
+            children[sub] = node;
+        } else {
+            children.push(node);
+        }
+        new_branch(m, children)
+    }
+
+    /// Replaces the item `i`. `None` if `i` is out of bounds.
+    pub fn set<M: Manager<Dealloc = D>>(&self, m: M, i: usize, value: T) -> Option<Self> {
+        if i >= self.len {
+            return None;
+        }
+        let offset = self.tail_offset();
+        let mut result = self.clone();
+        if i >= offset {
+            let mut tail = self.tail.items().to_vec();
+            tail[i - offset] = value;
+            result.tail = new_leaf(m, tail);
+        } else {
+            result.root = Self::assoc(m, self.shift, &self.root, i, value);
+        }
+        Some(result)
+    }
+
+    fn assoc<M: Manager<Dealloc = D>>(
+        m: M,
+        level: usize,
+        node: &FlexibleArray<Node<T, D>>,
+        i: usize,
+        value: T,
+    ) -> Branch<T, D> {
+        let mut children = node.items().to_vec();
+        let sub = (i >> level) & MASK;
+        children[sub] = if level == BITS {
+            let mut items = children[sub].leaf().to_vec();
+            items[i & MASK] = value;
+            Node::Leaf(new_leaf(m, items))
+        } else {
+            let c = Self::assoc(m, level - BITS, children[sub].branch(), i, value);
+            Node::Branch(c)
+        };
+        new_branch(m, children)
+    }
+
+    /// Appends all `items`, like `[...a, ...items]`.
+    pub fn extend<M: Manager<Dealloc = D>>(
+        &self,
+        m: M,
+        items: impl IntoIterator<Item = T>,
+    ) -> Self {
+        items
+            .into_iter()
+            .fold(self.clone(), |result, value| result.push(m, value))
+    }
+
+    fn leaves<'a>(node: &'a FlexibleArray<Node<T, D>>, result: &mut Vec<&'a [T]>) {
+        for c in node.items() {
+            match c {
+                Node::Branch(b) => Self::leaves(b, result),
+                Node::Leaf(l) => result.push(l.items()),
+            }
+        }
+    }
+
+    /// The items in order, read from the trie without copying them.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let mut leaves = Vec::new();
+        Self::leaves(&self.root, &mut leaves);
+        leaves.push(self.tail.items());
+        leaves.into_iter().flatten()
+    }
+}
+
+impl<D: Dealloc> PersistentArray<D> {
+    pub fn from_array<M: Manager<Dealloc = D>>(m: M, a: &JsArray<D>) -> Self {
+        Self::new(m).extend(m, a.items().iter().cloned())
+    }
+
+    /// A copy as a plain array.
+    pub fn to_array<M: Manager<Dealloc = D>>(&self, m: M) -> JsArrayMutRef<D> {
+        new_array(m, self.iter().cloned().collect::<Vec<_>>())
+    }
+
+    /// The array as a value. It isn't copied, the value refers to the same trie.
+    pub fn to_any<M: Manager<Dealloc = D>>(&self, m: M) -> Any<D> {
+        new_persistent(m, Persistent::Array(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        js::{any::Any, js_array::new_array, null::Null, type_::Type},
+        mem::{global::Global, local::Local},
+        serializer::{to_djs::to_djs, to_json::to_json},
+    };
+
+    use super::PersistentArray;
+
+    fn numbers(a: &PersistentArray<Global>) -> Vec<f64> {
+        a.iter()
+            .map(|v| v.clone().try_move::<f64>().unwrap())
+            .collect()
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_push() {
+        let m = Global();
+        let mut a = PersistentArray::new(m);
+        for i in 0..1000 {
+            a = a.push(m, Any::move_from(i as f64));
+        }
+        assert_eq!(a.len(), 1000);
+        for i in 0..1000 {
+            assert_eq!(a.get(i).cloned(), Some(Any::move_from(i as f64)));
+        }
+        assert_eq!(a.get(1000), None);
+        assert_eq!(numbers(&a), (0..1000).map(|i| i as f64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_persistence() {
+        let m = Global();
+        let a = PersistentArray::new(m).extend(m, (0..100).map(|i| Any::move_from(i as f64)));
+        let b = a.push(m, Any::move_from(100.0));
+        let c = a.set(m, 3, Any::move_from(-3.0)).unwrap();
+        let d = c.set(m, 99, Any::move_from(-99.0)).unwrap();
+        assert_eq!(numbers(&a), (0..100).map(|i| i as f64).collect::<Vec<_>>());
+        assert_eq!(b.len(), 101);
+        assert_eq!(b.get(100).cloned(), Some(Any::move_from(100.0)));
+        assert_eq!(c.get(3).cloned(), Some(Any::move_from(-3.0)));
+        assert_eq!(c.get(99).cloned(), Some(Any::move_from(99.0)));
+        assert_eq!(d.get(3).cloned(), Some(Any::move_from(-3.0)));
+        assert_eq!(d.get(99).cloned(), Some(Any::move_from(-99.0)));
+        assert!(a.set(m, 100, Any::move_from(0.0)).is_none());
+        // The unchanged leaves are shared.
+        let leaf = |a: &PersistentArray<Global>| a.root.items()[1].leaf().as_ptr();
+        assert_eq!(leaf(&c), leaf(&a));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_any() {
+        let m = Global();
+        let a = PersistentArray::from_array(
+            m,
+            &new_array(m, [Any::move_from(1.0), Any::move_from(2.0)]),
+        );
+        let b = a.push(m, a.to_any(m));
+        let any = b.to_any(m);
+        assert_eq!(any.get_type(), Type::Array);
+        assert_eq!(any.len(), Some(3));
+        assert_eq!(any.get_index(1), Some(Any::move_from(2.0)));
+        assert_eq!(any.get_index(2).unwrap().len(), Some(2));
+        assert_eq!(to_json(any.clone()).unwrap(), "[1,2,[1,2]]");
+        assert_eq!(to_djs(any, false).unwrap(), "export default [1,2,[1,2]]");
+        // Values are written in place, so nodes are never copied to a plain array.
+        let large = PersistentArray::new(m).extend(m, (0..100).map(|i| Any::move_from(i as f64)));
+        let expected = format!(
+            "[{}]",
+            (0..100)
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        assert_eq!(to_json(large.to_any(m)).unwrap(), expected);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_drop() {
+        let local = Local::default();
+        {
+            let a = PersistentArray::new(&local)
+                .extend(&local, (0..2000).map(|i| Any::move_from(i as f64)));
+            let b = a.set(&local, 0, Any::move_from(Null())).unwrap();
+            let _c = b.push(&local, a.to_any(&local)).to_any(&local);
+        }
+        assert_eq!(local.size(), 0);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{
+    js::js_string::JsStringRef,
+    mem::{
+        flexible_array::{
+            constructor::FlexibleArrayConstructor, header::FlexibleArrayHeader, FlexibleArray,
+        },
+        manager::{Dealloc, Manager},
+        ref_::Ref,
+    },
+};
+
+const BITS: u32 = 5;
+const MASK: u32 = (1 << BITS) - 1;
+/// Nodes at this shift and below hold keys with the same hash in a linear list.
+const COLLISION_SHIFT: u32 = 32;
+
+/// FNV-1a over the UTF-16 code units.
+pub fn hash(key: &[u16]) -> u32 {
+    key.iter().fold(0x811c_9dc5, |h, &c| {
+        (h ^ c as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// An entry of a node: a key with its position, or a child node for the keys that share a slot.
+enum Entry<D: Dealloc> {
+    Key(JsStringRef<D>, usize),
+    Child(NodeRef<D>),
+}
+
+impl<D: Dealloc> Clone for Entry<D> {
+    fn clone(&self) -> Self {
+        match self {
+            Entry::Key(k, p) => Entry::Key(k.clone(), *p),
+            Entry::Child(c) => Entry::Child(c.clone()),
+        }
+    }
+}
+
+struct NodeHeader {
+    /// A bit for each of the `1 << BITS` slots that has an entry.
+    bitmap: u32,
+    len: usize,
+}
+
+impl FlexibleArrayHeader for NodeHeader {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// The entries of the slots in `bitmap`, in order. Collision nodes have a zero bitmap and a list
+/// of keys.
+type Node<D> = FlexibleArray<Entry<D>, NodeHeader>;
+
+type NodeRef<D> = Ref<Node<D>, D>;
+
+fn new_node<M: Manager>(m: M, bitmap: u32, entries: Vec<Entry<M::Dealloc>>) -> NodeRef<M::Dealloc> {
+    let header = NodeHeader {
+        bitmap,
+        len: entries.len(),
+    };
+    m.new(FlexibleArrayConstructor::new(header, entries.into_iter()))
+        .to_ref()
+}
+
+/// The bit of `hash` at `shift` and the index of its entry in a node with `bitmap`.
+fn slot(bitmap: u32, hash: u32, shift: u32) -> (u32, usize) {
+    let bit = 1 << ((hash >> shift) & MASK);
+    (bit, (bitmap & (bit - 1)).count_ones() as usize)
+}
+
+/// A persistent hash array mapped trie from string keys to positions, e.g. of properties.
+pub struct Hamt<D: Dealloc> {
+    root: NodeRef<D>,
+}
+
+impl<D: Dealloc> Clone for Hamt<D> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+        }
+    }
+}
+
+impl<D: Dealloc> Hamt<D> {
+    pub fn new<M: Manager<Dealloc = D>>(m: M) -> Self {
+        Self {
+            root: new_node(m, 0, Vec::new()),
+        }
+    }
+
+    pub fn get(&self, key: &[u16]) -> Option<usize> {
+        let hash = hash(key);
+        let mut node: &Node<D> = &self.root;
+        let mut shift = 0;
+        loop {
+            if shift >= COLLISION_SHIFT {
+                return node.items().iter().find_map(|e| match e {
+                    Entry::Key(k, p) if k.items() == key => Some(*p),
+                    _ => None,
+                });
+            }
+            let bitmap = node.header.bitmap;
+            let (bit, i) = slot(bitmap, hash, shift);
+            if bitmap & bit == 0 {
+                return None;
+            }
+            match &node.items()[i] {
+                Entry::Key(k, p) => return (k.items() == key).then_some(*p),
+                Entry::Child(c) => node = c,
+            }
+            shift += BITS;
+        }
+    }
+
+    /// Adds `key` or replaces its position.
+    pub fn insert<M: Manager<Dealloc = D>>(
+        &self,
+        m: M,
+        key: JsStringRef<D>,
+        position: usize,
+    ) -> Self {
+        let h = hash(key.items());
+        Self {
+            root: Self::insert_node(m, &self.root, 0, h, key, position),
+        }
+    }
+
+    fn insert_node<M: Manager<Dealloc = D>>(
+        m: M,
+        node: &Node<D>,
+        shift: u32,
+        hash: u32,
+        key: JsStringRef<D>,
+        position: usize,
+    ) -> NodeRef<D> {
+        let mut entries = node.items().to_vec();
+        if shift >= COLLISION_SHIFT {
+            let same = entries
+                .iter()
+                .position(|e| matches!(e, Entry::Key(k, _) if k.items() == key.items()));
+            match same {
+                Some(i) => entries[i] = Entry::Key(key, position),
+                None => entries.push(Entry::Key(key, position)),
+            }
+            return new_node(m, 0, entries);
+        }
+        let bitmap = node.header.bitmap;
+        let (bit, i) = slot(bitmap, hash, shift);
+        if bitmap & bit == 0 {
+            entries.insert(i, Entry::Key(key, position));
+            return new_node(m, bitmap | bit, entries);
+        }
+        let next = shift + BITS;
+        entries[i] = match &entries[i] {
+            Entry::Key(old, _) if old.items() == key.items() => Entry::Key(key, position),
+            Entry::Key(old, p) => {
+                // Two keys share this slot: push both one level down.
+                let empty = new_node(m, 0, Vec::new());
+                let old_hash = self::hash(old.items());
+                let c = Self::insert_node(m, &empty, next, old_hash, old.clone(), *p);
+                Entry::Child(Self::insert_node(m, &c, next, hash, key, position))
+            }
+            Entry::Child(c) => Entry::Child(Self::insert_node(m, c, next, hash, key, position)),
+        };
+        new_node(m, bitmap, entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        js::js_string::new_string,
+        mem::{global::Global, local::Local},
+    };
+
+    use super::{hash, Hamt};
+
+    fn key(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_insert() {
+        let m = Global();
+        let mut h = Hamt::new(m);
+        for i in 0..1000 {
+            let k = new_string(m, key(&i.to_string())).to_ref();
+            h = h.insert(m, k, i);
+        }
+        let old = h.clone();
+        let k = new_string(m, key("7")).to_ref();
+        h = h.insert(m, k, 1007);
+        for i in 0..1000 {
+            let expected = if i == 7 { 1007 } else { i };
+            let k = key(&i.to_string());
+            assert_eq!(h.get(&k), Some(expected));
+            assert_eq!(old.get(&k), Some(i));
+        }
+        assert_eq!(h.get(&key("1000")), None);
+        assert_eq!(h.get(&key("")), None);
+    }
+
+    /// Two different keys with the same hash.
+    fn collision() -> (String, String) {
+        let mut seen = HashMap::new();
+        (0..)
+            .map(|i| format!("k{i}"))
+            .find_map(|k| {
+                let other = seen.insert(hash(&key(&k)), k.clone())?;
+                Some((other, k))
+            })
+            .unwrap()
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_collisions() {
+        let m = Global();
+        let (a, b) = collision();
+        assert_eq!(hash(&key(&a)), hash(&key(&b)));
+        let mut h = Hamt::new(m);
+        for (i, s) in [&a, &b, "c"].into_iter().enumerate() {
+            let k = new_string(m, key(s)).to_ref();
+            h = h.insert(m, k, i);
+        }
+        let old = h.clone();
+        let k = new_string(m, key(&b)).to_ref();
+        h = h.insert(m, k, 10);
+        assert_eq!(h.get(&key(&a)), Some(0));
+        assert_eq!(h.get(&key(&b)), Some(10));
+        assert_eq!(h.get(&key("c")), Some(2));
+        assert_eq!(old.get(&key(&b)), Some(1));
+        assert_eq!(h.get(&key("d")), None);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_drop() {
+        let local = Local::default();
+        {
+            let mut h = Hamt::new(&local);
+            for i in 0..100 {
+                let k = new_string(&local, key(&i.to_string())).to_ref();
+                h = h.insert(&local, k, i);
+            }
+        }
+        assert_eq!(local.size(), 0);
+    }
+}
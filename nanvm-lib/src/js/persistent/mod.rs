@@ -0,0 +1,42 @@
+//! Persistent collections: updates return a new collection and share the unchanged structure
+//! with the old one.
+//!
+//! Nodes are blocks of the same memory manager as other values. `to_any` stores a persistent array
+//! or object in `Any` without copying it. Such a value has `Type::Array` or `Type::Object`, and
+//! `to_json` and `to_djs` read its nodes directly.
+
+pub mod array;
+mod hamt;
+pub mod object;
+
+use crate::{
+    common::bit_subset64::BitSubset64,
+    mem::{
+        block::Block,
+        fixed::Fixed,
+        manager::{Dealloc, Manager},
+        ref_::Ref,
+    },
+};
+
+use self::{array::PersistentArray, object::PersistentObject};
+
+use super::{any::Any, bitset::PERSISTENT, ref_cast::RefCast};
+
+/// A persistent array or object in an `Any`.
+pub enum Persistent<D: Dealloc> {
+    Array(PersistentArray<D>),
+    Object(PersistentObject<D>),
+}
+
+pub type JsPersistent<D> = Fixed<Persistent<D>>;
+
+pub type JsPersistentRef<D> = Ref<JsPersistent<D>, D>;
+
+impl<D: Dealloc> RefCast<D> for JsPersistent<D> {
+    const REF_SUBSET: BitSubset64<*const Block<Self, D>> = PERSISTENT.cast();
+}
+
+fn new_persistent<M: Manager>(m: M, p: Persistent<M::Dealloc>) -> Any<M::Dealloc> {
+    Any::move_from(m.new(Fixed(p)).to_ref())
+}
@@ -0,0 +1,258 @@
+use crate::{
+    js::{
+        any::Any,
+        js_object::{array_index, new_ordered_object, JsObject, JsObjectMutRef, Property},
+        js_string::JsStringRef,
+    },
+    mem::manager::{Dealloc, Manager},
+};
+
+use super::{array::PersistentVector, hamt::Hamt, new_persistent, Persistent};
+
+/// A persistent object: properties in insertion order and a hash index from keys to their
+/// positions.
+///
+/// `set` replaces or appends one property in O(log n), sharing the rest with the old object.
+pub struct PersistentObject<D: Dealloc> {
+    properties: PersistentVector<Property<D>, D>,
+    index: Hamt<D>,
+}
+
+impl<D: Dealloc> Clone for PersistentObject<D> {
+    fn clone(&self) -> Self {
+        Self {
+            properties: self.properties.clone(),
+            index: self.index.clone(),
+        }
+    }
+}
+
+impl<D: Dealloc> PersistentObject<D> {
+    pub fn new<M: Manager<Dealloc = D>>(m: M) -> Self {
+        Self {
+            properties: PersistentVector::new(m),
+            index: Hamt::new(m),
+        }
+    }
+
+    pub fn from_object<M: Manager<Dealloc = D>>(m: M, o: &JsObject<D>) -> Self {
+        Self::new(m).spread(m, o.items().iter().cloned())
+    }
+
+    pub fn len(&self) -> usize {
+        self.properties.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.properties.is_empty()
+    }
+
+    pub fn get(&self, key: &[u16]) -> Option<&Any<D>> {
+        let (_, v) = self.properties.get(self.index.get(key)?)?;
+        Some(v)
+    }
+
+    pub fn has(&self, key: &[u16]) -> bool {
+        self.index.get(key).is_some()
+    }
+
+    /// Sets the property `key`, like `{ ...o, [key]: value }`. A new key goes after the existing
+    /// ones.
+    pub fn set<M: Manager<Dealloc = D>>(&self, m: M, key: JsStringRef<D>, value: Any<D>) -> Self {
+        if let Some(p) = self.index.get(key.items()) {
+            return Self {
+                properties: self.properties.set(m, p, (key, value)).unwrap(),
+                index: self.index.clone(),
+            };
+        }
+        Self {
+            index: self.index.insert(m, key.clone(), self.len()),
+            properties: self.properties.push(m, (key, value)),
+        }
+    }
+
+    /// Sets all `properties` in order, like `{ ...o, ...properties }`.
+    pub fn spread<M: Manager<Dealloc = D>>(
+        &self,
+        m: M,
+        properties: impl IntoIterator<Item = Property<D>>,
+    ) -> Self {
+        properties
+            .into_iter()
+            .fold(self.clone(), |result, (k, v)| result.set(m, k, v))
+    }
+
+    /// The properties in the ECMAScript order: array indices in ascending order, then the other
+    /// keys in insertion order, see `new_ordered_object`.
+    pub fn iter(&self) -> impl Iterator<Item = &Property<D>> {
+        let mut indices: Vec<_> = self
+            .properties
+            .iter()
+            .filter_map(|p| Some((array_index(p.0.items())?, p)))
+            .collect();
+        indices.sort_unstable_by_key(|(i, _)| *i);
+        let names = self
+            .properties
+            .iter()
+            .filter(|(k, _)| array_index(k.items()).is_none());
+        indices.into_iter().map(|(_, p)| p).chain(names)
+    }
+
+    /// A copy as a plain object.
+    pub fn to_object<M: Manager<Dealloc = D>>(&self, m: M) -> JsObjectMutRef<D> {
+        new_ordered_object(m, self.iter().cloned())
+    }
+
+    /// The object as a value. It isn't copied, the value refers to the same trie.
+    pub fn to_any<M: Manager<Dealloc = D>>(&self, m: M) -> Any<D> {
+        new_persistent(m, Persistent::Object(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        js::{any::Any, js_object::new_object, js_string::new_string, new::New, type_::Type},
+        mem::{global::Global, local::Local},
+        serializer::{
+            to_djs::{to_djs, to_djs_with_options, DjsOptions},
+            to_json::to_json,
+        },
+    };
+
+    use super::PersistentObject;
+
+    fn key(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_set() {
+        let m = Global();
+        let k = |s: &str| new_string(m, key(s)).to_ref();
+        let o = PersistentObject::new(m)
+            .set(m, k("b"), Any::move_from(1.0))
+            .set(m, k("1"), Any::move_from(2.0))
+            .set(m, k("a"), Any::move_from(3.0));
+        let p = o.set(m, k("b"), Any::move_from(4.0));
+        let q = p.set(m, k("c"), m.new_js_array([]));
+        assert_eq!(o.len(), 3);
+        assert_eq!(q.len(), 4);
+        assert_eq!(o.get(&key("b")).cloned(), Some(Any::move_from(1.0)));
+        assert_eq!(p.get(&key("b")).cloned(), Some(Any::move_from(4.0)));
+        assert!(q.has(&key("c")));
+        assert!(!p.has(&key("c")));
+        assert_eq!(to_json(o.to_any(m)).unwrap(), r#"{"1":2,"b":1,"a":3}"#);
+        assert_eq!(to_json(p.to_any(m)).unwrap(), r#"{"1":2,"b":4,"a":3}"#);
+        assert_eq!(
+            to_json(q.to_any(m)).unwrap(),
+            r#"{"1":2,"b":4,"a":3,"c":[]}"#
+        );
+        assert_eq!(
+            to_djs(q.to_any(m), false).unwrap(),
+            r#"export default {"1":2,"b":4,"a":3,"c":[]}"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_spread() {
+        let m = Global();
+        let k = |s: &str| new_string(m, key(s)).to_ref();
+        let a = new_object(
+            m,
+            [(k("x"), Any::move_from(1.0)), (k("y"), Any::move_from(2.0))],
+        );
+        let o = PersistentObject::from_object(m, &a);
+        let p = o.spread(
+            m,
+            [(k("z"), Any::move_from(3.0)), (k("x"), Any::move_from(4.0))],
+        );
+        assert_eq!(to_json(o.to_any(m)).unwrap(), r#"{"x":1,"y":2}"#);
+        assert_eq!(to_json(p.to_any(m)).unwrap(), r#"{"x":4,"y":2,"z":3}"#);
+        let keys: Vec<_> = p
+            .iter()
+            .map(|(k, _)| String::from_utf16(k.items()).unwrap())
+            .collect();
+        assert_eq!(keys, ["x", "y", "z"]);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_any() {
+        let m = Global();
+        let k = |s: &str| new_string(m, key(s)).to_ref();
+        let o = PersistentObject::new(m).set(m, k("a"), Any::move_from(1.0));
+        let shared = o.to_any(m);
+        let p = o
+            .set(m, k("b"), shared.clone())
+            .set(m, k("c"), shared.clone());
+        let any = p.to_any(m);
+        assert_eq!(any.get_type(), Type::Object);
+        assert_eq!(any.len(), Some(3));
+        assert!(any.has(&key("b")));
+        assert_eq!(any.get(&key("b")), Some(shared));
+        let keys: Vec<_> = any
+            .keys()
+            .unwrap()
+            .iter()
+            .map(|k| k.items().to_vec())
+            .collect();
+        assert_eq!(keys, [key("a"), key("b"), key("c")]);
+        // A persistent value that is referred twice is a const, like a plain object.
+        assert_eq!(
+            to_djs(any.clone(), false).unwrap(),
+            r#"const _0={"a":1};export default {"a":1,"b":_0,"c":_0}"#
+        );
+        // With structural deduplication, it's equal to a plain object with the same properties.
+        let plain = m.new_js_object([(k("a"), Any::move_from(1.0))]);
+        let both = m.new_js_array([any, plain]);
+        let options = DjsOptions {
+            structural: true,
+            ..DjsOptions::default()
+        };
+        assert_eq!(
+            to_djs_with_options(both, false, &options).unwrap(),
+            r#"const _0={"a":1};export default [{"a":1,"b":_0,"c":_0},_0]"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_large() {
+        let m = Global();
+        let mut o = PersistentObject::new(m);
+        for i in 0..500 {
+            let k = new_string(m, key(&format!("k{i}"))).to_ref();
+            o = o.set(m, k, Any::move_from(i as f64));
+        }
+        assert_eq!(o.len(), 500);
+        for i in 0..500 {
+            assert_eq!(
+                o.get(&key(&format!("k{i}"))).cloned(),
+                Some(Any::move_from(i as f64))
+            );
+        }
+        let a = o.to_object(m).to_ref();
+        assert_eq!(a.len(), 500);
+        assert_eq!(a.get(&key("k499")), Some(&Any::move_from(499.0)));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_drop() {
+        let local = Local::default();
+        {
+            let k = |s: &str| new_string(&local, key(s)).to_ref();
+            let o = PersistentObject::new(&local).set(&local, k("a"), Any::move_from(1.0));
+            let _p =
+                o.set(&local, k("a"), Any::move_from(2.0))
+                    .set(&local, k("b"), o.to_any(&local));
+            let _a = o.to_object(&local);
+        }
+        assert_eq!(local.size(), 0);
+    }
+}
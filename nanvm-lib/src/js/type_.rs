@@ -7,4 +7,6 @@ pub enum Type {
     Object = 4,
     Array = 5,
     Bigint = 6,
+    Function = 7,
+    Undefined = 8,
 }
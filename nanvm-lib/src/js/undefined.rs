@@ -0,0 +1,23 @@
+use crate::common::{bit_subset64::BitSubset64, cast::Cast};
+
+use super::{bitset::UNDEFINED, value_cast::ValueCast};
+
+pub struct Undefined();
+
+impl Cast<u64> for Undefined {
+    #[inline(always)]
+    fn cast(self) -> u64 {
+        0
+    }
+}
+
+impl Cast<Undefined> for u64 {
+    #[inline(always)]
+    fn cast(self) -> Undefined {
+        Undefined()
+    }
+}
+
+impl ValueCast for Undefined {
+    const SUBSET: BitSubset64<Undefined> = UNDEFINED;
+}
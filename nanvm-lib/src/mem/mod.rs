@@ -3,7 +3,7 @@ pub mod block;
 pub mod block_header;
 mod constructor;
 mod field_layout;
-mod fixed;
+pub mod fixed;
 pub mod flexible_array;
 pub mod global;
 pub mod local;
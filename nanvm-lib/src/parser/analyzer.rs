@@ -1,34 +1,181 @@
-use super::shared::DataType;
-use crate::ast::Module;
+use std::{collections::BTreeMap, mem::take};
+
+use super::shared::{DataType, ParsingStatus};
+use crate::ast::{Body, Expression, Function, Module, Property};
 use crate::common::default::default;
+use crate::js::{
+    any::Any, interner::Interner, js_string::JsStringRef, null::Null, undefined::Undefined,
+};
 use crate::mem::manager::{Dealloc, Manager};
-use crate::tokenizer::{create_transition_maps, JsonToken, TokenizerState, TransitionMaps};
+use crate::tokenizer::span::{Span, SpanTracker, SpannedToken};
+use crate::tokenizer::{
+    create_transition_maps, ErrorType, JsonToken, TokenizerState, TransitionMaps,
+};
 
 #[derive(Default)]
 pub struct AnalyzerParameters {
-    data_type: DataType,
+    /// The format the module starts in. Like in the parser, `Json` and `Djs` turn into `Cjs` or
+    /// `Mjs` as soon as a statement of that format is found.
+    pub data_type: DataType,
 }
 
-#[derive(Default)]
+#[derive(Debug, PartialEq)]
+pub enum AnalyzerError {
+    InvalidToken(ErrorType),
+    UnexpectedToken,
+    UnexpectedEnd,
+    NewLineExpected,
+    WrongExportStatement,
+    WrongConstStatement,
+    WrongImportStatement,
+    WrongRequireStatement,
+    UnknownIdentifier(String),
+    DuplicateName(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AnalyzerWarning {
+    UnusedConst(String),
+    UnusedImport(String),
+    /// An object literal defines the key more than once. The last value wins.
+    DuplicateKey(String),
+}
+
+#[derive(Debug, PartialEq)]
 pub enum AnalyzerDiagnostic {
-    #[default]
-    OK,
-    // TODO: add error, warning diagnostics.
+    Error {
+        error: AnalyzerError,
+        span: Span,
+        /// Description of the offending token, if the error is about a token.
+        token: Option<String>,
+        /// Descriptions of tokens that would have been accepted in place of `token`.
+        expected: &'static [&'static str],
+    },
+    Warning {
+        warning: AnalyzerWarning,
+        span: Span,
+    },
+}
+
+impl AnalyzerDiagnostic {
+    pub fn is_error(&self) -> bool {
+        matches!(self, AnalyzerDiagnostic::Error { .. })
+    }
 }
 
 pub struct AnalyzerResults<D: Dealloc> {
+    pub data_type: DataType,
     pub module: Module<D>,
+    /// Locations of the paths in `module.import`, in the same order.
+    pub import_spans: Vec<Span>,
     pub diagnostics: Vec<AnalyzerDiagnostic>,
+    /// The interner of the analysis with the strings of `module`, to continue the parse session.
+    pub interner: Interner<D>,
+    pub names: ModuleNames,
+    /// The number of comments in the source. `module` doesn't keep them.
+    pub comments: usize,
+}
+
+/// The names that a module binds in its source, to write the module back with them.
+#[derive(Debug, Default, PartialEq)]
+pub struct ModuleNames {
+    /// The names of `module.body.local`, in the same order.
+    pub consts: Vec<String>,
+    /// The names of `module.import` given by `import` statements, in the same order. The first
+    /// name is kept if a path is imported more than once, and an import by `require` has no name.
+    pub imports: Vec<Option<String>>,
+}
+
+#[derive(Clone, Copy)]
+enum Binding {
+    Const(u32),
+    Import(u32),
+}
+
+struct Name {
+    binding: Binding,
+    span: Span,
+    used: bool,
+}
+
+enum ValueTarget {
+    Const(String, Span),
+    Result,
+}
+
+/// A complete expression that the next token can still extend, with a call or, if it's a name,
+/// by turning it into the parameter of an arrow function.
+enum Operand<D: Dealloc> {
+    Name(String, Span),
+    Expression(Expression<D>),
+}
+
+struct FunctionFrame<D: Dealloc> {
+    params: Vec<String>,
+    captures: Vec<Expression<D>>,
+    capture_names: BTreeMap<String, u32>,
+}
+
+enum Frame<D: Dealloc> {
+    Array(Vec<Expression<D>>),
+    Object(Vec<Property<D>>, Option<JsStringRef<D>>),
+    /// `(` followed by a parenthesized expression or by parameters of an arrow function.
+    Group(Option<Operand<D>>),
+    Params(Vec<(String, Span)>),
+    /// A callee, arguments and the span of the opening parenthesis.
+    Call(Expression<D>, Vec<Expression<D>>, Span),
+    /// The body of an arrow function.
+    Function(FunctionFrame<D>),
+}
+
+struct ValueState<D: Dealloc> {
+    target: ValueTarget,
+    status: ParsingStatus,
+    stack: Vec<Frame<D>>,
+    /// An index of the import that is being read by `require(...)`.
+    require: u32,
+    /// The complete expression in `ParsingStatus::Operand`.
+    operand: Option<Operand<D>>,
+}
+
+#[derive(Default)]
+enum Status<D: Dealloc> {
+    #[default]
+    Initial,
+    Export,
+    Module,
+    ModuleDot,
+    ModuleDotExports,
+    Const,
+    ConstId(String, Span),
+    Import,
+    ImportId(String, Span),
+    ImportIdFrom(String, Span),
+    Value(ValueState<D>),
+    End,
+    Error,
 }
 
 pub struct AnalyzerState<M: Manager> {
-    parameters: AnalyzerParameters,
+    data_type: DataType,
     tokenizer_state: TokenizerState<M::Dealloc>,
     tokenizer_maps: TransitionMaps<M>,
     diagnostics_len: usize,
-    // TODO: add line number, column number tracking fields (needed for diagnostics).
+    /// The tokens accepted in the status the last token was dispatched in, for error messages.
+    accepted: &'static [&'static str],
+    span_tracker: SpanTracker,
+    status: Status<M::Dealloc>,
+    new_line: bool,
+    names: BTreeMap<String, Name>,
+    imports: BTreeMap<String, u32>,
+    import_spans: Vec<Span>,
     module: Module<M::Dealloc>,
     diagnostics: Vec<AnalyzerDiagnostic>,
+    interner: Interner<M::Dealloc>,
+    module_names: ModuleNames,
+    /// The name of the const whose value is being analyzed.
+    pending_const: Option<(String, Span)>,
+    comments: usize,
 }
 
 impl<M: Manager + 'static> AnalyzerState<M> {
@@ -36,38 +183,1123 @@ impl<M: Manager + 'static> AnalyzerState<M> {
     /// immediately after creation (since `parameters` value can be inconsistent).
     pub fn new(parameters: AnalyzerParameters) -> Self {
         Self {
-            parameters,
+            data_type: parameters.data_type,
             tokenizer_state: default(),
             tokenizer_maps: create_transition_maps(),
             module: default(),
             diagnostics: default(),
             diagnostics_len: 0,
+            accepted: &[],
+            span_tracker: default(),
+            status: default(),
+            new_line: true,
+            names: default(),
+            imports: default(),
+            import_spans: default(),
+            interner: default(),
+            module_names: default(),
+            pending_const: None,
+            comments: 0,
         }
     }
 
+    /// Uses `interner` for string literals, object keys and import paths, so equal strings share
+    /// one `JsString` with the previous analyses of a parse session.
+    pub fn with_interner(self, interner: Interner<M::Dealloc>) -> Self {
+        Self { interner, ..self }
+    }
+
     /// Updates analyzer state with a next input character; the result is the increment in the count
     /// of `diagnostics`. It's up to the caller to check what was added at the end of `diagnostics`
     ///  - are there any fatal errors, from the point of view of the current parsing session?
     pub fn push_mut(&mut self, manager: M, c: char) -> usize {
-        for token in self
-            .tokenizer_state
-            .push_mut(manager, c, &self.tokenizer_maps)
-        {
-            self.process_token(token);
+        let comment = self.tokenizer_state.is_comment();
+        for token in self.tokenizer_state.push_spanned(
+            manager,
+            c,
+            &self.tokenizer_maps,
+            &mut self.span_tracker,
+        ) {
+            self.process_token(manager, token);
         }
+        if !comment && self.tokenizer_state.is_comment() {
+            self.comments += 1;
+        }
+        self.diagnostics_increment()
+    }
+
+    /// Updates analyzer state with a token that was produced by another tokenizer, the same way as
+    /// `push_mut` does with a character.
+    pub fn push_token_mut(&mut self, manager: M, token: SpannedToken<M::Dealloc>) -> usize {
+        self.process_token(manager, token);
+        self.diagnostics_increment()
+    }
+
+    fn diagnostics_increment(&mut self) -> usize {
         let prior_diagnostics_len = self.diagnostics_len;
         self.diagnostics_len = self.diagnostics.len();
         self.diagnostics_len - prior_diagnostics_len
     }
 
-    /// Completes the analysis.
-    pub fn end(self) -> AnalyzerResults<M::Dealloc> {
-        // TODO: in case the current state is not a valid end state, add an error to self.diagnostics.
+    /// Completes the analysis. Errors are reported if the input ends in the middle of a statement
+    /// or without a result, and warnings are reported for unused consts and imports.
+    pub fn end(mut self, manager: M) -> AnalyzerResults<M::Dealloc> {
+        for token in take(&mut self.tokenizer_state).end_spanned(manager, &self.span_tracker) {
+            self.process_token(manager, token);
+        }
+        let position = self.span_tracker.position();
+        if let Status::Value(_) = self.status {
+            // The end of input completes the statement like a new line does.
+            let span = Span {
+                begin: position,
+                end: position,
+            };
+            self.dispatch(manager, JsonToken::NewLine, span);
+        }
+        match self.status {
+            Status::End | Status::Error => {}
+            // The missing result is likely in a statement with an error.
+            _ if self.diagnostics.iter().any(AnalyzerDiagnostic::is_error) => {}
+            _ => {
+                let expected = self.expected();
+                let span = Span {
+                    begin: position,
+                    end: position,
+                };
+                self.error(AnalyzerError::UnexpectedEnd, span);
+                self.describe_error(span, "end of input".to_owned(), expected);
+            }
+        }
+        let mut unused = take(&mut self.names)
+            .into_iter()
+            .filter(|(_, name)| !name.used)
+            .collect::<Vec<_>>();
+        unused.sort_by_key(|(_, name)| name.span.begin);
+        self.diagnostics
+            .extend(unused.into_iter().map(|(id, name)| {
+                let warning = match name.binding {
+                    Binding::Const(_) => AnalyzerWarning::UnusedConst(id),
+                    Binding::Import(_) => AnalyzerWarning::UnusedImport(id),
+                };
+                AnalyzerDiagnostic::Warning {
+                    warning,
+                    span: name.span,
+                }
+            }));
         AnalyzerResults {
+            data_type: self.data_type,
+            import_spans: self.import_spans,
             module: self.module,
             diagnostics: self.diagnostics,
+            interner: self.interner,
+            names: self.module_names,
+            comments: self.comments,
+        }
+    }
+
+    fn error(&mut self, error: AnalyzerError, span: Span) {
+        self.diagnostics.push(AnalyzerDiagnostic::Error {
+            error,
+            span,
+            token: None,
+            expected: &[],
+        });
+        self.status = Status::Error;
+        self.new_line = false;
+        if let Some((id, span)) = self.pending_const.take() {
+            // Defines the const without a value, so its uses aren't reported as unknown names.
+            if !self.names.contains_key(&id) {
+                let index = self.module.body.local.len() as u32;
+                self.module.body.local.push(Expression::Void);
+                self.module_names.consts.push(id.clone());
+                let binding = Binding::Const(index);
+                let used = true;
+                self.names.insert(
+                    id,
+                    Name {
+                        binding,
+                        span,
+                        used,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Attaches the token description to the last error if the error is located at the token.
+    fn describe_error(&mut self, at: Span, description: String, accepted: &'static [&'static str]) {
+        if let Some(AnalyzerDiagnostic::Error {
+            span,
+            token,
+            expected,
+            ..
+        }) = self.diagnostics.last_mut()
+        {
+            if *span == at && token.is_none() {
+                *token = Some(description);
+                *expected = accepted;
+            }
+        }
+    }
+
+    /// Skips the tokens after an error up to a line that starts with `const`, `import`, `export` or
+    /// `module`, so one error doesn't hide the errors in the next statements. Returns true if the
+    /// analysis continues with `token`.
+    fn recover(&mut self, token: &JsonToken<M::Dealloc>) -> bool {
+        match token {
+            JsonToken::NewLine => self.new_line = true,
+            JsonToken::Id(s)
+                if self.new_line
+                    && matches!(s.as_str(), "const" | "import" | "export" | "module") =>
+            {
+                self.status = Status::Initial;
+                return true;
+            }
+            _ => self.new_line = false,
+        }
+        false
+    }
+
+    /// Describes the tokens accepted in the current status, for error messages.
+    fn expected(&self) -> &'static [&'static str] {
+        match &self.status {
+            Status::Initial if self.new_line => {
+                &["`const`", "`import`", "`export`", "`module`", "value"]
+            }
+            Status::Initial => &["new line"],
+            Status::Export => &["`default`"],
+            Status::Module => &["`.`"],
+            Status::ModuleDot => &["`exports`"],
+            Status::ModuleDotExports | Status::ConstId(..) => &["`=`"],
+            Status::Const | Status::Import => &["identifier"],
+            Status::ImportId(..) => &["`from`"],
+            Status::ImportIdFrom(..) => &["string"],
+            Status::Value(value) => value.status.expected(self.data_type.is_djs()),
+            Status::End => &["end of input"],
+            Status::Error => &[],
+        }
+    }
+
+    fn process_token(&mut self, manager: M, token: SpannedToken<M::Dealloc>) {
+        let SpannedToken { token, span } = token;
+        if let Status::Error = self.status {
+            if !self.recover(&token) {
+                return;
+            }
+        }
+        let len = self.diagnostics.len();
+        let description = token.to_string();
+        if let JsonToken::ErrorToken(e) = token {
+            self.accepted = self.expected();
+            self.error(AnalyzerError::InvalidToken(e), span);
+        } else {
+            self.dispatch(manager, token, span);
+        }
+        if self.diagnostics.len() > len {
+            self.describe_error(span, description, self.accepted);
+        }
+    }
+
+    /// Processes a token in the current status. A token can be dispatched more than once, when it
+    /// completes an expression and then continues the enclosing one.
+    fn dispatch(&mut self, manager: M, token: JsonToken<M::Dealloc>, span: Span) {
+        if let Status::Error = self.status {
+            return;
+        }
+        self.accepted = self.expected();
+        match take(&mut self.status) {
+            Status::Value(value) => self.process_value_token(manager, value, token, span),
+            status => self.process_statement_token(manager, status, token, span),
+        }
+    }
+
+    fn process_statement_token(
+        &mut self,
+        manager: M,
+        status: Status<M::Dealloc>,
+        token: JsonToken<M::Dealloc>,
+        span: Span,
+    ) {
+        self.status = match (status, token) {
+            (
+                status @ (Status::Initial | Status::End),
+                JsonToken::NewLine | JsonToken::Semicolon,
+            ) => {
+                self.new_line = true;
+                status
+            }
+            (Status::End, _) => return self.error(AnalyzerError::UnexpectedToken, span),
+            (Status::Initial, _) if !self.new_line => {
+                return self.error(AnalyzerError::NewLineExpected, span)
+            }
+            (Status::Initial, JsonToken::Id(s)) if s == "const" => {
+                self.data_type = self.data_type.to_djs();
+                Status::Const
+            }
+            (Status::Initial, JsonToken::Id(s))
+                if s == "export" && self.data_type.is_mjs_compatible() =>
+            {
+                self.data_type = DataType::Mjs;
+                Status::Export
+            }
+            (Status::Initial, JsonToken::Id(s))
+                if s == "module" && self.data_type.is_cjs_compatible() =>
+            {
+                self.data_type = DataType::Cjs;
+                Status::Module
+            }
+            (Status::Initial, JsonToken::Id(s))
+                if s == "import" && self.data_type.is_mjs_compatible() =>
+            {
+                self.data_type = DataType::Mjs;
+                Status::Import
+            }
+            (Status::Initial, token) => {
+                return self.process_value_token(
+                    manager,
+                    ValueState::new(ValueTarget::Result),
+                    token,
+                    span,
+                )
+            }
+            (Status::Export, JsonToken::Id(s)) if s == "default" => {
+                Status::Value(ValueState::new(ValueTarget::Result))
+            }
+            (Status::Module, JsonToken::Dot) => Status::ModuleDot,
+            (Status::ModuleDot, JsonToken::Id(s)) if s == "exports" => Status::ModuleDotExports,
+            (Status::ModuleDotExports, JsonToken::Equals) => {
+                Status::Value(ValueState::new(ValueTarget::Result))
+            }
+            (Status::Export | Status::Module | Status::ModuleDot | Status::ModuleDotExports, _) => {
+                return self.error(AnalyzerError::WrongExportStatement, span)
+            }
+            (Status::Const, JsonToken::Id(s)) => Status::ConstId(s, span),
+            (Status::ConstId(id, id_span), JsonToken::Equals) => {
+                self.pending_const = Some((id.clone(), id_span));
+                Status::Value(ValueState::new(ValueTarget::Const(id, id_span)))
+            }
+            (Status::Const | Status::ConstId(..), _) => {
+                return self.error(AnalyzerError::WrongConstStatement, span)
+            }
+            (Status::Import, JsonToken::Id(s)) => Status::ImportId(s, span),
+            (Status::ImportId(id, id_span), JsonToken::Id(s)) if s == "from" => {
+                Status::ImportIdFrom(id, id_span)
+            }
+            (Status::ImportIdFrom(id, id_span), JsonToken::String(path)) => {
+                let index = self.import_index(manager, String::from_utf16_lossy(&path), span);
+                self.new_line = false;
+                self.declare(id, Binding::Import(index), id_span);
+                return;
+            }
+            (Status::Import | Status::ImportId(..) | Status::ImportIdFrom(..), _) => {
+                return self.error(AnalyzerError::WrongImportStatement, span)
+            }
+            (Status::Value(_) | Status::Error, _) => unreachable!(),
+        };
+    }
+
+    fn process_value_token(
+        &mut self,
+        manager: M,
+        mut value: ValueState<M::Dealloc>,
+        token: JsonToken<M::Dealloc>,
+        span: Span,
+    ) {
+        if let JsonToken::NewLine = token {
+            // A new line completes the statement unless it's inside brackets.
+            let completes = matches!(value.status, ParsingStatus::Operand)
+                && value.stack.iter().all(|f| matches!(f, Frame::Function(_)));
+            if !completes {
+                self.status = Status::Value(value);
+                return;
+            }
+        }
+        match (take(&mut value.status), token) {
+            (ParsingStatus::Operand, JsonToken::OpeningParenthesis) => {
+                let operand = value.operand.take().unwrap();
+                if let Some(callee) = self.resolve(&mut value.stack, operand) {
+                    self.data_type = self.data_type.to_djs();
+                    value.stack.push(Frame::Call(callee, default(), span));
+                    self.status = Status::Value(value.with_status(ParsingStatus::CallBegin));
+                }
+            }
+            (ParsingStatus::Operand, JsonToken::Arrow)
+                if matches!(value.operand, Some(Operand::Name(..))) =>
+            {
+                let Some(Operand::Name(name, _)) = value.operand.take() else {
+                    unreachable!()
+                };
+                self.begin_function(value, [name].into())
+            }
+            (ParsingStatus::Operand, token) => {
+                let operand = value.operand.take().unwrap();
+                self.complete_operand(manager, value, operand, token, span)
+            }
+            (
+                ParsingStatus::ArrayBegin | ParsingStatus::ArrayComma | ParsingStatus::ArrayValue,
+                JsonToken::ArrayEnd,
+            ) => {
+                let Some(Frame::Array(items)) = value.stack.pop() else {
+                    unreachable!()
+                };
+                self.push_operand(value, Operand::Expression(Expression::Array(items)))
+            }
+            (
+                ParsingStatus::ObjectBegin
+                | ParsingStatus::ObjectComma
+                | ParsingStatus::ObjectValue,
+                JsonToken::ObjectEnd,
+            ) => {
+                let Some(Frame::Object(properties, _)) = value.stack.pop() else {
+                    unreachable!()
+                };
+                self.push_operand(value, Operand::Expression(Expression::Object(properties)))
+            }
+            (
+                ParsingStatus::CallBegin | ParsingStatus::CallComma | ParsingStatus::CallValue,
+                JsonToken::ClosingParenthesis,
+            ) => {
+                let Some(Frame::Call(callee, args, open)) = value.stack.pop() else {
+                    unreachable!()
+                };
+                let span = Span {
+                    begin: open.begin,
+                    end: span.end,
+                };
+                let call = Expression::Call(Box::new(callee), args, span);
+                self.push_operand(value, Operand::Expression(call))
+            }
+            (ParsingStatus::GroupBegin, JsonToken::ClosingParenthesis) => {
+                *value.stack.last_mut().unwrap() = Frame::Params(default());
+                self.status = Status::Value(value.with_status(ParsingStatus::ParamsEnd))
+            }
+            (ParsingStatus::GroupValue, JsonToken::ClosingParenthesis) => {
+                let Some(Frame::Group(Some(operand))) = value.stack.pop() else {
+                    unreachable!()
+                };
+                self.push_operand(value, operand)
+            }
+            (ParsingStatus::GroupValue, JsonToken::Comma)
+                if matches!(
+                    value.stack.last(),
+                    Some(Frame::Group(Some(Operand::Name(..))))
+                ) =>
+            {
+                let Some(Frame::Group(Some(Operand::Name(name, name_span)))) = value.stack.pop()
+                else {
+                    unreachable!()
+                };
+                value.stack.push(Frame::Params([(name, name_span)].into()));
+                self.status = Status::Value(value.with_status(ParsingStatus::ParamsComma))
+            }
+            (ParsingStatus::ParamsComma, JsonToken::Id(name)) => {
+                let Some(Frame::Params(params)) = value.stack.last_mut() else {
+                    unreachable!()
+                };
+                params.push((name, span));
+                self.status = Status::Value(value.with_status(ParsingStatus::ParamsId))
+            }
+            (ParsingStatus::ParamsId, JsonToken::Comma) => {
+                self.status = Status::Value(value.with_status(ParsingStatus::ParamsComma))
+            }
+            (
+                ParsingStatus::ParamsId | ParsingStatus::ParamsComma,
+                JsonToken::ClosingParenthesis,
+            ) => self.status = Status::Value(value.with_status(ParsingStatus::ParamsEnd)),
+            (ParsingStatus::ParamsEnd, JsonToken::Arrow) => {
+                let Some(Frame::Params(params)) = value.stack.pop() else {
+                    unreachable!()
+                };
+                let mut names = Vec::with_capacity(params.len());
+                for (name, name_span) in params {
+                    if names.contains(&name) {
+                        return self.error(AnalyzerError::DuplicateName(name), name_span);
+                    }
+                    names.push(name);
+                }
+                self.begin_function(value, names)
+            }
+            (
+                ParsingStatus::Initial
+                | ParsingStatus::ObjectColon
+                | ParsingStatus::ArrayBegin
+                | ParsingStatus::ArrayComma
+                | ParsingStatus::CallBegin
+                | ParsingStatus::CallComma
+                | ParsingStatus::GroupBegin
+                | ParsingStatus::ArrowBody,
+                token,
+            ) => match token {
+                JsonToken::ArrayBegin => {
+                    value.stack.push(Frame::Array(default()));
+                    self.status = Status::Value(value.with_status(ParsingStatus::ArrayBegin));
+                }
+                JsonToken::ObjectBegin => {
+                    value.stack.push(Frame::Object(default(), None));
+                    self.status = Status::Value(value.with_status(ParsingStatus::ObjectBegin));
+                }
+                JsonToken::OpeningParenthesis => {
+                    value.stack.push(Frame::Group(None));
+                    self.status = Status::Value(value.with_status(ParsingStatus::GroupBegin));
+                }
+                JsonToken::Id(s) if s == "require" && self.data_type.is_cjs_compatible() => {
+                    self.data_type = DataType::Cjs;
+                    self.status = Status::Value(value.with_status(ParsingStatus::ImportBegin));
+                }
+                JsonToken::Id(s) if s == "void" => {
+                    self.data_type = self.data_type.to_djs();
+                    self.status = Status::Value(value.with_status(ParsingStatus::Void));
+                }
+                JsonToken::Id(s)
+                    if !matches!(
+                        s.as_str(),
+                        "null" | "true" | "false" | "undefined" | "NaN" | "Infinity"
+                    ) =>
+                {
+                    self.push_operand(value, Operand::Name(s, span))
+                }
+                token => {
+                    if let Some(expression) = self.token_to_expression(manager, token, span) {
+                        self.push_operand(value, Operand::Expression(expression))
+                    }
+                }
+            },
+            (ParsingStatus::Void, JsonToken::Number(0.0)) => {
+                let undefined = Expression::Value(Any::move_from(Undefined()));
+                self.push_operand(value, Operand::Expression(undefined))
+            }
+            (ParsingStatus::ArrayValue, JsonToken::Comma) => {
+                self.status = Status::Value(value.with_status(ParsingStatus::ArrayComma))
+            }
+            (ParsingStatus::ObjectValue, JsonToken::Comma) => {
+                self.status = Status::Value(value.with_status(ParsingStatus::ObjectComma))
+            }
+            (ParsingStatus::CallValue, JsonToken::Comma) => {
+                self.status = Status::Value(value.with_status(ParsingStatus::CallComma))
+            }
+            (ParsingStatus::ObjectBegin | ParsingStatus::ObjectComma, JsonToken::String(s)) => {
+                self.push_key(manager, value, s, ParsingStatus::ObjectKey, span)
+            }
+            (ParsingStatus::ObjectBegin | ParsingStatus::ObjectComma, JsonToken::Id(s))
+                if self.data_type.is_djs() =>
+            {
+                let key = s.encode_utf16().collect();
+                self.push_key(
+                    manager,
+                    value,
+                    key,
+                    ParsingStatus::ObjectIdKey(s, span),
+                    span,
+                )
+            }
+            (ParsingStatus::ObjectKey | ParsingStatus::ObjectIdKey(..), JsonToken::Colon) => {
+                self.status = Status::Value(value.with_status(ParsingStatus::ObjectColon))
+            }
+            (ParsingStatus::ObjectIdKey(name, name_span), token)
+                if matches!(token, JsonToken::Comma | JsonToken::ObjectEnd) =>
+            {
+                // A shorthand property: the key is also the name of the value.
+                self.complete_operand(manager, value, Operand::Name(name, name_span), token, span)
+            }
+            (ParsingStatus::ImportBegin, JsonToken::OpeningParenthesis) => {
+                self.status = Status::Value(value.with_status(ParsingStatus::ImportValue))
+            }
+            (ParsingStatus::ImportValue, JsonToken::String(path)) => {
+                value.require = self.import_index(manager, String::from_utf16_lossy(&path), span);
+                self.status = Status::Value(value.with_status(ParsingStatus::ImportEnd))
+            }
+            (ParsingStatus::ImportEnd, JsonToken::ClosingParenthesis) => {
+                let index = value.require;
+                self.push_operand(value, Operand::Expression(Expression::ArgRef(index)))
+            }
+            (
+                ParsingStatus::ImportBegin | ParsingStatus::ImportValue | ParsingStatus::ImportEnd,
+                _,
+            ) => self.error(AnalyzerError::WrongRequireStatement, span),
+            _ => self.error(AnalyzerError::UnexpectedToken, span),
+        }
+    }
+
+    /// Converts a literal token to an expression.
+    fn token_to_expression(
+        &mut self,
+        manager: M,
+        token: JsonToken<M::Dealloc>,
+        span: Span,
+    ) -> Option<Expression<M::Dealloc>> {
+        let any = match token {
+            JsonToken::Number(f) => {
+                // `-Infinity` isn't JSON.
+                if f.is_infinite() {
+                    self.data_type = self.data_type.to_djs();
+                }
+                Any::move_from(f)
+            }
+            JsonToken::String(s) => Any::move_from(self.interner.intern(manager, &s)),
+            JsonToken::BigInt(b) => Any::move_from(b.to_ref()),
+            JsonToken::Id(s) if s == "null" => Any::move_from(Null()),
+            JsonToken::Id(s) if s == "true" => Any::move_from(true),
+            JsonToken::Id(s) if s == "false" => Any::move_from(false),
+            JsonToken::Id(s) if s == "undefined" => {
+                self.data_type = self.data_type.to_djs();
+                Any::move_from(Undefined())
+            }
+            JsonToken::Id(s) if s == "NaN" => {
+                self.data_type = self.data_type.to_djs();
+                Any::move_from(f64::NAN)
+            }
+            JsonToken::Id(s) if s == "Infinity" => {
+                self.data_type = self.data_type.to_djs();
+                Any::move_from(f64::INFINITY)
+            }
+            _ => {
+                self.error(AnalyzerError::UnexpectedToken, span);
+                return None;
+            }
+        };
+        Some(Expression::Value(any))
+    }
+
+    /// Resolves a name to a reference: to a parameter of the innermost function, to a value the
+    /// function captures or to a const or an import of the module.
+    fn resolve_name(
+        &mut self,
+        stack: &mut [Frame<M::Dealloc>],
+        name: &str,
+    ) -> Option<Expression<M::Dealloc>> {
+        let Some(i) = stack.iter().rposition(|f| matches!(f, Frame::Function(_))) else {
+            let name = self.names.get_mut(name)?;
+            name.used = true;
+            return Some(match name.binding {
+                Binding::Const(index) => Expression::LocalRef(index),
+                Binding::Import(index) => Expression::ArgRef(index),
+            });
+        };
+        let (outer, inner) = stack.split_at_mut(i);
+        let Frame::Function(function) = &mut inner[0] else {
+            unreachable!()
+        };
+        if let Some(index) = function.params.iter().position(|p| p == name) {
+            return Some(Expression::ArgRef(index as u32));
+        }
+        if let Some(index) = function.capture_names.get(name) {
+            return Some(Expression::CaptureRef(*index));
         }
+        let expression = self.resolve_name(outer, name)?;
+        let index = function.captures.len() as u32;
+        function.captures.push(expression);
+        function.capture_names.insert(name.to_owned(), index);
+        Some(Expression::CaptureRef(index))
     }
 
-    fn process_token(&mut self, _token: JsonToken<M::Dealloc>) {}
+    fn resolve(
+        &mut self,
+        stack: &mut [Frame<M::Dealloc>],
+        operand: Operand<M::Dealloc>,
+    ) -> Option<Expression<M::Dealloc>> {
+        match operand {
+            Operand::Expression(expression) => Some(expression),
+            Operand::Name(name, span) => {
+                let expression = self.resolve_name(stack, &name);
+                if expression.is_none() {
+                    let description = format!("identifier `{name}`");
+                    self.error(AnalyzerError::UnknownIdentifier(name), span);
+                    self.describe_error(span, description, &[]);
+                }
+                expression
+            }
+        }
+    }
+
+    fn begin_function(&mut self, mut value: ValueState<M::Dealloc>, params: Vec<String>) {
+        self.data_type = self.data_type.to_djs();
+        value.stack.push(Frame::Function(FunctionFrame {
+            params,
+            captures: default(),
+            capture_names: default(),
+        }));
+        self.status = Status::Value(value.with_status(ParsingStatus::ArrowBody));
+    }
+
+    fn push_key(
+        &mut self,
+        manager: M,
+        mut value: ValueState<M::Dealloc>,
+        key: Vec<u16>,
+        status: ParsingStatus,
+        span: Span,
+    ) {
+        let Some(Frame::Object(properties, k)) = value.stack.last_mut() else {
+            unreachable!()
+        };
+        let key = self.interner.intern(manager, &key);
+        if properties.iter().any(|(p, _)| p.items() == key.items()) {
+            self.diagnostics.push(AnalyzerDiagnostic::Warning {
+                warning: AnalyzerWarning::DuplicateKey(String::from_utf16_lossy(key.items())),
+                span,
+            });
+        }
+        *k = Some(key);
+        self.status = Status::Value(value.with_status(status));
+    }
+
+    fn push_operand(&mut self, mut value: ValueState<M::Dealloc>, operand: Operand<M::Dealloc>) {
+        value.operand = Some(operand);
+        self.status = Status::Value(value.with_status(ParsingStatus::Operand));
+    }
+
+    /// Adds a complete expression to the innermost frame, or completes the statement, and then
+    /// dispatches `token` that follows the expression.
+    fn complete_operand(
+        &mut self,
+        manager: M,
+        mut value: ValueState<M::Dealloc>,
+        operand: Operand<M::Dealloc>,
+        token: JsonToken<M::Dealloc>,
+        span: Span,
+    ) {
+        if let Some(Frame::Group(group)) = value.stack.last_mut() {
+            // A name in parentheses can still be a parameter.
+            *group = Some(operand);
+            self.status = Status::Value(value.with_status(ParsingStatus::GroupValue));
+            return self.dispatch(manager, token, span);
+        }
+        let Some(expression) = self.resolve(&mut value.stack, operand) else {
+            return;
+        };
+        match value.stack.last_mut() {
+            Some(Frame::Array(items)) => {
+                items.push(expression);
+                self.status = Status::Value(value.with_status(ParsingStatus::ArrayValue));
+            }
+            Some(Frame::Object(properties, key)) => {
+                properties.push((key.take().unwrap(), expression));
+                self.status = Status::Value(value.with_status(ParsingStatus::ObjectValue));
+            }
+            Some(Frame::Call(_, args, _)) => {
+                args.push(expression);
+                self.status = Status::Value(value.with_status(ParsingStatus::CallValue));
+            }
+            Some(Frame::Function(_)) => {
+                let Some(Frame::Function(function)) = value.stack.pop() else {
+                    unreachable!()
+                };
+                let function = Function {
+                    captures: function.captures,
+                    params: function.params.len() as u32,
+                    body: Body {
+                        local: default(),
+                        result: expression,
+                    },
+                };
+                value.operand = Some(Operand::Expression(Expression::Function(Box::new(
+                    function,
+                ))));
+                self.status = Status::Value(value.with_status(ParsingStatus::Operand));
+            }
+            Some(Frame::Group(_) | Frame::Params(_)) => unreachable!(),
+            None => {
+                self.new_line = false;
+                match value.target {
+                    ValueTarget::Const(id, span) => {
+                        self.pending_const = None;
+                        let index = self.module.body.local.len() as u32;
+                        self.module.body.local.push(expression);
+                        self.declare(id, Binding::Const(index), span);
+                    }
+                    ValueTarget::Result => {
+                        self.module.body.result = expression;
+                        self.status = Status::End;
+                    }
+                }
+            }
+        }
+        self.dispatch(manager, token, span)
+    }
+
+    /// Returns the index of the import `path`, adding it to the module imports if it's new.
+    fn import_index(&mut self, manager: M, path: String, span: Span) -> u32 {
+        let module = &mut self.module;
+        let import_spans = &mut self.import_spans;
+        let interner = &mut self.interner;
+        *self.imports.entry(path).or_insert_with_key(|path| {
+            let path: Vec<_> = path.encode_utf16().collect();
+            module.import.push(interner.intern(manager, &path));
+            import_spans.push(span);
+            module.import.len() as u32 - 1
+        })
+    }
+
+    fn declare(&mut self, id: String, binding: Binding, span: Span) {
+        if self.names.contains_key(&id) {
+            return self.error(AnalyzerError::DuplicateName(id), span);
+        }
+        match binding {
+            Binding::Const(_) => self.module_names.consts.push(id.clone()),
+            Binding::Import(index) => {
+                let imports = &mut self.module_names.imports;
+                if imports.len() <= index as usize {
+                    imports.resize(index as usize + 1, None);
+                }
+                imports[index as usize].get_or_insert_with(|| id.clone());
+            }
+        }
+        self.names.insert(
+            id,
+            Name {
+                binding,
+                span,
+                used: false,
+            },
+        );
+        self.status = Status::Initial;
+    }
+}
+
+impl<D: Dealloc> ValueState<D> {
+    fn new(target: ValueTarget) -> Self {
+        ValueState {
+            target,
+            status: ParsingStatus::Initial,
+            stack: default(),
+            require: 0,
+            operand: None,
+        }
+    }
+
+    fn with_status(self, status: ParsingStatus) -> Self {
+        ValueState { status, ..self }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        ast::Expression,
+        js::{any::Any, js_string::JsStringRef, undefined::Undefined},
+        mem::{
+            global::{Global, GLOBAL},
+            manager::Dealloc,
+        },
+        parser::shared::DataType,
+        serializer::to_json::to_json,
+        tokenizer::ErrorType,
+    };
+
+    use super::{
+        AnalyzerDiagnostic, AnalyzerError, AnalyzerParameters, AnalyzerResults, AnalyzerState,
+        AnalyzerWarning,
+    };
+
+    fn analyze(input: &str) -> AnalyzerResults<Global> {
+        let mut state = AnalyzerState::new(AnalyzerParameters::default());
+        for c in input.chars() {
+            state.push_mut(GLOBAL, c);
+        }
+        state.end(GLOBAL)
+    }
+
+    fn string<D: Dealloc>(s: &JsStringRef<D>) -> String {
+        to_json(Any::move_from(s.clone())).unwrap()
+    }
+
+    /// Renders an expression the way `notes/bytecode-initial.md` does.
+    fn show<D: Dealloc>(e: &Expression<D>) -> String {
+        match e {
+            Expression::Void => "[\"void\"]".to_owned(),
+            Expression::LocalRef(i) => format!("[\"localRef\",{i}]"),
+            Expression::ArgRef(i) => format!("[\"argRef\",{i}]"),
+            Expression::Value(any) if any.is::<Undefined>() => "[\"undefined\"]".to_owned(),
+            Expression::Value(any) => format!("[\"value\",{}]", to_json(any.clone()).unwrap()),
+            Expression::Object(properties) => {
+                let properties = properties
+                    .iter()
+                    .map(|(k, v)| format!("[{},{}]", string(k), show(v)))
+                    .collect::<Vec<_>>();
+                format!("[\"object\",[{}]]", properties.join(","))
+            }
+            Expression::Array(items) => {
+                let items = items.iter().map(show).collect::<Vec<_>>();
+                format!("[\"array\",[{}]]", items.join(","))
+            }
+            Expression::CaptureRef(i) => format!("[\"captureRef\",{i}]"),
+            Expression::Function(f) => {
+                let captures = f.captures.iter().map(show).collect::<Vec<_>>();
+                format!(
+                    "[\"function\",{},[{}],{}]",
+                    f.params,
+                    captures.join(","),
+                    show(&f.body.result)
+                )
+            }
+            Expression::Call(callee, args, _) => {
+                let args = args.iter().map(show).collect::<Vec<_>>();
+                format!("[\"call\",{},[{}]]", show(callee), args.join(","))
+            }
+        }
+    }
+
+    fn errors(results: &AnalyzerResults<Global>) -> Vec<&AnalyzerError> {
+        results
+            .diagnostics
+            .iter()
+            .filter_map(|d| match d {
+                AnalyzerDiagnostic::Error { error, .. } => Some(error),
+                AnalyzerDiagnostic::Warning { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_mjs() {
+        let results = analyze(
+            "import m1 from \"module1.d.mjs\"\nimport m2 from \"module2.d.mjs\"\n\nconst a = { m2: m2, f: true }\nconst b = [3, m1]\n\nexport default { a: a, \"b\": b }\n",
+        );
+        assert_eq!(results.diagnostics, []);
+        assert_eq!(results.data_type, DataType::Mjs);
+        let module = results.module;
+        let import: Vec<_> = module.import.iter().map(string).collect();
+        assert_eq!(import, ["\"module1.d.mjs\"", "\"module2.d.mjs\""]);
+        let local: Vec<_> = module.body.local.iter().map(show).collect();
+        assert_eq!(
+            local,
+            [
+                r#"["object",[["m2",["argRef",1]],["f",["value",true]]]]"#,
+                r#"["array",[["value",3],["argRef",0]]]"#,
+            ]
+        );
+        assert_eq!(
+            show(&module.body.result),
+            r#"["object",[["a",["localRef",0]],["b",["localRef",1]]]]"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_cjs() {
+        let results = analyze(
+            "const a = require(\"x.d.cjs\")\nmodule.exports = [a, require(\"x.d.cjs\"), require(\"y.d.cjs\"), null, \"s\"]",
+        );
+        assert_eq!(results.diagnostics, []);
+        assert_eq!(results.data_type, DataType::Cjs);
+        let module = results.module;
+        assert_eq!(module.import.len(), 2);
+        let local: Vec<_> = module.body.local.iter().map(show).collect();
+        assert_eq!(local, [r#"["argRef",0]"#]);
+        assert_eq!(
+            show(&module.body.result),
+            r#"["array",[["localRef",0],["argRef",0],["argRef",1],["value",null],["value","s"]]]"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_json() {
+        let results = analyze("{\"a\": [1, {}], \"b\": []}");
+        assert_eq!(results.diagnostics, []);
+        assert_eq!(results.data_type, DataType::Json);
+        assert_eq!(
+            show(&results.module.body.result),
+            r#"["object",[["a",["array",[["value",1],["object",[]]]]],["b",["array",[]]]]]"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_comments() {
+        let results = analyze("// a\n[1, /* b */ 2 /* c * d */]\n// e");
+        assert_eq!(results.diagnostics, []);
+        assert_eq!(results.comments, 4);
+        assert_eq!(analyze("[\"//\", \"/* */\"]").comments, 0);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_undefined() {
+        let results = analyze("[undefined, void 0, {a: undefined}]");
+        assert_eq!(results.diagnostics, []);
+        assert_eq!(results.data_type, DataType::Djs);
+        assert_eq!(
+            show(&results.module.body.result),
+            r#"["array",[["undefined"],["undefined"],["object",[["a",["undefined"]]]]]]"#
+        );
+
+        let results = analyze("export default void 1");
+        assert_eq!(errors(&results), [&AnalyzerError::UnexpectedToken]);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_shorthand() {
+        let results = analyze("const a = 1\nconst b = [a]\nexport default { a, c: 2, b }");
+        assert_eq!(results.diagnostics, []);
+        assert_eq!(
+            show(&results.module.body.result),
+            r#"["object",[["a",["localRef",0]],["c",["value",2]],["b",["localRef",1]]]]"#
+        );
+
+        let results = analyze("export default (x => ({ x }))");
+        assert_eq!(results.diagnostics, []);
+
+        let results = analyze("export default { b }");
+        assert_eq!(
+            errors(&results),
+            [&AnalyzerError::UnknownIdentifier("b".to_owned())]
+        );
+
+        let results = analyze(r#"export default { "a" }"#);
+        assert_eq!(errors(&results), [&AnalyzerError::UnexpectedToken]);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_recovery() {
+        let results =
+            analyze("const a = [1 2]\nconst b = }\nconst c = 3\nexport default [a, b, c, d]");
+        assert_eq!(
+            errors(&results),
+            [
+                &AnalyzerError::UnexpectedToken,
+                &AnalyzerError::UnexpectedToken,
+                &AnalyzerError::UnknownIdentifier("d".to_owned())
+            ]
+        );
+        let lines: Vec<_> = results
+            .diagnostics
+            .iter()
+            .map(|d| match d {
+                AnalyzerDiagnostic::Error { span, .. } => span.begin.line,
+                AnalyzerDiagnostic::Warning { .. } => panic!(),
+            })
+            .collect();
+        assert_eq!(lines, [0, 1, 3]);
+
+        // The rest of a line with an error is skipped.
+        let results = analyze("export default [1 2] const a = [");
+        assert_eq!(errors(&results), [&AnalyzerError::UnexpectedToken]);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_diagnostics() {
+        let results = analyze("const a = 1\nconst a = 2\nexport default a");
+        assert_eq!(
+            errors(&results),
+            [&AnalyzerError::DuplicateName("a".to_owned())]
+        );
+        let AnalyzerDiagnostic::Error { span, .. } = &results.diagnostics[0] else {
+            panic!()
+        };
+        assert_eq!((span.begin.line, span.begin.column), (1, 6));
+
+        let results = analyze("export default [b]");
+        assert_eq!(
+            errors(&results),
+            [&AnalyzerError::UnknownIdentifier("b".to_owned())]
+        );
+
+        let results = analyze("export default [1");
+        assert_eq!(errors(&results), [&AnalyzerError::UnexpectedEnd]);
+
+        let results = analyze("export default {a: 1, b: 2, a: 3}");
+        assert!(!results.diagnostics.iter().any(AnalyzerDiagnostic::is_error));
+        let AnalyzerDiagnostic::Warning { warning, span } = &results.diagnostics[0] else {
+            panic!()
+        };
+        assert_eq!(warning, &AnalyzerWarning::DuplicateKey("a".to_owned()));
+        assert_eq!(span.begin.column, 28);
+
+        let results = analyze("const a = 1 const b = 2");
+        assert_eq!(errors(&results), [&AnalyzerError::NewLineExpected]);
+
+        let results = analyze("module.exports = \"a\\x\"");
+        assert_eq!(
+            errors(&results),
+            [&AnalyzerError::InvalidToken(ErrorType::UnexpectedCharacter)]
+        );
+
+        let results = analyze("import a from \"a.d.mjs\"\nconst u = 1\nexport default 2");
+        assert!(!results.diagnostics.iter().any(AnalyzerDiagnostic::is_error));
+        let warnings: Vec<_> = results
+            .diagnostics
+            .iter()
+            .map(|d| match d {
+                AnalyzerDiagnostic::Warning { warning, span } => (warning, span.begin.line),
+                AnalyzerDiagnostic::Error { .. } => panic!(),
+            })
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (&AnalyzerWarning::UnusedImport("a".to_owned()), 0),
+                (&AnalyzerWarning::UnusedConst("u".to_owned()), 1),
+            ]
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_functions() {
+        let results = analyze(
+            "import m from \"m.d.mjs\"\nconst pair = (a, b,) => [a, b]\nconst k = x => y => [x, y, m]\nconst id = (x) => x\nexport default { p: pair(1, id(2)), k: k(3)(4), e: (() => null)() }\n",
+        );
+        assert_eq!(results.diagnostics, []);
+        assert_eq!(results.data_type, DataType::Mjs);
+        let module = results.module;
+        let local: Vec<_> = module.body.local.iter().map(show).collect();
+        assert_eq!(
+            local,
+            [
+                r#"["function",2,[],["array",[["argRef",0],["argRef",1]]]]"#,
+                r#"["function",1,[["argRef",0]],["function",1,[["argRef",0],["captureRef",0]],["array",[["captureRef",0],["argRef",0],["captureRef",1]]]]]"#,
+                r#"["function",1,[],["argRef",0]]"#,
+            ]
+        );
+        assert_eq!(
+            show(&module.body.result),
+            r#"["object",[["p",["call",["localRef",0],[["value",1],["call",["localRef",2],[["value",2]]]]]],["k",["call",["call",["localRef",1],[["value",3]]],[["value",4]]]],["e",["call",["function",0,[],["value",null]],[]]]]]"#
+        );
+
+        let results = analyze("const f = a =>\n  a\nmodule.exports = f");
+        assert_eq!(results.diagnostics, []);
+        assert_eq!(results.data_type, DataType::Cjs);
+        assert_eq!(
+            show(&results.module.body.local[0]),
+            r#"["function",1,[],["argRef",0]]"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_function_diagnostics() {
+        let results = analyze("const f = (a, a) => a");
+        assert_eq!(
+            errors(&results),
+            [&AnalyzerError::DuplicateName("a".to_owned())]
+        );
+
+        let results = analyze("const f = (1, 2) => 1");
+        assert_eq!(errors(&results), [&AnalyzerError::UnexpectedToken]);
+
+        let results = analyze("const f = a => b");
+        assert_eq!(
+            errors(&results),
+            [&AnalyzerError::UnknownIdentifier("b".to_owned())]
+        );
+
+        let results = analyze("const f = () => f()");
+        assert_eq!(
+            errors(&results),
+            [&AnalyzerError::UnknownIdentifier("f".to_owned())]
+        );
+
+        let results = analyze("const f = () => 1\nexport default f(");
+        assert_eq!(errors(&results), [&AnalyzerError::UnexpectedEnd]);
+
+        let results = analyze("export default ()");
+        assert_eq!(errors(&results), [&AnalyzerError::UnexpectedEnd]);
+    }
 }
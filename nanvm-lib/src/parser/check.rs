@@ -0,0 +1,533 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use io_trait::Io;
+
+use super::{
+    analyzer::{AnalyzerDiagnostic, AnalyzerParameters, AnalyzerState},
+    parser::to_parse_error,
+    path::normalize,
+    policy::{LoadPolicy, LoadStats},
+    resolver::{ModuleResolver, NodeResolver},
+    shared::{ImportLocation, ParseError, ParseErrorKind},
+};
+use crate::{
+    ast::Module,
+    js::any::Any,
+    mem::manager::{Dealloc, Manager},
+    tokenizer::span::Span,
+};
+
+/// An analyzed module with the resolved paths of its imports.
+struct CheckedModule<D: Dealloc> {
+    source: String,
+    imports: Vec<(String, Span)>,
+    /// The module to evaluate, if it has no errors and all its imports are resolved.
+    module: Option<Module<D>>,
+}
+
+/// A module to check, imported at `depth` by the module `importer` at `span`.
+struct Import {
+    path: String,
+    depth: usize,
+    importer: String,
+    span: Span,
+}
+
+/// Checks many modules and the modules they import. Unlike `parse`, it doesn't stop at the first
+/// error: all errors of every module are collected, with unresolved imports and circular
+/// dependencies. Modules without errors are evaluated at the `end`, so errors like calling a
+/// value that isn't a function are reported too.
+pub struct Checker<'a, M: Manager, I: Io, R: ModuleResolver = NodeResolver> {
+    manager: M,
+    io: &'a I,
+    resolver: R,
+    modules: BTreeMap<String, CheckedModule<M::Dealloc>>,
+    errors: Vec<ParseError>,
+    policy: LoadPolicy,
+    stats: LoadStats,
+}
+
+impl<'a, M: Manager + 'static, I: Io> Checker<'a, M, I> {
+    pub fn new(manager: M, io: &'a I) -> Self {
+        Checker {
+            manager,
+            io,
+            resolver: NodeResolver,
+            modules: BTreeMap::new(),
+            errors: Vec::new(),
+            policy: LoadPolicy::default(),
+            stats: LoadStats::default(),
+        }
+    }
+}
+
+impl<'a, M: Manager + 'static, I: Io, R: ModuleResolver> Checker<'a, M, I, R> {
+    /// Uses `resolver` to find the modules of `import` and `require` paths.
+    pub fn with_resolver<T: ModuleResolver>(self, resolver: T) -> Checker<'a, M, I, T> {
+        Checker {
+            manager: self.manager,
+            io: self.io,
+            resolver,
+            modules: self.modules,
+            errors: self.errors,
+            policy: self.policy,
+            stats: self.stats,
+        }
+    }
+
+    /// Limits the modules that can be read, the same way as `Context::with_policy`. A module that
+    /// the policy doesn't allow is reported as an error.
+    pub fn with_policy(self, policy: LoadPolicy) -> Self {
+        Checker { policy, ..self }
+    }
+
+    /// Checks the file `path` and its imports.
+    pub fn check_file(&mut self, path: &str) {
+        let path = normalize(path);
+        if self.modules.contains_key(&path) {
+            return;
+        }
+        match self.policy.read(self.io, &mut self.stats, &path) {
+            Ok(source) => self.check_module(path, source),
+            Err(kind) => self
+                .errors
+                .push(ParseError::from(kind).with_source(&path, "")),
+        }
+    }
+
+    /// Checks `source` as the content of the file `path`, e.g. a module read from stdin, and its
+    /// imports.
+    pub fn check_source(&mut self, path: &str, source: String) {
+        match self.policy.check_read(&mut self.stats, source.len()) {
+            Ok(()) => self.check_module(path.to_owned(), source),
+            Err(kind) => self
+                .errors
+                .push(ParseError::from(kind).with_source(path, "")),
+        }
+    }
+
+    /// Checks the module `path` with the content `source` and its imports. The imports are walked
+    /// without recursion, so long import chains don't overflow the stack.
+    fn check_module(&mut self, path: String, source: String) {
+        let mut pending = Vec::new();
+        self.add_module(path, source, 0, &mut pending);
+        while let Some(Import {
+            path,
+            depth,
+            importer,
+            span,
+        }) = pending.pop()
+        {
+            if self.modules.contains_key(&path) {
+                continue;
+            }
+            let source = self
+                .policy
+                .check_depth(depth)
+                .and_then(|_| self.policy.read(self.io, &mut self.stats, &path));
+            match source {
+                Ok(source) => self.add_module(path, source, depth, &mut pending),
+                Err(kind) => {
+                    let error = ParseError {
+                        span: Some(span),
+                        ..ParseError::from(kind)
+                    };
+                    let source = &self.modules[&importer].source;
+                    self.errors.push(error.with_source(&importer, source));
+                }
+            }
+        }
+    }
+
+    /// Analyzes the module `path` with the content `source`, imported at `depth`, and adds its
+    /// resolved imports to `pending`.
+    fn add_module(
+        &mut self,
+        path: String,
+        source: String,
+        depth: usize,
+        pending: &mut Vec<Import>,
+    ) {
+        let mut state = AnalyzerState::new(AnalyzerParameters::default());
+        for c in source.chars() {
+            state.push_mut(self.manager, c);
+        }
+        let results = state.end(self.manager);
+        let valid = !results.diagnostics.iter().any(AnalyzerDiagnostic::is_error);
+        self.errors.extend(
+            results
+                .diagnostics
+                .into_iter()
+                .filter_map(to_parse_error)
+                .map(|e| e.with_source(&path, &source)),
+        );
+        let mut imports = Vec::new();
+        for (import, span) in results.module.import.iter().zip(results.import_spans) {
+            let specifier = String::from_utf16_lossy(import.items());
+            match self.resolver.resolve(
+                self.manager,
+                self.io,
+                &self.policy,
+                &mut self.stats,
+                &path,
+                &specifier,
+            ) {
+                Some(import) => imports.push((import, span)),
+                None => {
+                    let error = ParseError {
+                        span: Some(span),
+                        ..ParseError::from(ParseErrorKind::CannotResolveModule)
+                    };
+                    self.errors.push(error.with_source(&path, &source));
+                }
+            }
+        }
+        // In reverse, so the imports are checked in the source order.
+        pending.extend(imports.iter().rev().map(|(import, span)| Import {
+            path: import.clone(),
+            depth: depth + 1,
+            importer: path.clone(),
+            span: *span,
+        }));
+        let module = results.module;
+        let resolved = imports.len() == module.import.len();
+        self.modules.insert(
+            path,
+            CheckedModule {
+                source,
+                imports,
+                module: (valid && resolved).then_some(module),
+            },
+        );
+    }
+
+    /// Reports circular dependencies at the imports that close them, evaluates the modules without
+    /// errors, and returns all errors ordered by file and position.
+    pub fn end(mut self) -> CheckResults {
+        let mut cycles = Vec::new();
+        let mut done = BTreeSet::new();
+        for path in self.modules.keys() {
+            self.find_cycles(path, &mut done, &mut cycles);
+        }
+        self.errors.extend(cycles);
+        let mut evaluation_errors = Vec::new();
+        let mut values = BTreeMap::new();
+        for path in self.modules.keys() {
+            self.evaluate(path, &mut values, &mut evaluation_errors);
+        }
+        self.errors.extend(evaluation_errors);
+        self.errors
+            .sort_by_key(|e| (e.path.clone(), e.span.map(|span| span.begin)));
+        CheckResults {
+            modules: self.modules.len(),
+            errors: self.errors,
+        }
+    }
+
+    /// Evaluates the module `root` after its imports, walking them without recursion. A module
+    /// with errors, in a cycle or with an import that has no value has no value, and its errors are
+    /// already reported.
+    fn evaluate<'p>(
+        &'p self,
+        root: &'p str,
+        values: &mut BTreeMap<&'p str, Option<Any<M::Dealloc>>>,
+        errors: &mut Vec<ParseError>,
+    ) {
+        // Modules to walk, `true` for a module whose imports are already walked.
+        let mut stack = vec![(root, false)];
+        while let Some((path, imported)) = stack.pop() {
+            let Some(checked) = self.modules.get(path) else {
+                continue;
+            };
+            let Some(module) = &checked.module else {
+                continue;
+            };
+            if !imported {
+                if values.contains_key(path) {
+                    continue;
+                }
+                // Stops at a circular dependency, which `find_cycles` reports.
+                values.insert(path, None);
+                stack.push((path, true));
+                stack.extend(
+                    checked
+                        .imports
+                        .iter()
+                        .rev()
+                        .map(|(i, _)| (i.as_str(), false)),
+                );
+                continue;
+            }
+            let args: Option<Vec<_>> = checked
+                .imports
+                .iter()
+                .map(|(import, _)| values.get(import.as_str()).cloned().flatten())
+                .collect();
+            let Some(args) = args else {
+                continue;
+            };
+            match module.body.evaluate(self.manager, &args) {
+                Ok(any) => {
+                    values.insert(path, Some(any));
+                }
+                Err(e) => {
+                    let error = ParseError {
+                        span: e.span(),
+                        ..ParseError::from(ParseErrorKind::CannotEvaluate(e))
+                    };
+                    errors.push(error.with_source(path, &checked.source));
+                }
+            }
+        }
+    }
+
+    /// Walks the imports of `root` without recursion and reports the imports that close cycles.
+    fn find_cycles<'p>(
+        &'p self,
+        root: &'p str,
+        done: &mut BTreeSet<&'p str>,
+        cycles: &mut Vec<ParseError>,
+    ) {
+        if done.contains(root) || !self.modules.contains_key(root) {
+            return;
+        }
+        // The modules being walked, each with the number of its walked imports and the import of
+        // the next module.
+        let mut stack: Vec<(&str, usize, Option<ImportLocation>)> = vec![(root, 0, None)];
+        while let Some(&(path, next, _)) = stack.last() {
+            let module = &self.modules[path];
+            let Some((import, span)) = module.imports.get(next) else {
+                stack.pop();
+                done.insert(path);
+                continue;
+            };
+            stack.last_mut().unwrap().1 += 1;
+            let location = ImportLocation::new(path, *span, &module.source);
+            match stack.iter().position(|(p, ..)| p == import) {
+                Some(index) => {
+                    let import_cycle = stack[index..stack.len() - 1]
+                        .iter()
+                        .filter_map(|(.., import)| import.clone())
+                        .collect();
+                    cycles.push(ParseError {
+                        span: Some(*span),
+                        source_line: location.source_line,
+                        path: path.to_owned(),
+                        import_cycle,
+                        ..ParseError::from(ParseErrorKind::CircularDependency)
+                    });
+                }
+                None if done.contains(import.as_str()) || !self.modules.contains_key(import) => {}
+                None => {
+                    stack.last_mut().unwrap().2 = Some(location);
+                    stack.push((import, 0, None));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CheckResults {
+    /// The number of checked modules, including the imported ones.
+    pub modules: usize,
+    pub errors: Vec<ParseError>,
+}
+
+#[cfg(test)]
+mod test {
+    use io_test::VirtualIo;
+    use io_trait::Io;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        ast::eval::EvaluationError,
+        mem::global::GLOBAL,
+        parser::{
+            policy::LoadPolicy,
+            shared::{ParseError, ParseErrorKind},
+        },
+    };
+
+    use super::Checker;
+
+    /// The kind, path and one-based line of an error.
+    fn show(e: &ParseError) -> (&ParseErrorKind, &str, Option<usize>) {
+        (&e.kind, e.path.as_str(), e.span.map(|s| s.begin.line + 1))
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_check() {
+        let io = VirtualIo::new(&[]);
+        io.create_dir("data").unwrap();
+        let files: &[(&str, &str)] = &[
+            (
+                "data/main.d.mjs",
+                "import a from \"./a.d.mjs\"\nimport m from \"./missing.json\"\nexport default [a, m]",
+            ),
+            ("data/a.d.mjs", "import b from \"b.d.mjs\"\nexport default [b]"),
+            ("data/b.d.mjs", "import a from \"a.d.mjs\"\nexport default [a 1]"),
+            ("data/c.json", "{\"a\": }\n"),
+            ("data/d.d.cjs", "const x = [1 2]\nconst y = }\nmodule.exports = [x, y]"),
+        ];
+        for (path, source) in files {
+            io.write(path, source.as_bytes()).unwrap();
+        }
+        let mut checker = Checker::new(GLOBAL, &io);
+        checker.check_file("./data/main.d.mjs");
+        checker.check_file("data/c.json");
+        checker.check_file("data/d.d.cjs");
+        checker.check_file("data/a.d.mjs");
+        checker.check_file("data/none.json");
+        let results = checker.end();
+        assert_eq!(results.modules, 5);
+        let errors: Vec<_> = results.errors.iter().map(show).collect();
+        assert_eq!(
+            errors,
+            [
+                (&ParseErrorKind::CircularDependency, "data/b.d.mjs", Some(1)),
+                (&ParseErrorKind::UnexpectedToken, "data/b.d.mjs", Some(2)),
+                (&ParseErrorKind::UnexpectedToken, "data/c.json", Some(1)),
+                (&ParseErrorKind::UnexpectedToken, "data/d.d.cjs", Some(1)),
+                (&ParseErrorKind::UnexpectedToken, "data/d.d.cjs", Some(2)),
+                (&ParseErrorKind::CannotReadFile, "data/main.d.mjs", Some(2)),
+                (&ParseErrorKind::CannotReadFile, "data/none.json", None),
+            ]
+        );
+        assert_eq!(results.errors[0].import_cycle[0].path, "data/a.d.mjs");
+        assert_eq!(
+            results.errors[5].to_string(),
+            "CannotReadFile\n --> data/main.d.mjs:2:15\n  |\n2 | import m from \"./missing.json\"\n  |               ^^^^^^^^^^^^^^^^"
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_evaluate() {
+        let io = VirtualIo::new(&[]);
+        let files: &[(&str, &str)] = &[
+            (
+                "main.d.mjs",
+                "import f from \"./f.d.mjs\"\nexport default f(1)",
+            ),
+            ("f.d.mjs", "export default x => [x]"),
+            ("bad.d.mjs", "const n = 1\nexport default n(2)"),
+            (
+                "user.d.mjs",
+                "import b from \"./bad.d.mjs\"\nexport default [b]",
+            ),
+        ];
+        for (path, source) in files {
+            io.write(path, source.as_bytes()).unwrap();
+        }
+        let mut checker = Checker::new(GLOBAL, &io);
+        checker.check_file("main.d.mjs");
+        checker.check_file("user.d.mjs");
+        let results = checker.end();
+        assert_eq!(results.modules, 4);
+        // A module that imports a module with an error isn't reported again.
+        let errors: Vec<_> = results.errors.iter().map(show).collect();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors[0],
+            (
+                ParseErrorKind::CannotEvaluate(EvaluationError::NotAFunction(_)),
+                "bad.d.mjs",
+                Some(2)
+            )
+        ));
+        assert_eq!(
+            results.errors[0].to_string(),
+            "CannotEvaluate\n --> bad.d.mjs:2:17\n  |\n2 | export default n(2)\n  |                 ^^^"
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_depth() {
+        let io = VirtualIo::new(&[]);
+        let n = 1000;
+        let path = |i: usize| format!("m{i}.d.mjs");
+        for i in 0..n {
+            let source = match i + 1 < n {
+                true => format!("import a from \"./{}\"\nexport default [a]", path(i + 1)),
+                false => "export default 0".to_owned(),
+            };
+            io.write(&path(i), source.as_bytes()).unwrap();
+        }
+        // The imports are checked, and the chain is evaluated, without recursion.
+        let mut checker = Checker::new(GLOBAL, &io);
+        checker.check_file(&path(0));
+        let results = checker.end();
+        assert_eq!(results.modules, n);
+        assert!(results.errors.is_empty());
+
+        let policy = LoadPolicy {
+            max_depth: Some(10),
+            ..LoadPolicy::default()
+        };
+        let mut checker = Checker::new(GLOBAL, &io).with_policy(policy);
+        checker.check_file(&path(0));
+        let results = checker.end();
+        let errors: Vec<_> = results.errors.iter().map(show).collect();
+        let last = path(10);
+        assert_eq!(
+            errors,
+            [(&ParseErrorKind::ImportTooDeep, last.as_str(), Some(1))]
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_policy() {
+        let io = VirtualIo::new(&[]);
+        io.create_dir("data").unwrap();
+        let files: &[(&str, &str)] = &[
+            ("secret.json", "1"),
+            (
+                "data/main.d.mjs",
+                "import s from \"../secret.json\"\nimport t from \"./a.txt\"\nimport d from \"./d.d.mjs\"\nexport default [s, t, d]",
+            ),
+            ("data/a.txt", "1"),
+            ("data/d.d.mjs", "import e from \"./e.json\"\nexport default e"),
+            ("data/e.json", "1"),
+        ];
+        for (path, source) in files {
+            io.write(path, source.as_bytes()).unwrap();
+        }
+        let policy = LoadPolicy {
+            max_depth: Some(1),
+            ..LoadPolicy::sandbox("data")
+        };
+        let mut checker = Checker::new(GLOBAL, &io).with_policy(policy);
+        checker.check_file("data/main.d.mjs");
+        checker.check_file("secret.json");
+        let results = checker.end();
+        assert_eq!(results.modules, 2);
+        let errors: Vec<_> = results.errors.iter().map(show).collect();
+        assert_eq!(
+            errors,
+            [
+                (&ParseErrorKind::ImportTooDeep, "data/d.d.mjs", Some(1)),
+                (&ParseErrorKind::PathOutsideRoot, "data/main.d.mjs", Some(1)),
+                (
+                    &ParseErrorKind::ExtensionNotAllowed,
+                    "data/main.d.mjs",
+                    Some(2)
+                ),
+                (&ParseErrorKind::PathOutsideRoot, "secret.json", None),
+            ]
+        );
+
+        let policy = LoadPolicy {
+            max_bytes: Some(2),
+            ..LoadPolicy::default()
+        };
+        let mut checker = Checker::new(GLOBAL, &io).with_policy(policy);
+        checker.check_source("<stdin>", "[1]".to_owned());
+        let results = checker.end();
+        let errors: Vec<_> = results.errors.iter().map(show).collect();
+        assert_eq!(errors, [(&ParseErrorKind::TooManyBytes, "<stdin>", None)]);
+    }
+}
@@ -1,11 +1,12 @@
 // Analyzer is a temporary name of a refactored parser that will be merged with the main parser.
 // It uses src/ast APIs.
 #![allow(clippy::module_inception)]
+// `ParseError` carries the location of the error; it is returned once per parse, so size is not a concern.
+#![allow(clippy::result_large_err)]
 pub mod analyzer;
-pub mod any_state;
-pub mod const_state;
-pub mod json_state;
+pub mod check;
 pub mod parser;
 pub mod path;
-pub mod root_state;
+pub mod policy;
+pub mod resolver;
 pub mod shared;
@@ -1,23 +1,29 @@
+use std::mem::take;
+
 use super::{
-    any_state::{AnyResult, AnyState},
-    const_state::ConstState,
-    json_state::JsonState,
-    path::{concat, split},
-    root_state::{RootState, RootStatus},
-    shared::{JsonElement, ModuleCache, ParseError, ParseResult, ParsingStatus},
+    analyzer::{
+        AnalyzerDiagnostic, AnalyzerError, AnalyzerParameters, AnalyzerResults, AnalyzerState,
+    },
+    policy::{LoadPolicy, LoadStats},
+    resolver::{ModuleResolver, NodeResolver},
+    shared::{ImportLocation, Loading, ModuleCache, ParseError, ParseErrorKind, ParseResult},
 };
 use crate::{
     common::default::default,
+    js::any::Any,
     mem::manager::Manager,
-    tokenizer::{tokenize, JsonToken},
+    tokenizer::{span::SpannedToken, JsonToken},
 };
 use io_trait::Io;
 
-pub struct Context<'a, M: Manager, I: Io> {
+pub struct Context<'a, M: Manager, I: Io, R: ModuleResolver = NodeResolver> {
     manager: M,
     io: &'a I,
     path: String,
     module_cache: &'a mut ModuleCache<M::Dealloc>,
+    resolver: R,
+    policy: LoadPolicy,
+    stats: LoadStats,
 }
 
 impl<'a, M: Manager, I: Io> Context<'a, M, I> {
@@ -32,223 +38,282 @@ impl<'a, M: Manager, I: Io> Context<'a, M, I> {
             io,
             path,
             module_cache,
+            resolver: NodeResolver,
+            policy: LoadPolicy::default(),
+            stats: LoadStats::default(),
         }
     }
 }
 
-fn const_state_parse<M: Manager + 'static, I: Io>(
-    const_state: ConstState<M>,
-    context: &mut Context<M, I>,
-    token: JsonToken<M::Dealloc>,
-) -> JsonState<M> {
-    match token {
-        JsonToken::Semicolon => todo!(),
-        _ => {
-            let result = any_state_parse(const_state.state, context, token);
-            match result {
-                AnyResult::Continue(state) => JsonState::ParseConst(ConstState {
-                    key: const_state.key,
-                    state,
-                }),
-                AnyResult::Success(mut success) => {
-                    success.state.consts.insert(const_state.key, success.value);
-                    JsonState::ParseRoot(RootState {
-                        status: RootStatus::Initial,
-                        state: success.state,
-                        new_line: false,
-                    })
-                }
-                AnyResult::Error(error) => JsonState::Error(error),
-            }
+impl<'a, M: Manager, I: Io, R: ModuleResolver> Context<'a, M, I, R> {
+    /// Uses `resolver` to find the modules of `import` and `require` paths.
+    pub fn with_resolver<T: ModuleResolver>(self, resolver: T) -> Context<'a, M, I, T> {
+        Context {
+            manager: self.manager,
+            io: self.io,
+            path: self.path,
+            module_cache: self.module_cache,
+            resolver,
+            policy: self.policy,
+            stats: self.stats,
         }
     }
-}
 
-fn any_state_parse_for_module<M: Manager + 'static, I: Io>(
-    any_state: AnyState<M>,
-    context: &mut Context<M, I>,
-    token: JsonToken<M::Dealloc>,
-) -> JsonState<M> {
-    let result = any_state_parse(any_state, context, token);
-    match result {
-        AnyResult::Continue(state) => JsonState::ParseModule(state),
-        AnyResult::Success(success) => JsonState::Result(ParseResult {
-            data_type: success.state.data_type,
-            any: success.value,
-        }),
-        AnyResult::Error(error) => JsonState::Error(error),
+    /// Limits the modules that can be loaded, e.g. to parse untrusted modules.
+    pub fn with_policy(self, policy: LoadPolicy) -> Self {
+        Context { policy, ..self }
+    }
+
+    /// The modules and bytes read so far.
+    pub fn stats(&self) -> LoadStats {
+        self.stats
     }
 }
 
-fn any_state_parse_import_value<M: Manager + 'static, I: Io>(
-    any_state: AnyState<M>,
-    context: &mut Context<M, I>,
-    token: JsonToken<M::Dealloc>,
-) -> AnyResult<M> {
-    match token {
-        JsonToken::String(s) => {
-            let current_path = concat(split(&context.path).0, s.as_str());
-            if let Some(any) = context.module_cache.complete.get(&current_path) {
-                return AnyResult::Continue(AnyState {
-                    status: ParsingStatus::ImportEnd,
-                    current: JsonElement::Any(any.clone()),
-                    ..any_state
-                });
-            }
-            if context.module_cache.progress.contains(&current_path) {
-                return AnyResult::Error(ParseError::CircularDependency);
-            }
-            context.module_cache.progress.insert(current_path.clone());
-            let read_result = context.io.read_to_string(current_path.as_str());
-            match read_result {
-                Ok(s) => {
-                    let tokens = tokenize(context.manager, s);
-                    let res = parse_with_tokens(context, tokens.into_iter());
-                    match res {
-                        Ok(r) => {
-                            context.module_cache.progress.remove(&current_path);
-                            context
-                                .module_cache
-                                .complete
-                                .insert(current_path, r.any.clone());
-                            AnyResult::Continue(AnyState {
-                                status: ParsingStatus::ImportEnd,
-                                current: JsonElement::Any(r.any),
-                                ..any_state
-                            })
-                        }
-                        Err(e) => AnyResult::Error(e),
-                    }
-                }
-                Err(_) => AnyResult::<M>::Error(ParseError::CannotReadFile),
+impl From<AnalyzerError> for ParseErrorKind {
+    fn from(error: AnalyzerError) -> Self {
+        match error {
+            AnalyzerError::InvalidToken(_)
+            | AnalyzerError::UnexpectedToken
+            | AnalyzerError::UnknownIdentifier(_) => ParseErrorKind::UnexpectedToken,
+            AnalyzerError::UnexpectedEnd => ParseErrorKind::UnexpectedEnd,
+            AnalyzerError::NewLineExpected => ParseErrorKind::NewLineExpected,
+            AnalyzerError::WrongExportStatement => ParseErrorKind::WrongExportStatement,
+            AnalyzerError::WrongConstStatement | AnalyzerError::DuplicateName(_) => {
+                ParseErrorKind::WrongConstStatement
             }
+            AnalyzerError::WrongImportStatement => ParseErrorKind::WrongImportStatement,
+            AnalyzerError::WrongRequireStatement => ParseErrorKind::WrongRequireStatement,
         }
-        _ => AnyResult::Error(ParseError::WrongRequireStatement),
     }
 }
 
-fn any_state_parse<M: Manager + 'static, I: Io>(
-    any_state: AnyState<M>,
-    context: &mut Context<M, I>,
-    token: JsonToken<M::Dealloc>,
-) -> AnyResult<M> {
-    match any_state.status {
-        ParsingStatus::Initial | ParsingStatus::ObjectColon => {
-            any_state.parse_value(context.manager, token)
-        }
-        ParsingStatus::ArrayBegin => any_state.parse_array_begin(context.manager, token),
-        ParsingStatus::ArrayValue => any_state.parse_array_value(context.manager, token),
-        ParsingStatus::ArrayComma => any_state.parse_array_comma(context.manager, token),
-        ParsingStatus::ObjectBegin => any_state.parse_object_begin(context.manager, token),
-        ParsingStatus::ObjectKey => any_state.parse_object_key(token),
-        ParsingStatus::ObjectValue => any_state.parse_object_next(context.manager, token),
-        ParsingStatus::ObjectComma => any_state.parse_object_comma(context.manager, token),
-        ParsingStatus::ImportBegin => any_state.parse_import_begin(token),
-        ParsingStatus::ImportValue => any_state_parse_import_value(any_state, context, token),
-        ParsingStatus::ImportEnd => any_state.parse_import_end(token),
+pub fn parse<M: Manager + 'static, I: Io, R: ModuleResolver>(
+    context: &mut Context<M, I, R>,
+) -> Result<ParseResult<M::Dealloc>, ParseError> {
+    let path = context.path.clone();
+    match read_module(context, &path) {
+        Ok(s) => parse_main(context, &s),
+        Err(kind) => Err(ParseError::from(kind).with_source(&path, "")),
     }
 }
 
-fn root_state_parse<M: Manager + 'static, I: Io>(
-    root_state: RootState<M>,
-    context: &mut Context<M, I>,
-    token: JsonToken<M::Dealloc>,
-) -> JsonState<M> {
-    let (json_state, import) = root_state.parse(context.manager, token);
-    match import {
-        None => json_state,
-        Some((id, module)) => match json_state {
-            JsonState::ParseRoot(mut root_state) => {
-                let current_path = concat(split(&context.path).0, module.as_str());
-                if let Some(any) = context.module_cache.complete.get(&current_path) {
-                    root_state.state.consts.insert(id, any.clone());
-                    return JsonState::ParseRoot(RootState {
-                        status: RootStatus::Initial,
-                        state: root_state.state,
-                        new_line: false,
-                    });
-                }
-                if context.module_cache.progress.contains(&current_path) {
-                    return JsonState::Error(ParseError::CircularDependency);
-                }
-                context.module_cache.progress.insert(current_path.clone());
-                let read_result = context.io.read_to_string(current_path.as_str());
-                match read_result {
-                    Ok(s) => {
-                        let tokens = tokenize(context.manager, s);
-                        let res = parse_with_tokens(context, tokens.into_iter());
-                        match res {
-                            Ok(r) => {
-                                context.module_cache.progress.remove(&current_path);
-                                context
-                                    .module_cache
-                                    .complete
-                                    .insert(current_path, r.any.clone());
-                                root_state.state.consts.insert(id, r.any);
-                                JsonState::ParseRoot(RootState {
-                                    status: RootStatus::Initial,
-                                    state: root_state.state,
-                                    new_line: false,
-                                })
-                            }
-                            Err(e) => JsonState::Error(e),
-                        }
-                    }
-                    Err(_) => JsonState::Error(ParseError::CannotReadFile),
-                }
-            }
-            _ => panic!("JsonState::ParseRoot expected when root_state.parse returns import"),
-        },
-    }
+/// Parses `source` as the content of the file `context.path`, e.g. a module read from stdin.
+/// Imports are loaded relative to the directory of the path.
+pub fn parse_source<M: Manager + 'static, I: Io, R: ModuleResolver>(
+    context: &mut Context<M, I, R>,
+    source: &str,
+) -> Result<ParseResult<M::Dealloc>, ParseError> {
+    let path = context.path.clone();
+    context
+        .policy
+        .check_read(&mut context.stats, source.len())
+        .map_err(|kind| ParseError::from(kind).with_source(&path, ""))?;
+    parse_main(context, source)
 }
 
-fn json_state_push<M: Manager + 'static, I: Io>(
-    json_state: JsonState<M>,
-    context: &mut Context<M, I>,
-    token: JsonToken<M::Dealloc>,
-) -> JsonState<M> {
-    if let JsonToken::NewLine = token {
-        return match json_state {
-            JsonState::ParseRoot(state) => root_state_parse(state, context, token),
-            _ => json_state,
-        };
+/// Parses the main module `context.path` with the content `source`.
+fn parse_main<M: Manager + 'static, I: Io, R: ModuleResolver>(
+    context: &mut Context<M, I, R>,
+    source: &str,
+) -> Result<ParseResult<M::Dealloc>, ParseError> {
+    let path = context.path.clone();
+    context.module_cache.progress.push(Loading {
+        path: path.clone(),
+        import: None,
+    });
+    let result = load(context, &path, source);
+    // The module is popped on errors too, so a reused cache doesn't see a circular dependency.
+    context.module_cache.progress.pop();
+    let result = result?;
+    context
+        .module_cache
+        .complete
+        .insert(path, result.any.clone());
+    Ok(result)
+}
+
+/// Analyzes `source` without loading its imports, e.g. to write the module back with
+/// `to_module`. Fails with the first error of the analysis.
+pub fn analyze<M: Manager + 'static>(
+    manager: M,
+    path: &str,
+    source: &str,
+) -> Result<AnalyzerResults<M::Dealloc>, ParseError> {
+    let mut state = AnalyzerState::new(AnalyzerParameters::default());
+    for c in source.chars() {
+        state.push_mut(manager, c);
     }
-    match json_state {
-        JsonState::ParseRoot(state) => root_state_parse(state, context, token),
-        JsonState::Result(_) => JsonState::Error(ParseError::UnexpectedToken),
-        JsonState::ParseModule(state) => any_state_parse_for_module(state, context, token),
-        JsonState::ParseConst(state) => const_state_parse(state, context, token),
-        _ => json_state,
+    let mut results = state.end(manager);
+    match take_error(&mut results.diagnostics) {
+        Some(error) => Err(error.with_source(path, source)),
+        None => Ok(results),
     }
 }
 
-pub fn parse<M: Manager + 'static, I: Io>(
-    context: &mut Context<M, I>,
+pub fn parse_with_tokens<M: Manager + 'static, I: Io, R: ModuleResolver>(
+    context: &mut Context<M, I, R>,
+    iter: impl Iterator<Item = JsonToken<M::Dealloc>>,
 ) -> Result<ParseResult<M::Dealloc>, ParseError> {
-    context.module_cache.progress.insert(context.path.clone());
-    let read_result = context.io.read_to_string(context.path.as_str());
-    match read_result {
-        Ok(s) => {
-            let tokens = tokenize(context.manager, s);
-            parse_with_tokens(context, tokens.into_iter())
-        }
-        Err(_) => Err(ParseError::CannotReadFile),
+    let mut state = AnalyzerState::new(AnalyzerParameters::default())
+        .with_interner(take(&mut context.module_cache.interner));
+    for token in iter {
+        state.push_token_mut(
+            context.manager,
+            SpannedToken {
+                token,
+                span: default(),
+            },
+        );
     }
+    let mut results = state.end(context.manager);
+    context.module_cache.interner = take(&mut results.interner);
+    let path = context.path.clone();
+    // Raw tokens have no location, so only errors from imported files keep theirs.
+    link(context, &path, "", results).map_err(|e| match e.path.is_empty() {
+        true => ParseError { span: None, ..e },
+        false => e,
+    })
 }
 
-pub fn parse_with_tokens<M: Manager + 'static, I: Io>(
-    context: &mut Context<M, I>,
-    iter: impl Iterator<Item = JsonToken<M::Dealloc>>,
+/// Analyzes the content of the file `path` and links the resulting module.
+fn load<M: Manager + 'static, I: Io, R: ModuleResolver>(
+    context: &mut Context<M, I, R>,
+    path: &str,
+    source: &str,
 ) -> Result<ParseResult<M::Dealloc>, ParseError> {
-    let mut state = JsonState::ParseRoot(RootState {
-        status: RootStatus::Initial,
-        state: default(),
-        new_line: true,
-    });
-    for token in iter {
-        state = json_state_push(state, context, token);
+    let mut state = AnalyzerState::new(AnalyzerParameters::default())
+        .with_interner(take(&mut context.module_cache.interner));
+    for c in source.chars() {
+        state.push_mut(context.manager, c);
+    }
+    let mut results = state.end(context.manager);
+    context.module_cache.interner = take(&mut results.interner);
+    link(context, path, source, results).map_err(|e| e.with_source(path, source))
+}
+
+/// Removes the first error from `diagnostics` and converts it to a `ParseError` without a file.
+fn take_error(diagnostics: &mut Vec<AnalyzerDiagnostic>) -> Option<ParseError> {
+    let index = diagnostics.iter().position(AnalyzerDiagnostic::is_error)?;
+    to_parse_error(diagnostics.remove(index))
+}
+
+/// Converts an analyzer error to a `ParseError` without a file. Warnings are `None`.
+pub(super) fn to_parse_error(diagnostic: AnalyzerDiagnostic) -> Option<ParseError> {
+    match diagnostic {
+        AnalyzerDiagnostic::Error {
+            error,
+            span,
+            token,
+            expected,
+        } => Some(ParseError {
+            span: Some(span),
+            token,
+            expected,
+            ..ParseError::from(ParseErrorKind::from(error))
+        }),
+        AnalyzerDiagnostic::Warning { .. } => None,
+    }
+}
+
+/// Loads the imports of an analyzed module, relative to the directory of `path`, and evaluates
+/// the module body with them as arguments. `source` is the content of `path`, to show the import
+/// statements of circular dependencies.
+fn link<M: Manager + 'static, I: Io, R: ModuleResolver>(
+    context: &mut Context<M, I, R>,
+    path: &str,
+    source: &str,
+    mut results: AnalyzerResults<M::Dealloc>,
+) -> Result<ParseResult<M::Dealloc>, ParseError> {
+    if let Some(error) = take_error(&mut results.diagnostics) {
+        return Err(error);
+    }
+    let module = results.module;
+    let mut args = Vec::with_capacity(module.import.len());
+    for (import, span) in module.import.iter().zip(results.import_spans) {
+        let specifier = String::from_utf16_lossy(import.items());
+        let import = ImportLocation::new(path, span, source);
+        let Some(import_path) = context.resolver.resolve(
+            context.manager,
+            context.io,
+            &context.policy,
+            &mut context.stats,
+            path,
+            &specifier,
+        ) else {
+            return Err(ParseError {
+                span: Some(span),
+                ..ParseError::from(ParseErrorKind::CannotResolveModule)
+            });
+        };
+        args.push(import_module(context, import_path, import)?);
+    }
+    let any = module
+        .body
+        .evaluate(context.manager, &args)
+        .map_err(|e| ParseError {
+            span: e.span(),
+            ..ParseError::from(ParseErrorKind::CannotEvaluate(e))
+        })?;
+    Ok(ParseResult {
+        data_type: results.data_type,
+        any,
+    })
+}
+
+/// Returns the value of the module `path`, from the cache if it was loaded before. Errors that
+/// prevent loading the module are located at `import`, the import statement.
+fn import_module<M: Manager + 'static, I: Io, R: ModuleResolver>(
+    context: &mut Context<M, I, R>,
+    path: String,
+    import: ImportLocation,
+) -> Result<Any<M::Dealloc>, ParseError> {
+    let at_import = |kind| ParseError {
+        span: Some(import.span),
+        ..ParseError::from(kind)
+    };
+    // The cache can be shared with a context of another policy, so the path is checked first.
+    context.policy.check_path(&path).map_err(at_import)?;
+    if let Some(any) = context.module_cache.complete.get(&path) {
+        return Ok(any.clone());
+    }
+    let progress = &context.module_cache.progress;
+    if let Some(index) = progress.iter().position(|loading| loading.path == path) {
+        // The imports from the module imported again up to the importing file.
+        let import_cycle = progress[index + 1..]
+            .iter()
+            .filter_map(|loading| loading.import.clone())
+            .collect();
+        return Err(ParseError {
+            import_cycle,
+            ..at_import(ParseErrorKind::CircularDependency)
+        });
     }
-    state.end()
+    context
+        .policy
+        .check_depth(progress.len())
+        .map_err(at_import)?;
+    let source = read_module(context, &path).map_err(at_import)?;
+    context.module_cache.progress.push(Loading {
+        path: path.clone(),
+        import: Some(import),
+    });
+    let result = load(context, &path, &source);
+    context.module_cache.progress.pop();
+    let result = result?;
+    context
+        .module_cache
+        .complete
+        .insert(path, result.any.clone());
+    Ok(result.any)
+}
+
+/// Reads the module `path` if the load policy of `context` allows it.
+fn read_module<M: Manager, I: Io, R: ModuleResolver>(
+    context: &mut Context<M, I, R>,
+    path: &str,
+) -> Result<String, ParseErrorKind> {
+    context.policy.read(context.io, &mut context.stats, path)
 }
 
 #[cfg(test)]
@@ -260,19 +325,30 @@ mod test {
     use crate::{
         common::default::default,
         js::{
+            any::Any,
             js_array::JsArrayRef,
             js_bigint::{from_u64, new_bigint, JsBigintRef, Sign},
             js_object::JsObjectRef,
             js_string::JsStringRef,
             type_::Type,
         },
-        mem::{global::GLOBAL, local::Local, manager::Manager},
+        mem::{
+            global::{Global, GLOBAL},
+            local::Local,
+            manager::Manager,
+        },
+        serializer::{to_djs::to_djs, to_json::to_json},
         tokenizer::{tokenize, ErrorType, JsonToken},
     };
 
-    use super::super::{parser::parse, path::concat, shared::DataType};
+    use super::super::{
+        parser::parse,
+        path::concat,
+        policy::{LoadPolicy, LoadStats},
+        shared::DataType,
+    };
 
-    use super::{parse_with_tokens, Context, ModuleCache, ParseError, ParseResult};
+    use super::{parse_with_tokens, Context, ModuleCache, ParseError, ParseErrorKind, ParseResult};
 
     fn virtual_io() -> VirtualIo {
         VirtualIo::new(&[])
@@ -332,6 +408,22 @@ mod test {
         assert_eq!(result.unwrap().data_type, DataType::Mjs);
     }
 
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_property_order() {
+        let djs_str = include_str!("../../test/test-property-order.d.cjs");
+        let tokens = tokenize(GLOBAL, djs_str.to_owned());
+        let any = parse_with_virtual_io(GLOBAL, tokens.into_iter())
+            .unwrap()
+            .any;
+        let expected = r#"{"2":3,"10":2,"4294967294":6,"b":8,"a":{"0":null,"1":null,"z":[]},"-1":4,"4294967295":5,"01":7}"#;
+        assert_eq!(to_json(any.clone()).unwrap(), expected);
+        assert_eq!(
+            to_djs(any, true).unwrap(),
+            format!("module.exports={expected}")
+        );
+    }
+
     #[test]
     #[wasm_bindgen_test]
     fn test_const() {
@@ -363,7 +455,7 @@ mod test {
         let tokens = tokenize(manager, json_str.to_owned());
         let result = parse_with_virtual_io(manager, tokens.into_iter());
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), ParseError::NewLineExpected);
+        assert_eq!(result.unwrap_err().kind, ParseErrorKind::NewLineExpected);
     }
 
     #[test]
@@ -566,9 +658,12 @@ mod test {
             &mut mc,
         );
 
-        let result = parse(&mut context);
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), ParseError::CircularDependency);
+        let error = parse(&mut context).unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::CircularDependency);
+        assert!(error.path.ends_with("test_circular_2.d.cjs.txt"));
+        assert!(error.import_cycle[0]
+            .path
+            .ends_with("test_circular_1.d.cjs.txt"));
 
         let io: VirtualIo = VirtualIo::new(&[]);
 
@@ -588,9 +683,59 @@ mod test {
             &mut mc,
         );
 
-        let result = parse(&mut context);
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), ParseError::CircularDependency);
+        let error = parse(&mut context).unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::CircularDependency);
+        assert!(error.path.ends_with("test_circular_2.d.mjs.txt"));
+        assert!(error.import_cycle[0]
+            .path
+            .ends_with("test_circular_1.d.mjs.txt"));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_error_location() {
+        let io: VirtualIo = VirtualIo::new(&[]);
+        let main_path = concat(io.current_dir().unwrap().as_str(), "main.d.cjs");
+        io.write(&main_path, b"module.exports = {\n  \"a\": 1\n  \"b\": 2\n}")
+            .unwrap();
+        let mut mc = default();
+        let mut context = Context::new(GLOBAL, &io, main_path.clone(), &mut mc);
+        let error = parse(&mut context).unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedToken);
+        assert_eq!(error.path, main_path);
+        let span = error.span.unwrap();
+        assert_eq!((span.begin.line, span.begin.column), (2, 2));
+        assert_eq!(span.begin.offset, 30);
+        assert_eq!(error.expected, ["`,`", "`}`"]);
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "UnexpectedToken: unexpected string \"b\", expected `,` or `}}`\n --> {main_path}:3:3\n  |\n3 |   \"b\": 2\n  |   ^^^"
+            )
+        );
+
+        // An error inside an imported module points into that module.
+        let module_path = concat(io.current_dir().unwrap().as_str(), "module.d.cjs");
+        io.write(&module_path, b"module.exports = [1 2]").unwrap();
+        io.write(&main_path, b"module.exports = require(\"module.d.cjs\")")
+            .unwrap();
+        let mut mc = default();
+        let mut context = Context::new(GLOBAL, &io, main_path.clone(), &mut mc);
+        let error = parse(&mut context).unwrap_err();
+        assert_eq!(error.path, module_path);
+        assert_eq!(error.token, Some("number `2`".to_owned()));
+        assert!(error
+            .to_string()
+            .ends_with("1 | module.exports = [1 2]\n  |                     ^"));
+
+        let io: VirtualIo = VirtualIo::new(&[]);
+        io.write(&main_path, b"[1,").unwrap();
+        let mut mc = default();
+        let mut context = Context::new(GLOBAL, &io, main_path.clone(), &mut mc);
+        let error = parse(&mut context).unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(error.span.unwrap().begin.column, 3);
+        assert_eq!(error.token, Some("end of input".to_owned()));
     }
 
     #[test]
@@ -621,7 +766,7 @@ mod test {
 
         let result = parse(&mut context);
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), ParseError::UnexpectedToken);
+        assert_eq!(result.unwrap_err().kind, ParseErrorKind::UnexpectedToken);
 
         let io: VirtualIo = VirtualIo::new(&[]);
 
@@ -644,7 +789,332 @@ mod test {
 
         let result = parse(&mut context);
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), ParseError::UnexpectedToken);
+        assert_eq!(result.unwrap_err().kind, ParseErrorKind::UnexpectedToken);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_relative_import() {
+        let io: VirtualIo = VirtualIo::new(&[]);
+        io.create_dir("sub").unwrap();
+        io.write(
+            "main.d.cjs",
+            b"const a = require(\"sub/a.d.cjs\")\nconst b = require(\"sub/b.d.cjs\")\nmodule.exports = [a, b]",
+        )
+        .unwrap();
+        io.write(
+            "sub/a.d.cjs",
+            b"const b = require(\"b.d.cjs\")\nmodule.exports = { b: b }",
+        )
+        .unwrap();
+        io.write("sub/b.d.cjs", b"module.exports = [1]").unwrap();
+        let mut mc = default();
+        let mut context = Context::new(GLOBAL, &io, "main.d.cjs".to_owned(), &mut mc);
+        let result = parse(&mut context).unwrap();
+        assert_eq!(to_json(result.any).unwrap(), r#"[{"b":[1]},[1]]"#);
+        assert_eq!(mc.complete.len(), 3);
+        assert!(mc.progress.is_empty());
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_interning() {
+        let io: VirtualIo = VirtualIo::new(&[]);
+        io.write(
+            "main.d.cjs",
+            b"const a = require(\"a.d.cjs\")\nmodule.exports = [{ name: \"x\" }, { name: \"x\" }, a]",
+        )
+        .unwrap();
+        io.write("a.d.cjs", b"module.exports = { name: \"name\" }")
+            .unwrap();
+        let mut mc = default();
+        let mut context = Context::new(GLOBAL, &io, "main.d.cjs".to_owned(), &mut mc);
+        let result = parse(&mut context).unwrap();
+        assert_eq!(
+            to_json(result.any.clone()).unwrap(),
+            r#"[{"name":"x"},{"name":"x"},{"name":"name"}]"#
+        );
+        let items: Vec<_> = (0..3).map(|i| result.any.get_index(i).unwrap()).collect();
+        let keys: Vec<_> = items.iter().map(|o| o.keys().unwrap()).collect();
+        assert_eq!(keys[0][0], keys[1][0]);
+        assert_eq!(keys[0][0], keys[2][0]);
+        let name: Vec<_> = "name".encode_utf16().collect();
+        assert_eq!(items[0].get(&name), items[1].get(&name));
+        assert_eq!(
+            items[2].get(&name).unwrap(),
+            Any::move_from(keys[0][0].clone())
+        );
+        // "a.d.cjs", "name" and "x" are allocated once.
+        let stats = mc.interner.stats();
+        assert_eq!(stats.strings, 3);
+        assert_eq!(stats.hits, 4);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_interning_shared_keys() {
+        let io: VirtualIo = VirtualIo::new(&[]);
+        let source = b"module.exports = [{ a_long_repeated_key: 1 }, { a_long_repeated_key: 2 }]";
+        io.write("a.d.cjs", source).unwrap();
+        io.write("b.d.cjs", source).unwrap();
+        // Whether both modules refer to the same string for the key of their first object.
+        let parse_all = |caches: &mut [ModuleCache<Global>]| {
+            let n = caches.len();
+            let keys: Vec<_> = ["a.d.cjs", "b.d.cjs"]
+                .iter()
+                .enumerate()
+                .map(|(i, path)| {
+                    let mc = &mut caches[i % n];
+                    let any = parse(&mut Context::new(GLOBAL, &io, path.to_string(), mc))
+                        .unwrap()
+                        .any;
+                    let array = any.try_move::<JsArrayRef<_>>().unwrap();
+                    let object = array.items()[0]
+                        .clone()
+                        .try_move::<JsObjectRef<_>>()
+                        .unwrap();
+                    Any::move_from(object.items()[0].0.clone())
+                })
+                .collect();
+            keys[0] == keys[1]
+        };
+        // Both modules share the key through the session interner.
+        assert!(parse_all(&mut [default()]));
+        // Each cache has its own interner, so each module has its own copy of the key.
+        assert!(!parse_all(&mut [default(), default()]));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_cache_after_error() {
+        let io: VirtualIo = VirtualIo::new(&[]);
+        io.write(
+            "main.d.mjs",
+            b"import b from \"bad.d.mjs\"\nexport default b",
+        )
+        .unwrap();
+        io.write(
+            "other.d.mjs",
+            b"import b from \"bad.d.mjs\"\nexport default [b]",
+        )
+        .unwrap();
+        io.write("bad.d.mjs", b"export default [1 2]").unwrap();
+        let mut mc = default();
+        for path in ["main.d.mjs", "other.d.mjs", "main.d.mjs"] {
+            let mut context = Context::new(GLOBAL, &io, path.to_owned(), &mut mc);
+            let error = parse(&mut context).unwrap_err();
+            assert_eq!(error.kind, ParseErrorKind::UnexpectedToken);
+            assert_eq!(error.path, "bad.d.mjs");
+        }
+        assert!(mc.progress.is_empty());
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_circular_error_location() {
+        let io: VirtualIo = VirtualIo::new(&[]);
+        io.write(
+            "a.d.cjs",
+            b"const b = require(\"b.d.cjs\")\nmodule.exports = b",
+        )
+        .unwrap();
+        io.write(
+            "b.d.cjs",
+            b"const a = require(\"a.d.cjs\")\nmodule.exports = a",
+        )
+        .unwrap();
+        let mut mc = default();
+        let mut context = Context::new(GLOBAL, &io, "a.d.cjs".to_owned(), &mut mc);
+        let error = parse(&mut context).unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::CircularDependency);
+        assert_eq!(error.path, "b.d.cjs");
+        assert_eq!(
+            error.to_string(),
+            "CircularDependency: a.d.cjs -> b.d.cjs -> a.d.cjs\n --> b.d.cjs:1:19\n  |\n1 | const a = require(\"a.d.cjs\")\n  |                   ^^^^^^^^^\nnote: imported here\n --> a.d.cjs:1:19\n  |\n1 | const b = require(\"b.d.cjs\")\n  |                   ^^^^^^^^^"
+        );
+
+        io.write("main.d.mjs", b"import a from \"a.d.mjs\"\nexport default a")
+            .unwrap();
+        io.write("a.d.mjs", b"import b from \"b.d.mjs\"\nexport default b")
+            .unwrap();
+        io.write(
+            "b.d.mjs",
+            b"const x = 1\nimport c from \"c.d.mjs\"\nexport default c",
+        )
+        .unwrap();
+        io.write("c.d.mjs", b"import a from \"a.d.mjs\"\nexport default a")
+            .unwrap();
+        let mut mc = default();
+        let mut context = Context::new(GLOBAL, &io, "main.d.mjs".to_owned(), &mut mc);
+        let error = parse(&mut context).unwrap_err();
+        let cycle: Vec<_> = error
+            .import_cycle
+            .iter()
+            .map(|i| (i.path.as_str(), i.span.begin.line))
+            .collect();
+        assert_eq!(cycle, [("a.d.mjs", 0), ("b.d.mjs", 1)]);
+        assert_eq!(
+            (error.path.as_str(), error.span.unwrap().begin.line),
+            ("c.d.mjs", 0)
+        );
+        assert!(error
+            .to_string()
+            .starts_with("CircularDependency: a.d.mjs -> b.d.mjs -> c.d.mjs -> a.d.mjs\n"));
+
+        io.write(
+            "self.d.mjs",
+            b"import s from \"self.d.mjs\"\nexport default s",
+        )
+        .unwrap();
+        let mut mc = default();
+        let mut context = Context::new(GLOBAL, &io, "self.d.mjs".to_owned(), &mut mc);
+        let error = parse(&mut context).unwrap_err();
+        assert!(error.import_cycle.is_empty());
+        assert!(error
+            .to_string()
+            .starts_with("CircularDependency: self.d.mjs -> self.d.mjs\n"));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_load_policy() {
+        let io: VirtualIo = VirtualIo::new(&[]);
+        io.create_dir("data").unwrap();
+        let files: &[(&str, &str)] = &[
+            ("secret.json", "\"secret\""),
+            (
+                "data/escape.d.mjs",
+                "import s from \"../secret.json\"\nexport default s",
+            ),
+            (
+                "data/text.d.mjs",
+                "import t from \"./a.txt\"\nexport default t",
+            ),
+            ("data/a.txt", "1"),
+            (
+                "data/a.d.mjs",
+                "import b from \"./b.d.mjs\"\nexport default [b]",
+            ),
+            (
+                "data/b.d.mjs",
+                "import c from \"./c.json\"\nexport default [c]",
+            ),
+            ("data/c.json", "[1, 2, 3]"),
+        ];
+        for (path, source) in files {
+            io.write(path, source.as_bytes()).unwrap();
+        }
+        let load = |path: &str, policy: LoadPolicy| {
+            let mut mc = default();
+            let mut context =
+                Context::new(GLOBAL, &io, path.to_owned(), &mut mc).with_policy(policy);
+            let result = parse(&mut context);
+            result
+                .map(|_| context.stats())
+                .map_err(|e| (e.kind, e.path, e.span.map(|s| s.begin.line)))
+        };
+        let error = |kind, path: &str, line| Err((kind, path.to_owned(), line));
+
+        assert!(load("data/escape.d.mjs", LoadPolicy::default()).is_ok());
+        assert_eq!(
+            load("data/escape.d.mjs", LoadPolicy::sandbox("data")),
+            error(
+                ParseErrorKind::PathOutsideRoot,
+                "data/escape.d.mjs",
+                Some(0)
+            )
+        );
+        assert_eq!(
+            load("secret.json", LoadPolicy::sandbox("data")),
+            error(ParseErrorKind::PathOutsideRoot, "secret.json", None)
+        );
+        assert_eq!(
+            load("data/text.d.mjs", LoadPolicy::sandbox("data")),
+            error(
+                ParseErrorKind::ExtensionNotAllowed,
+                "data/text.d.mjs",
+                Some(0)
+            )
+        );
+
+        let a_b = files[4].1.len() + files[5].1.len();
+        assert_eq!(
+            load("data/a.d.mjs", LoadPolicy::sandbox("data")),
+            Ok(LoadStats {
+                modules: 3,
+                bytes: a_b + files[6].1.len(),
+            })
+        );
+        let limits = [
+            (
+                LoadPolicy {
+                    max_modules: Some(2),
+                    ..default()
+                },
+                ParseErrorKind::TooManyModules,
+            ),
+            (
+                LoadPolicy {
+                    max_bytes: Some(a_b),
+                    ..default()
+                },
+                ParseErrorKind::TooManyBytes,
+            ),
+            (
+                LoadPolicy {
+                    max_depth: Some(1),
+                    ..default()
+                },
+                ParseErrorKind::ImportTooDeep,
+            ),
+        ];
+        for (policy, kind) in limits {
+            assert_eq!(
+                load("data/a.d.mjs", policy),
+                error(kind, "data/b.d.mjs", Some(0))
+            );
+        }
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_functions() {
+        let io: VirtualIo = VirtualIo::new(&[]);
+        io.write(
+            "main.d.mjs",
+            b"import n from \"n.d.mjs\"\nconst twice = f => x => f(f(x))\nconst wrap = x => [x, n]\nexport default twice(wrap)(1)",
+        )
+        .unwrap();
+        io.write("n.d.mjs", b"export default 2").unwrap();
+        let mut mc = default();
+        let mut context = Context::new(GLOBAL, &io, "main.d.mjs".to_owned(), &mut mc);
+        let result = parse(&mut context).unwrap();
+        assert_eq!(to_json(result.any).unwrap(), "[[1,2],2]");
+
+        io.write(
+            "main.d.mjs",
+            b"import f from \"f.d.mjs\"\nexport default [f]",
+        )
+        .unwrap();
+        io.write("f.d.mjs", b"export default x => [x]").unwrap();
+        let mut mc = default();
+        let mut context = Context::new(GLOBAL, &io, "main.d.mjs".to_owned(), &mut mc);
+        let result = parse(&mut context).unwrap();
+        // A module can export a function, but it isn't data.
+        assert_eq!(to_json(result.any), Err(core::fmt::Error));
+
+        // Functions are passed across modules in both directions.
+        io.write(
+            "main.d.mjs",
+            b"import twice from \"twice.d.mjs\"\nimport f from \"f.d.mjs\"\nconst wrap = x => [x, f(x)]\nexport default twice(wrap)(1)",
+        )
+        .unwrap();
+        io.write("twice.d.mjs", b"export default g => x => g(g(x))")
+            .unwrap();
+        let mut mc = default();
+        let mut context = Context::new(GLOBAL, &io, "main.d.mjs".to_owned(), &mut mc);
+        let result = parse(&mut context).unwrap();
+        assert_eq!(to_json(result.any).unwrap(), "[[1,[1]],[[1,[1]]]]");
     }
 
     #[test]
@@ -662,7 +1132,7 @@ mod test {
         {
             let tokens = [
                 JsonToken::ObjectBegin,
-                JsonToken::String(String::from("k")),
+                JsonToken::String("k".encode_utf16().collect()),
                 JsonToken::Colon,
                 JsonToken::ObjectBegin,
                 JsonToken::ObjectEnd,
@@ -684,7 +1154,7 @@ mod test {
         {
             let tokens = [
                 JsonToken::ObjectBegin,
-                JsonToken::String(String::from("k")),
+                JsonToken::String("k".encode_utf16().collect()),
                 JsonToken::Colon,
                 JsonToken::ObjectBegin,
                 JsonToken::ObjectEnd,
@@ -792,7 +1262,7 @@ mod test {
         assert!(result.is_ok());
         assert_eq!(result.unwrap().any.try_move(), Ok(0.1));
 
-        let tokens = [JsonToken::String(String::from("abc"))];
+        let tokens = [JsonToken::String("abc".encode_utf16().collect())];
         let result = parse_with_virtual_io(manager, tokens.into_iter());
         assert!(result.is_ok());
         let result = result.unwrap().any.try_move::<JsStringRef<M::Dealloc>>();
@@ -869,15 +1339,15 @@ mod test {
 
         let tokens = [
             JsonToken::ObjectBegin,
-            JsonToken::String(String::from("k1")),
+            JsonToken::String("k1".encode_utf16().collect()),
             JsonToken::Colon,
             JsonToken::Number(1.0),
             JsonToken::Comma,
-            JsonToken::String(String::from("k0")),
+            JsonToken::String("k0".encode_utf16().collect()),
             JsonToken::Colon,
             JsonToken::Number(0.0),
             JsonToken::Comma,
-            JsonToken::String(String::from("k2")),
+            JsonToken::String("k2".encode_utf16().collect()),
             JsonToken::Colon,
             JsonToken::Number(2.0),
             JsonToken::ObjectEnd,
@@ -892,12 +1362,12 @@ mod test {
         let items = result_unwrap.items();
         let (key0, value0) = items[0].clone();
         let key0_items = key0.items();
-        assert_eq!(key0_items, [0x6b, 0x30]);
-        assert_eq!(value0.try_move(), Ok(0.0));
+        assert_eq!(key0_items, [0x6b, 0x31]);
+        assert_eq!(value0.try_move(), Ok(1.0));
         let (key1, value1) = items[1].clone();
         let key1_items = key1.items();
-        assert_eq!(key1_items, [0x6b, 0x31]);
-        assert_eq!(value1.try_move(), Ok(1.0));
+        assert_eq!(key1_items, [0x6b, 0x30]);
+        assert_eq!(value1.try_move(), Ok(0.0));
         let (key2, value2) = items[2].clone();
         let key2_items = key2.items();
         assert_eq!(key2_items, [0x6b, 0x32]);
@@ -915,7 +1385,7 @@ mod test {
         assert!(items.is_empty());
         let tokens = [
             JsonToken::ObjectBegin,
-            JsonToken::String(String::from("k")),
+            JsonToken::String("k".encode_utf16().collect()),
             JsonToken::Colon,
             JsonToken::ObjectBegin,
             JsonToken::ObjectEnd,
@@ -984,7 +1454,7 @@ mod test {
         let result = parse_with_virtual_io(manager, tokens.into_iter());
         assert!(result.is_err());
 
-        let tokens = [JsonToken::ArrayBegin, JsonToken::String(String::default())];
+        let tokens = [JsonToken::ArrayBegin, JsonToken::String(Vec::default())];
         let result = parse_with_virtual_io(manager, tokens.into_iter());
         assert!(result.is_err());
 
@@ -1025,7 +1495,7 @@ mod test {
 
         let tokens = [
             JsonToken::ObjectBegin,
-            JsonToken::String(String::from("key")),
+            JsonToken::String("key".encode_utf16().collect()),
             JsonToken::Number(0.0),
             JsonToken::ObjectEnd,
         ];
@@ -1034,7 +1504,7 @@ mod test {
 
         let tokens = [
             JsonToken::ObjectBegin,
-            JsonToken::String(String::from("key")),
+            JsonToken::String("key".encode_utf16().collect()),
             JsonToken::Colon,
             JsonToken::Colon,
             JsonToken::Number(0.0),
@@ -1045,12 +1515,12 @@ mod test {
 
         let tokens = [
             JsonToken::ObjectBegin,
-            JsonToken::String(String::from("key0")),
+            JsonToken::String("key0".encode_utf16().collect()),
             JsonToken::Colon,
             JsonToken::Number(0.0),
             JsonToken::Comma,
             JsonToken::Comma,
-            JsonToken::String(String::from("key1")),
+            JsonToken::String("key1".encode_utf16().collect()),
             JsonToken::Colon,
             JsonToken::Number(1.0),
             JsonToken::ObjectEnd,
@@ -1068,7 +1538,7 @@ mod test {
 
         let tokens = [
             JsonToken::ObjectBegin,
-            JsonToken::String(String::from("key")),
+            JsonToken::String("key".encode_utf16().collect()),
             JsonToken::Colon,
             JsonToken::Number(0.0),
         ];
@@ -1078,7 +1548,7 @@ mod test {
         let tokens = [
             JsonToken::ObjectBegin,
             JsonToken::Comma,
-            JsonToken::String(String::from("key")),
+            JsonToken::String("key".encode_utf16().collect()),
             JsonToken::Colon,
             JsonToken::Number(0.0),
             JsonToken::ObjectEnd,
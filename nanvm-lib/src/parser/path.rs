@@ -1,17 +1,21 @@
 use crate::common::default::default;
 
+/// Removes `.`, `..` and empty segments. An absolute path stays absolute.
 pub fn normalize(path: &str) -> String {
+    let absolute = path.starts_with('/');
     let path_split: Vec<_> = path.split('/').collect();
     let mut result_split: Vec<&str> = Vec::new();
     for &dir in path_split.iter() {
         match dir {
-            "" => {}
+            "" | "." => {}
             ".." => {
                 let last = result_split.last();
                 match last {
                     Some(x) if x != &".." => {
                         result_split.pop();
                     }
+                    // There is nothing above the root.
+                    None if absolute => {}
                     _ => {
                         result_split.push(dir);
                     }
@@ -22,19 +26,29 @@ pub fn normalize(path: &str) -> String {
             }
         }
     }
-    result_split.join("/")
+    let result = result_split.join("/");
+    match absolute {
+        true => format!("/{result}"),
+        false => result,
+    }
 }
 
+/// Joins the path `b` to the directory `a`. An empty `a` is the current directory.
 pub fn concat(a: &str, b: &str) -> String {
+    if a.is_empty() {
+        return normalize(b);
+    }
     let mut concat = a.to_string();
     concat.push('/');
     concat.push_str(b);
     normalize(&concat)
 }
 
+/// Splits `path` into the directory and the file name. The directory of a file in the root is `/`.
 pub fn split(path: &str) -> (&str, &str) {
     match path.rsplit_once('/') {
         None => (default(), path),
+        Some(("", file)) => ("/", file),
         Some(t) => t,
     }
 }
@@ -43,7 +57,7 @@ pub fn split(path: &str) -> (&str, &str) {
 mod test {
     use wasm_bindgen_test::wasm_bindgen_test;
 
-    use crate::parser::path::{concat, normalize};
+    use crate::parser::path::{concat, normalize, split};
 
     #[test]
     #[wasm_bindgen_test]
@@ -53,6 +67,12 @@ mod test {
 
         let norm = normalize("../../dir/../file.json");
         assert_eq!(norm, "../../file.json");
+
+        let norm = normalize("./dir/./file.json");
+        assert_eq!(norm, "dir/file.json");
+
+        let norm = normalize("/../dir//file.json");
+        assert_eq!(norm, "/dir/file.json");
     }
 
     #[test]
@@ -66,5 +86,11 @@ mod test {
 
         let result = concat("a/../b/..", "c");
         assert_eq!(result, "c");
+
+        let result = concat("", "../c");
+        assert_eq!(result, "../c");
+
+        let result = concat(split("/a.d.mjs").0, "./c");
+        assert_eq!(result, "/c");
     }
 }
@@ -0,0 +1,220 @@
+use io_trait::{Io, Metadata};
+
+use super::{path::normalize, shared::ParseErrorKind};
+
+/// Limits what a `Context` loads, e.g. to parse untrusted modules. `LoadPolicy::default()` has no
+/// limits.
+///
+/// Paths are checked after normalization, so `..` can't escape `root`. Symbolic links are only
+/// resolved with `real_path`, without it a link inside `root` can still point outside of it.
+#[derive(Debug, Default, Clone)]
+pub struct LoadPolicy {
+    /// The directory that all modules must be in, e.g. `/srv/data`. An empty string is the
+    /// current directory. A relative root doesn't contain absolute paths and vice versa. Module
+    /// resolvers don't look at files outside of it either.
+    pub root: Option<String>,
+    /// Returns the path of an existing file or directory with symbolic links resolved, e.g.
+    /// `policy::real_path`. Paths are checked against `root` after it, if it succeeds for both of
+    /// them.
+    pub real_path: Option<fn(&str) -> Option<String>>,
+    /// The extensions that module paths can end with, e.g. `.json` and `.d.mjs`.
+    pub extensions: Option<Vec<String>>,
+    /// The maximum number of modules read, including the main one.
+    pub max_modules: Option<usize>,
+    /// The maximum number of bytes read from all modules.
+    pub max_bytes: Option<usize>,
+    /// The maximum length of an import chain. The main module has the depth `0` and the modules it
+    /// imports have the depth `1`.
+    pub max_depth: Option<usize>,
+}
+
+impl LoadPolicy {
+    /// Only allows modules in `root` with the DJS extensions.
+    pub fn sandbox(root: &str) -> Self {
+        LoadPolicy {
+            root: Some(root.to_owned()),
+            extensions: Some([".json", ".d.cjs", ".d.mjs"].map(str::to_owned).to_vec()),
+            ..LoadPolicy::default()
+        }
+    }
+
+    /// Checks that `path` is in `root`.
+    pub fn check_root(&self, path: &str) -> Result<(), ParseErrorKind> {
+        let Some(root) = &self.root else {
+            return Ok(());
+        };
+        let real = self
+            .real_path
+            .and_then(|real_path| Some((real_path(root)?, real_path(path)?)));
+        let inside = match real {
+            Some((root, path)) => is_inside(&normalize(&root), &normalize(&path)),
+            None => is_inside(&normalize(root), &normalize(path)),
+        };
+        match inside {
+            true => Ok(()),
+            false => Err(ParseErrorKind::PathOutsideRoot),
+        }
+    }
+
+    /// Checks that the module `path` can be loaded.
+    pub fn check_path(&self, path: &str) -> Result<(), ParseErrorKind> {
+        self.check_root(path)?;
+        if let Some(extensions) = &self.extensions {
+            if !extensions.iter().any(|e| path.ends_with(e.as_str())) {
+                return Err(ParseErrorKind::ExtensionNotAllowed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that a module can be imported at `depth`.
+    pub fn check_depth(&self, depth: usize) -> Result<(), ParseErrorKind> {
+        match self.max_depth {
+            Some(max) if depth > max => Err(ParseErrorKind::ImportTooDeep),
+            _ => Ok(()),
+        }
+    }
+
+    /// Adds a module of `len` bytes to `stats`, if it stays within the limits.
+    pub fn check_read(&self, stats: &mut LoadStats, len: usize) -> Result<(), ParseErrorKind> {
+        let modules = stats.modules + 1;
+        let bytes = stats.bytes.saturating_add(len);
+        if self.max_modules.is_some_and(|max| modules > max) {
+            return Err(ParseErrorKind::TooManyModules);
+        }
+        if self.max_bytes.is_some_and(|max| bytes > max) {
+            return Err(ParseErrorKind::TooManyBytes);
+        }
+        *stats = LoadStats { modules, bytes };
+        Ok(())
+    }
+
+    /// Reads the module `path` if the policy allows it, and adds it to `stats`.
+    pub fn read<I: Io>(
+        &self,
+        io: &I,
+        stats: &mut LoadStats,
+        path: &str,
+    ) -> Result<String, ParseErrorKind> {
+        self.check_path(path)?;
+        // The size is checked before reading, so a file over the limit isn't read at all.
+        let metadata = io
+            .metadata(path)
+            .map_err(|_| ParseErrorKind::CannotReadFile)?;
+        let len = usize::try_from(metadata.len()).unwrap_or(usize::MAX);
+        self.check_read(&mut stats.clone(), len)?;
+        let source = io
+            .read_to_string(path)
+            .map_err(|_| ParseErrorKind::CannotReadFile)?;
+        self.check_read(stats, source.len())?;
+        Ok(source)
+    }
+}
+
+/// Resolves symbolic links with the file system, for `LoadPolicy::real_path`.
+pub fn real_path(path: &str) -> Option<String> {
+    let path = std::fs::canonicalize(path).ok()?;
+    path.to_str().map(str::to_owned)
+}
+
+/// What a `Context` has read so far.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LoadStats {
+    pub modules: usize,
+    pub bytes: usize,
+}
+
+/// Whether the normalized `path` is in the normalized directory `root`.
+fn is_inside(root: &str, path: &str) -> bool {
+    if path == ".." || path.starts_with("../") || path.starts_with('/') != root.starts_with('/') {
+        return false;
+    }
+    match root {
+        "" | "/" => true,
+        _ => path
+            .strip_prefix(root)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/')),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::parser::{path::normalize, shared::ParseErrorKind};
+
+    use super::{LoadPolicy, LoadStats};
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_path() {
+        let policy = LoadPolicy::sandbox("/srv/data/");
+        assert_eq!(policy.check_path("/srv/data/a.json"), Ok(()));
+        assert_eq!(policy.check_path("/srv/data/./b/../a.d.mjs"), Ok(()));
+        let outside = Err(ParseErrorKind::PathOutsideRoot);
+        assert_eq!(policy.check_path("/srv/data/../secret.json"), outside);
+        assert_eq!(policy.check_path("/srv/database.json"), outside);
+        assert_eq!(policy.check_path("data/a.json"), outside);
+        assert_eq!(
+            policy.check_path("/srv/data/passwd"),
+            Err(ParseErrorKind::ExtensionNotAllowed)
+        );
+
+        let policy = LoadPolicy::sandbox("");
+        assert_eq!(policy.check_path("a/b.json"), Ok(()));
+        assert_eq!(policy.check_path("a/../../b.json"), outside);
+        assert_eq!(policy.check_path("/a.json"), outside);
+
+        assert_eq!(
+            LoadPolicy::default().check_path("../../../etc/passwd"),
+            Ok(())
+        );
+
+        /// `data/link` is a link to `/etc`, and the current directory is `/srv`.
+        fn real_path(path: &str) -> Option<String> {
+            let path = normalize(path);
+            match path.strip_prefix("data/link") {
+                Some(rest) => Some(format!("/etc{rest}")),
+                None => Some(format!("/srv/{path}")),
+            }
+        }
+        let policy = LoadPolicy {
+            real_path: Some(real_path),
+            ..LoadPolicy::sandbox("data")
+        };
+        assert_eq!(policy.check_path("data/a.json"), Ok(()));
+        assert_eq!(policy.check_path("data/link/passwd.json"), outside);
+        assert_eq!(policy.check_root("data/link"), outside);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_limits() {
+        let policy = LoadPolicy {
+            max_modules: Some(2),
+            max_bytes: Some(10),
+            max_depth: Some(1),
+            ..LoadPolicy::default()
+        };
+        let mut stats = LoadStats::default();
+        assert_eq!(policy.check_read(&mut stats, 6), Ok(()));
+        assert_eq!(
+            policy.check_read(&mut stats, 5),
+            Err(ParseErrorKind::TooManyBytes)
+        );
+        assert_eq!(policy.check_read(&mut stats, 4), Ok(()));
+        assert_eq!(
+            stats,
+            LoadStats {
+                modules: 2,
+                bytes: 10
+            }
+        );
+        assert_eq!(
+            policy.check_read(&mut stats, 0),
+            Err(ParseErrorKind::TooManyModules)
+        );
+        assert_eq!(policy.check_depth(1), Ok(()));
+        assert_eq!(policy.check_depth(2), Err(ParseErrorKind::ImportTooDeep));
+    }
+}
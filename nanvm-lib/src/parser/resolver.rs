@@ -0,0 +1,677 @@
+use std::{cell::Cell, collections::BTreeMap};
+
+use io_trait::{Io, Metadata};
+
+use super::{
+    analyzer::{AnalyzerDiagnostic, AnalyzerParameters, AnalyzerState},
+    path::{concat, normalize, split},
+    policy::{LoadPolicy, LoadStats},
+    shared::DataType,
+};
+use crate::{
+    js::{
+        any::Any, js_array::JsArrayRef, js_object::JsObjectRef, js_string::JsStringRef, null::Null,
+    },
+    mem::manager::{Dealloc, Manager},
+};
+
+/// Finds the module that an `import` or `require` path refers to.
+pub trait ModuleResolver {
+    /// Returns the path of the file that `specifier` refers to from the module `referrer`, or
+    /// `None` if `specifier` can't refer to a module. The file doesn't have to exist. Files outside
+    /// of the root of `policy` must not be looked at, so the result doesn't tell if they exist.
+    /// Files that are read, e.g. `package.json`, are read with `LoadPolicy::read`, so they count in
+    /// `stats`, and their values are allocated with `manager`.
+    fn resolve<M: Manager + 'static, I: Io>(
+        &self,
+        manager: M,
+        io: &I,
+        policy: &LoadPolicy,
+        stats: &mut LoadStats,
+        referrer: &str,
+        specifier: &str,
+    ) -> Option<String>;
+}
+
+/// The extensions tried for a path without one, in this order.
+const EXTENSIONS: [&str; 3] = [".d.mjs", ".d.cjs", ".json"];
+
+/// The conditions of `package.json` `exports` that are accepted, in any order.
+const CONDITIONS: [&str; 4] = ["import", "require", "node", "default"];
+
+/// Resolves paths like Node.js does:
+/// - `./a.d.mjs` and `../a.d.mjs` are relative to the directory of the importing module,
+/// - `/a.d.mjs` and `file:///a.d.mjs` are absolute,
+/// - other paths are packages in `node_modules` of the importing module's directory or of one of
+///   its parents, resolved with `exports` or `main` of their `package.json`.
+///
+/// A path without one of the DJS extensions is tried with them and as a directory with an `index`
+/// file. A path that is neither relative nor a package is relative, as in the first versions of
+/// DJS.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NodeResolver;
+
+impl ModuleResolver for NodeResolver {
+    fn resolve<M: Manager + 'static, I: Io>(
+        &self,
+        manager: M,
+        io: &I,
+        policy: &LoadPolicy,
+        stats: &mut LoadStats,
+        referrer: &str,
+        specifier: &str,
+    ) -> Option<String> {
+        let files = Files {
+            manager,
+            io,
+            policy,
+            stats: Cell::new(*stats),
+        };
+        let result = resolve(&files, referrer, specifier);
+        *stats = files.stats.get();
+        result
+    }
+}
+
+/// Resolves `specifier` from the module `referrer` in `io`, see `NodeResolver`.
+fn resolve(
+    io: &Files<impl Manager + 'static, impl Io>,
+    referrer: &str,
+    specifier: &str,
+) -> Option<String> {
+    let dir = split(referrer).0;
+    if let Some(url) = specifier.strip_prefix("file:") {
+        return Some(probe(io, &normalize(&file_url_path(url)?)));
+    }
+    if specifier.starts_with('/') {
+        return Some(probe(io, &normalize(specifier)));
+    }
+    let relative = [".", ".."].contains(&specifier)
+        || specifier.starts_with("./")
+        || specifier.starts_with("../");
+    if !relative {
+        if let Some(package) = find_package(io, dir, specifier) {
+            return package;
+        }
+    }
+    Some(probe(io, &concat(dir, specifier)))
+}
+
+/// The files in the root of a load policy. Files outside of it don't exist for a resolver.
+struct Files<'a, M: Manager, I: Io> {
+    manager: M,
+    io: &'a I,
+    policy: &'a LoadPolicy,
+    /// What the policy allows to read, updated by `read_to_string`.
+    stats: Cell<LoadStats>,
+}
+
+impl<M: Manager, I: Io> Files<'_, M, I> {
+    fn allows(&self, path: &str) -> bool {
+        self.policy.check_root(path).is_ok()
+    }
+
+    fn is_file(&self, path: &str) -> bool {
+        self.allows(path) && self.io.metadata(path).is_ok_and(|m| !m.is_dir())
+    }
+
+    fn is_dir(&self, path: &str) -> bool {
+        self.allows(path) && self.io.metadata(path).is_ok_and(|m| m.is_dir())
+    }
+
+    /// Reads `path` with `LoadPolicy::read`, so the file counts in the load stats.
+    fn read_to_string(&self, path: &str) -> Option<String> {
+        let mut stats = self.stats.get();
+        let source = self.policy.read(self.io, &mut stats, path).ok()?;
+        self.stats.set(stats);
+        Some(source)
+    }
+}
+
+/// Returns the first existing file of `path`, `path` with one of `EXTENSIONS`, and `index` with
+/// one of them in the directory `path`. If there is none, `path` itself.
+fn probe(io: &Files<impl Manager + 'static, impl Io>, path: &str) -> String {
+    if io.is_file(path) {
+        return path.to_owned();
+    }
+    let index = concat(path, "index");
+    EXTENSIONS
+        .iter()
+        .map(|e| format!("{path}{e}"))
+        .chain(EXTENSIONS.iter().map(|e| format!("{index}{e}")))
+        .find(|p| io.is_file(p))
+        .unwrap_or_else(|| path.to_owned())
+}
+
+/// The path of a `file:` URL without the scheme, e.g. `/a b` for `file:///a%20b`. Only local
+/// files are supported, and `%2F` isn't.
+fn file_url_path(url: &str) -> Option<String> {
+    let path = match url.strip_prefix("//") {
+        Some(rest) => {
+            let (host, path) = rest.split_at(rest.find('/')?);
+            if !host.is_empty() && host != "localhost" {
+                return None;
+            }
+            path
+        }
+        None if url.starts_with('/') => url,
+        None => return None,
+    };
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            let decoded = u8::from_str_radix(hex, 16).ok()?;
+            // An encoded `/` would be a separator after decoding, e.g. `..%2F`.
+            if decoded == b'/' {
+                return None;
+            }
+            bytes.push(decoded);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Splits a package path into the package name and the subpath, e.g. `@scope/name/a.json` into
+/// `@scope/name` and `./a.json`.
+fn split_package(specifier: &str) -> Option<(&str, String)> {
+    let name_len = match specifier.strip_prefix('@') {
+        Some(scoped) => {
+            let scope_len = scoped.find('/')? + 2;
+            let rest = &specifier[scope_len..];
+            scope_len + rest.find('/').unwrap_or(rest.len())
+        }
+        None => specifier.find('/').unwrap_or(specifier.len()),
+    };
+    let (name, subpath) = specifier.split_at(name_len);
+    Some((name, format!(".{subpath}")))
+}
+
+/// Looks for the package of `specifier` in `node_modules` of `dir` and of its parents up to the
+/// root of the load policy. Returns `None` if there is no such package, and `Some(None)` if the
+/// package doesn't export the path.
+fn find_package(
+    io: &Files<impl Manager + 'static, impl Io>,
+    dir: &str,
+    specifier: &str,
+) -> Option<Option<String>> {
+    let (name, subpath) = split_package(specifier)?;
+    let mut dir = dir;
+    loop {
+        if !io.allows(dir) {
+            return None;
+        }
+        let package = concat(dir, &format!("node_modules/{name}"));
+        if io.is_dir(&package) {
+            return Some(resolve_in_package(io, &package, &subpath));
+        }
+        if dir.is_empty() || dir == "/" {
+            return None;
+        }
+        dir = match split(dir).0 {
+            "" if dir.starts_with('/') => "/",
+            parent => parent,
+        };
+    }
+}
+
+/// Returns `true` if the relative `path` has no empty, `.` or `..` segments, so it can't leave the
+/// directory it's relative to, e.g. `data/a.json`.
+fn is_inside_segments(path: &str) -> bool {
+    path.split('/').all(|s| !["", ".", ".."].contains(&s))
+}
+
+/// Resolves `subpath`, e.g. `.` or `./data.json`, in the package directory `package`. The result
+/// is always in `package`.
+fn resolve_in_package(
+    io: &Files<impl Manager + 'static, impl Io>,
+    package: &str,
+    subpath: &str,
+) -> Option<String> {
+    if let Some(rest) = subpath.strip_prefix("./") {
+        if !is_inside_segments(rest) {
+            return None;
+        }
+    }
+    let path = concat(package, "package.json");
+    // A manifest that can't be read, e.g. over the load limits, doesn't fall back to `index`.
+    let manifest = match io.is_file(&path) {
+        true => Some(read_json(io, &path)?),
+        false => None,
+    };
+    let field = |name| manifest.as_ref().and_then(|m| property(m, name));
+    if let Some(exports) = field("exports") {
+        let target = match_exports(&exports, subpath)?;
+        return Some(concat(package, &target));
+    }
+    if subpath != "." {
+        return Some(probe(io, &concat(package, subpath)));
+    }
+    let main = match field("main").and_then(|main| string(&main)) {
+        Some(main) => {
+            let main = normalize(&main);
+            if main.starts_with('/') || main == ".." || main.starts_with("../") {
+                return None;
+            }
+            main
+        }
+        None => ".".to_owned(),
+    };
+    Some(probe(io, &concat(package, &main)))
+}
+
+/// Finds the target of `subpath` in the `exports` of a `package.json`. Supports a single target,
+/// subpaths, subpath patterns with `*`, and conditions.
+fn match_exports<D: Dealloc>(exports: &Any<D>, subpath: &str) -> Option<String> {
+    let Some(entries) = entries(exports) else {
+        return (subpath == ".").then(|| target(exports, None)).flatten();
+    };
+    if !entries.iter().any(|(k, _)| k.starts_with('.')) {
+        // Conditions of the main export.
+        return (subpath == ".").then(|| target(exports, None)).flatten();
+    }
+    if let Some((_, value)) = entries.iter().find(|(k, _)| k == subpath) {
+        return target(value, None);
+    }
+    // The pattern with the longest prefix wins.
+    entries
+        .iter()
+        .filter_map(|(k, v)| {
+            let (prefix, suffix) = k.split_once('*')?;
+            let matched = subpath.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            Some((prefix.len(), v, matched))
+        })
+        .max_by_key(|(len, ..)| *len)
+        .and_then(|(_, v, matched)| target(v, Some(matched)))
+}
+
+/// The path of an export target: a string, the first accepted condition of an object, or the first
+/// valid item of an array. `*` in the path is replaced with `matched`. A path with `.` or `..`
+/// segments is invalid, also after the replacement, so a target can't leave the package.
+fn target<D: Dealloc>(value: &Any<D>, matched: Option<&str>) -> Option<String> {
+    if value.is::<Null>() {
+        return None;
+    }
+    if let Some(path) = string(value) {
+        let path = match matched {
+            Some(matched) => path.replace('*', matched),
+            None => path,
+        };
+        // Targets are relative to the package.
+        return path
+            .strip_prefix("./")
+            .is_some_and(is_inside_segments)
+            .then_some(path);
+    }
+    if let Ok(array) = value.clone().try_move::<JsArrayRef<D>>() {
+        return array.items().iter().find_map(|item| target(item, matched));
+    }
+    entries(value)?
+        .iter()
+        .find(|(k, _)| CONDITIONS.contains(&k.as_str()))
+        .and_then(|(_, v)| target(v, matched))
+}
+
+/// Reads the JSON file `path`, `None` if it can't be read or isn't valid JSON.
+fn read_json<M: Manager + 'static>(io: &Files<M, impl Io>, path: &str) -> Option<Any<M::Dealloc>> {
+    let source = io.read_to_string(path)?;
+    let mut state = AnalyzerState::new(AnalyzerParameters {
+        data_type: DataType::Json,
+    });
+    for c in source.chars() {
+        state.push_mut(io.manager, c);
+    }
+    let results = state.end(io.manager);
+    if results.data_type != DataType::Json
+        || results.diagnostics.iter().any(AnalyzerDiagnostic::is_error)
+    {
+        return None;
+    }
+    results.module.body.evaluate(io.manager, &[]).ok()
+}
+
+fn string<D: Dealloc>(any: &Any<D>) -> Option<String> {
+    let s = any.clone().try_move::<JsStringRef<D>>().ok()?;
+    Some(String::from_utf16_lossy(s.items()))
+}
+
+fn entries<D: Dealloc>(any: &Any<D>) -> Option<Vec<(String, Any<D>)>> {
+    let o = any.clone().try_move::<JsObjectRef<D>>().ok()?;
+    Some(
+        o.items()
+            .iter()
+            .map(|(k, v)| (String::from_utf16_lossy(k.items()), v.clone()))
+            .collect(),
+    )
+}
+
+fn property<D: Dealloc>(any: &Any<D>, name: &str) -> Option<Any<D>> {
+    entries(any)?
+        .into_iter()
+        .find_map(|(k, v)| (k == name).then_some(v))
+}
+
+/// Resolves only the specifiers in its map, like an import map: a key that ends with `/` maps all
+/// the specifiers that start with it, e.g. `lib/` to `/vendor/lib/`. Other modules can't be
+/// imported, which makes it useful for tests and for embedding with a fixed set of modules.
+#[derive(Debug, Default, Clone)]
+pub struct MapResolver {
+    map: BTreeMap<String, String>,
+}
+
+impl MapResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, specifier: &str, path: &str) -> Self {
+        self.map.insert(specifier.to_owned(), path.to_owned());
+        self
+    }
+}
+
+impl ModuleResolver for MapResolver {
+    fn resolve<M: Manager + 'static, I: Io>(
+        &self,
+        _: M,
+        _: &I,
+        _: &LoadPolicy,
+        _: &mut LoadStats,
+        _: &str,
+        specifier: &str,
+    ) -> Option<String> {
+        if let Some(path) = self.map.get(specifier) {
+            return Some(path.clone());
+        }
+        self.map
+            .iter()
+            .filter(|(k, _)| k.ends_with('/') && specifier.starts_with(k.as_str()))
+            .max_by_key(|(k, _)| k.len())
+            .map(|(k, path)| format!("{path}{}", &specifier[k.len()..]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use io_test::VirtualIo;
+    use io_trait::Io;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        mem::global::GLOBAL,
+        parser::{
+            path::normalize,
+            policy::{LoadPolicy, LoadStats},
+        },
+    };
+
+    use super::{split_package, MapResolver, ModuleResolver, NodeResolver};
+
+    fn io(files: &[(&str, &str)]) -> VirtualIo {
+        let io = VirtualIo::new(&[]);
+        for (path, source) in files {
+            io.write_recursively(path, source.as_bytes()).unwrap();
+        }
+        io
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_paths() {
+        let io = io(&[
+            ("a/b.d.mjs", ""),
+            ("a/dir/index.json", ""),
+            ("/abs/x-y.json", ""),
+            ("a/legacy.d.cjs", ""),
+        ]);
+        let resolve = |specifier| {
+            NodeResolver.resolve(
+                GLOBAL,
+                &io,
+                &LoadPolicy::default(),
+                &mut LoadStats::default(),
+                "a/main.d.mjs",
+                specifier,
+            )
+        };
+        assert_eq!(resolve("./b"), Some("a/b.d.mjs".to_owned()));
+        assert_eq!(resolve("./b.d.mjs"), Some("a/b.d.mjs".to_owned()));
+        assert_eq!(resolve("./dir"), Some("a/dir/index.json".to_owned()));
+        assert_eq!(resolve("../a/./dir/"), Some("a/dir/index.json".to_owned()));
+        assert_eq!(resolve("./missing"), Some("a/missing".to_owned()));
+        assert_eq!(resolve("/abs/x-y"), Some("/abs/x-y.json".to_owned()));
+        assert_eq!(
+            resolve("file:///abs/x%2Dy.json"),
+            Some("/abs/x-y.json".to_owned())
+        );
+        assert_eq!(
+            resolve("file://localhost/abs/x-y.json"),
+            Some("/abs/x-y.json".to_owned())
+        );
+        assert_eq!(resolve("file://host/abs/x-y.json"), None);
+        assert_eq!(resolve("file:abs/x-y.json"), None);
+        assert_eq!(resolve("file:///abs/%2"), None);
+        assert_eq!(resolve("file:///abs/..%2F..%2fetc/passwd"), None);
+        assert_eq!(resolve("legacy.d.cjs"), Some("a/legacy.d.cjs".to_owned()));
+        let resolve = |specifier| {
+            NodeResolver.resolve(
+                GLOBAL,
+                &io,
+                &LoadPolicy::default(),
+                &mut LoadStats::default(),
+                "/abs/main.d.mjs",
+                specifier,
+            )
+        };
+        assert_eq!(resolve("./x-y"), Some("/abs/x-y.json".to_owned()));
+        assert_eq!(resolve("../../abs/x-y"), Some("/abs/x-y.json".to_owned()));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_packages() {
+        let io = io(&[
+            (
+                "app/node_modules/pkg/package.json",
+                r#"{
+                    "exports": {
+                        ".": { "types": "./x.d.ts", "import": "./lib/main.d.mjs" },
+                        "./data/*": "./data/*.json",
+                        "./data/private/*": null,
+                        "./list": ["../outside.json", "./list.json"],
+                        "./up": "./lib/../../outside.json"
+                    }
+                }"#,
+            ),
+            ("app/node_modules/pkg/lib/main.d.mjs", ""),
+            ("node_modules/old/package.json", r#"{"main": "lib"}"#),
+            ("node_modules/old/lib/index.d.cjs", ""),
+            ("node_modules/old/extra.json", ""),
+            (
+                "node_modules/escape/package.json",
+                r#"{"main": "../old/extra.json"}"#,
+            ),
+            (
+                "node_modules/single/package.json",
+                r#"{"exports": "./single.json"}"#,
+            ),
+        ]);
+        let resolve = |specifier| {
+            NodeResolver.resolve(
+                GLOBAL,
+                &io,
+                &LoadPolicy::default(),
+                &mut LoadStats::default(),
+                "app/src/main.d.mjs",
+                specifier,
+            )
+        };
+        assert_eq!(
+            resolve("pkg"),
+            Some("app/node_modules/pkg/lib/main.d.mjs".to_owned())
+        );
+        assert_eq!(
+            resolve("pkg/data/users"),
+            Some("app/node_modules/pkg/data/users.json".to_owned())
+        );
+        assert_eq!(resolve("pkg/data/private/key"), None);
+        assert_eq!(
+            resolve("pkg/list"),
+            Some("app/node_modules/pkg/list.json".to_owned())
+        );
+        assert_eq!(resolve("pkg/lib/main.d.mjs"), None);
+        // Paths can't leave the package directory.
+        assert_eq!(resolve("pkg/data/../../../secret"), None);
+        assert_eq!(resolve("pkg/data/./users"), None);
+        assert_eq!(resolve("pkg/up"), None);
+        assert_eq!(resolve("old/../../secret.json"), None);
+        assert_eq!(resolve("escape"), None);
+        assert_eq!(
+            resolve("old"),
+            Some("node_modules/old/lib/index.d.cjs".to_owned())
+        );
+        assert_eq!(
+            resolve("old/extra"),
+            Some("node_modules/old/extra.json".to_owned())
+        );
+        assert_eq!(
+            resolve("single"),
+            Some("node_modules/single/single.json".to_owned())
+        );
+        assert_eq!(resolve("single/other"), None);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_stats() {
+        let manifest = r#"{"main": "lib.json"}"#;
+        let io = io(&[("node_modules/pkg/package.json", manifest)]);
+        let mut stats = LoadStats::default();
+        let resolve = |policy: &LoadPolicy, stats: &mut LoadStats| {
+            NodeResolver.resolve(GLOBAL, &io, policy, stats, "main.d.mjs", "pkg")
+        };
+        assert_eq!(
+            resolve(&LoadPolicy::default(), &mut stats),
+            Some("node_modules/pkg/lib.json".to_owned())
+        );
+        assert_eq!(
+            stats,
+            LoadStats {
+                modules: 1,
+                bytes: manifest.len()
+            }
+        );
+        // A `package.json` over the limits isn't read.
+        let policy = LoadPolicy {
+            max_bytes: Some(manifest.len() * 3 / 2),
+            ..LoadPolicy::default()
+        };
+        assert_eq!(resolve(&policy, &mut stats), None);
+        assert_eq!(stats.modules, 1);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_root() {
+        let io = io(&[
+            ("node_modules/outside/index.json", ""),
+            (
+                "node_modules/outside/package.json",
+                r#"{"main": "index.json"}"#,
+            ),
+            ("app/node_modules/inside/index.json", ""),
+            ("secret.json", ""),
+            ("app/link/passwd.json", ""),
+        ]);
+        /// `app/link` is a link to `/etc`.
+        fn real_path(path: &str) -> Option<String> {
+            let path = normalize(path);
+            match path.strip_prefix("app/link") {
+                Some(rest) => Some(format!("/etc{rest}")),
+                None => Some(format!("/srv/{path}")),
+            }
+        }
+        let policy = LoadPolicy {
+            real_path: Some(real_path),
+            ..LoadPolicy::sandbox("app")
+        };
+        let resolve = |specifier| {
+            NodeResolver.resolve(
+                GLOBAL,
+                &io,
+                &policy,
+                &mut LoadStats::default(),
+                "app/src/a.d.mjs",
+                specifier,
+            )
+        };
+        assert_eq!(
+            resolve("inside"),
+            Some("app/node_modules/inside/index.json".to_owned())
+        );
+        // The walk stops at the root, and files outside of it aren't probed.
+        assert_eq!(resolve("outside"), Some("app/src/outside".to_owned()));
+        assert_eq!(resolve("../../secret"), Some("secret".to_owned()));
+        assert_eq!(
+            resolve("../link/passwd"),
+            Some("app/link/passwd".to_owned())
+        );
+        let resolve = |specifier| {
+            NodeResolver.resolve(
+                GLOBAL,
+                &io,
+                &LoadPolicy::default(),
+                &mut LoadStats::default(),
+                "app/src/a.d.mjs",
+                specifier,
+            )
+        };
+        assert_eq!(
+            resolve("outside"),
+            Some("node_modules/outside/index.json".to_owned())
+        );
+        assert_eq!(resolve("../../secret"), Some("secret.json".to_owned()));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_split_package() {
+        assert_eq!(split_package("a"), Some(("a", ".".to_owned())));
+        assert_eq!(split_package("a/b/c"), Some(("a", "./b/c".to_owned())));
+        assert_eq!(split_package("@s/a"), Some(("@s/a", ".".to_owned())));
+        assert_eq!(split_package("@s/a/b"), Some(("@s/a", "./b".to_owned())));
+        assert_eq!(split_package("@s"), None);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_map() {
+        let resolver = MapResolver::new()
+            .with("config", "/etc/app/config.json")
+            .with("lib/", "/vendor/lib/")
+            .with("lib/special/", "/special/");
+        let io = VirtualIo::new(&[]);
+        let resolve = |specifier| {
+            resolver.resolve(
+                GLOBAL,
+                &io,
+                &LoadPolicy::default(),
+                &mut LoadStats::default(),
+                "main.d.mjs",
+                specifier,
+            )
+        };
+        assert_eq!(resolve("config"), Some("/etc/app/config.json".to_owned()));
+        assert_eq!(resolve("lib/a.json"), Some("/vendor/lib/a.json".to_owned()));
+        assert_eq!(
+            resolve("lib/special/b.json"),
+            Some("/special/b.json".to_owned())
+        );
+        assert_eq!(resolve("./config"), None);
+    }
+}
@@ -1,16 +1,17 @@
 use crate::{
+    ast::eval::EvaluationError,
     big_numbers::big_int::{BigInt, Sign},
     common::default::default,
     js::{
         any::Any,
+        interner::Interner,
         js_bigint::{new_bigint, JsBigintRef},
         js_string::{new_string, JsStringRef},
-        null::Null,
     },
     mem::manager::{Dealloc, Manager},
-    tokenizer::JsonToken,
+    tokenizer::span::Span,
 };
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::BTreeMap;
 use std::fmt::Display;
 
 #[derive(Debug, Default, PartialEq)]
@@ -53,16 +54,64 @@ pub enum ParsingStatus {
     ArrayComma,
     ObjectBegin,
     ObjectKey,
+    /// After an identifier key, which can be a shorthand property like `{ a }`.
+    ObjectIdKey(String, Span),
     ObjectColon,
     ObjectValue,
     ObjectComma,
     ImportBegin,
     ImportValue,
     ImportEnd,
+    /// After a complete expression that a call or `=>` can extend.
+    Operand,
+    CallBegin,
+    CallValue,
+    CallComma,
+    GroupBegin,
+    GroupValue,
+    ParamsId,
+    ParamsComma,
+    ParamsEnd,
+    ArrowBody,
+    /// After `void`, which has to be followed by `0`.
+    Void,
+}
+
+impl ParsingStatus {
+    /// Describes the tokens accepted in the status, for error messages.
+    pub fn expected(&self, is_djs: bool) -> &'static [&'static str] {
+        match self {
+            ParsingStatus::Initial | ParsingStatus::ObjectColon | ParsingStatus::ArrowBody => {
+                &["value"]
+            }
+            ParsingStatus::ArrayBegin | ParsingStatus::ArrayComma => &["value", "`]`"],
+            ParsingStatus::ArrayValue => &["`,`", "`]`"],
+            ParsingStatus::ObjectBegin | ParsingStatus::ObjectComma if is_djs => {
+                &["string", "identifier", "`}`"]
+            }
+            ParsingStatus::ObjectBegin | ParsingStatus::ObjectComma => &["string", "`}`"],
+            ParsingStatus::ObjectKey => &["`:`"],
+            ParsingStatus::ObjectIdKey(..) => &["`:`", "`,`", "`}`"],
+            ParsingStatus::ObjectValue => &["`,`", "`}`"],
+            ParsingStatus::ImportBegin => &["`(`"],
+            ParsingStatus::ImportValue => &["string"],
+            ParsingStatus::ImportEnd => &["`)`"],
+            ParsingStatus::Operand => &["`(`"],
+            ParsingStatus::CallBegin | ParsingStatus::CallComma | ParsingStatus::GroupBegin => {
+                &["value", "`)`"]
+            }
+            ParsingStatus::CallValue | ParsingStatus::GroupValue | ParsingStatus::ParamsId => {
+                &["`,`", "`)`"]
+            }
+            ParsingStatus::ParamsComma => &["identifier", "`)`"],
+            ParsingStatus::ParamsEnd => &["`=>`"],
+            ParsingStatus::Void => &["`0`"],
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
-pub enum ParseError {
+pub enum ParseErrorKind {
     UnexpectedToken,
     UnexpectedEnd,
     WrongExportStatement,
@@ -70,29 +119,226 @@ pub enum ParseError {
     WrongRequireStatement,
     WrongImportStatement,
     CannotReadFile,
+    /// The module resolver doesn't find a module for an import path.
+    CannotResolveModule,
     CircularDependency,
     NewLineExpected,
+    /// The `LoadPolicy` root doesn't contain the module.
+    PathOutsideRoot,
+    /// The `LoadPolicy` doesn't allow the extension of the module.
+    ExtensionNotAllowed,
+    /// Loading the module exceeds the `LoadPolicy` maximum number of modules.
+    TooManyModules,
+    /// Loading the module exceeds the `LoadPolicy` maximum number of bytes.
+    TooManyBytes,
+    /// The module is imported deeper than the `LoadPolicy` maximum depth.
+    ImportTooDeep,
+    /// The module is valid but its value can't be computed, e.g. it exports a function.
+    CannotEvaluate(EvaluationError),
 }
 
-impl Display for ParseError {
+impl Display for ParseErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(match self {
-            ParseError::UnexpectedToken => "UnexpectedToken",
-            ParseError::UnexpectedEnd => "UnexpectedEnd",
-            ParseError::WrongExportStatement => "WrongExportStatement",
-            ParseError::WrongConstStatement => "WrongConstStatement",
-            ParseError::WrongRequireStatement => "WrongRequireStatement",
-            ParseError::WrongImportStatement => "WrongImportStatement",
-            ParseError::CannotReadFile => "CannotReadFile",
-            ParseError::CircularDependency => "CircularDependency",
-            ParseError::NewLineExpected => "NewLineExpected",
+            ParseErrorKind::UnexpectedToken => "UnexpectedToken",
+            ParseErrorKind::UnexpectedEnd => "UnexpectedEnd",
+            ParseErrorKind::WrongExportStatement => "WrongExportStatement",
+            ParseErrorKind::WrongConstStatement => "WrongConstStatement",
+            ParseErrorKind::WrongRequireStatement => "WrongRequireStatement",
+            ParseErrorKind::WrongImportStatement => "WrongImportStatement",
+            ParseErrorKind::CannotReadFile => "CannotReadFile",
+            ParseErrorKind::CannotResolveModule => "CannotResolveModule",
+            ParseErrorKind::CircularDependency => "CircularDependency",
+            ParseErrorKind::NewLineExpected => "NewLineExpected",
+            ParseErrorKind::PathOutsideRoot => "PathOutsideRoot",
+            ParseErrorKind::ExtensionNotAllowed => "ExtensionNotAllowed",
+            ParseErrorKind::TooManyModules => "TooManyModules",
+            ParseErrorKind::TooManyBytes => "TooManyBytes",
+            ParseErrorKind::ImportTooDeep => "ImportTooDeep",
+            ParseErrorKind::CannotEvaluate(_) => "CannotEvaluate",
         })
     }
 }
 
+/// A parse error with the location it was found at.
+///
+/// The analyzer provides the `kind` and the location; the file is attached once the error leaves
+/// the module, so errors coming from imported modules keep the location inside that module.
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// Path of the file that contains the error, empty when parsing raw tokens.
+    pub path: String,
+    pub span: Option<Span>,
+    /// Descriptions of tokens that would have been accepted in place of `token`.
+    pub expected: &'static [&'static str],
+    /// Description of the offending token.
+    pub token: Option<String>,
+    /// The source line `span` begins at, used to print a snippet.
+    pub source_line: String,
+    /// For `CircularDependency`, the imports that lead from the module imported again to the file
+    /// of the error, whose import closes the cycle.
+    pub import_cycle: Vec<ImportLocation>,
+}
+
+/// An import statement in a chain of imports.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportLocation {
+    /// Path of the importing file.
+    pub path: String,
+    pub span: Span,
+    pub source_line: String,
+}
+
+impl ImportLocation {
+    pub fn new(path: &str, span: Span, source: &str) -> Self {
+        ImportLocation {
+            path: path.to_owned(),
+            span,
+            source_line: source_line(source, &span),
+        }
+    }
+}
+
+/// Returns the line of `source` that `span` begins at.
+fn source_line(source: &str, span: &Span) -> String {
+    source
+        .split('\n')
+        .nth(span.begin.line)
+        .unwrap_or_default()
+        .trim_end_matches('\r')
+        .to_owned()
+}
+
+impl From<ParseErrorKind> for ParseError {
+    fn from(kind: ParseErrorKind) -> Self {
+        ParseError {
+            kind,
+            path: default(),
+            span: None,
+            expected: &[],
+            token: None,
+            source_line: default(),
+            import_cycle: default(),
+        }
+    }
+}
+
+impl ParseError {
+    /// Attaches the file path and the source line unless the error already belongs to a file.
+    pub fn with_source(self, path: &str, source: &str) -> Self {
+        if !self.path.is_empty() {
+            return self;
+        }
+        let source_line = match &self.span {
+            Some(span) => source_line(source, span),
+            None => default(),
+        };
+        ParseError {
+            path: path.to_owned(),
+            source_line,
+            ..self
+        }
+    }
+}
+
+/// Returns the prefix of `line` that spans the UTF-16 columns `[0, column)`.
+fn utf16_prefix(line: &str, column: usize) -> &str {
+    let mut utf16 = 0;
+    for (i, c) in line.char_indices() {
+        if utf16 >= column {
+            return &line[..i];
+        }
+        utf16 += c.len_utf16();
+    }
+    line
+}
+
+/// Writes ` --> path:line:column` and the snippet of `source_line` with `span` underlined.
+fn write_location(
+    f: &mut std::fmt::Formatter<'_>,
+    path: &str,
+    span: &Span,
+    source_line: &str,
+) -> std::fmt::Result {
+    write!(f, "\n --> {path}:{}", span.begin)?;
+    if source_line.is_empty() {
+        return Ok(());
+    }
+    let line = (span.begin.line + 1).to_string();
+    let gutter = " ".repeat(line.len());
+    let before = utf16_prefix(source_line, span.begin.column);
+    let underlined = if span.end.line == span.begin.line {
+        utf16_prefix(
+            &source_line[before.len()..],
+            span.end.column - span.begin.column,
+        )
+    } else {
+        &source_line[before.len()..]
+    };
+    let padding: String = before
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let carets = "^".repeat(underlined.chars().count().max(1));
+    write!(
+        f,
+        "\n{gutter} |\n{line} | {source_line}\n{gutter} | {padding}{carets}"
+    )
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if self.kind == ParseErrorKind::CircularDependency && !self.path.is_empty() {
+            // The module imported again is the first in the chain, or the file of the error if it
+            // imports itself.
+            let first = self.import_cycle.first().map_or(&self.path, |i| &i.path);
+            f.write_str(": ")?;
+            for i in &self.import_cycle {
+                write!(f, "{} -> ", i.path)?;
+            }
+            write!(f, "{} -> {first}", self.path)?;
+        }
+        if let Some(token) = &self.token {
+            write!(f, ": unexpected {token}")?;
+        }
+        if let Some((last, init)) = self.expected.split_last() {
+            f.write_str(", expected ")?;
+            if !init.is_empty() {
+                write!(f, "{} or ", init.join(", "))?;
+            }
+            f.write_str(last)?;
+        }
+        let Some(span) = &self.span else {
+            if !self.path.is_empty() {
+                write!(f, "\n --> {}", self.path)?;
+            }
+            return Ok(());
+        };
+        write_location(f, &self.path, span, &self.source_line)?;
+        for i in &self.import_cycle {
+            f.write_str("\nnote: imported here")?;
+            write_location(f, &i.path, &i.span, &i.source_line)?;
+        }
+        Ok(())
+    }
+}
+
+/// A module that is being loaded, in `ModuleCache::progress`.
+#[derive(Debug)]
+pub struct Loading {
+    pub path: String,
+    /// The import statement that loads the module, `None` for the module a parse starts with.
+    pub import: Option<ImportLocation>,
+}
+
 pub struct ModuleCache<D: Dealloc> {
     pub complete: BTreeMap<String, Any<D>>,
-    pub progress: BTreeSet<String>,
+    /// The stack of modules that are being loaded, each imported by the previous one.
+    pub progress: Vec<Loading>,
+    /// Shares equal strings between the modules of a parse session.
+    pub interner: Interner<D>,
 }
 
 impl<D: Dealloc> Default for ModuleCache<D> {
@@ -100,26 +346,11 @@ impl<D: Dealloc> Default for ModuleCache<D> {
         Self {
             complete: default(),
             progress: default(),
+            interner: default(),
         }
     }
 }
 
-pub enum JsonStackElement<D: Dealloc> {
-    Object(JsonStackObject<D>),
-    Array(Vec<Any<D>>),
-}
-
-pub struct JsonStackObject<D: Dealloc> {
-    pub map: BTreeMap<String, Any<D>>,
-    pub key: String,
-}
-
-pub enum JsonElement<D: Dealloc> {
-    None,
-    Stack(JsonStackElement<D>),
-    Any(Any<D>),
-}
-
 #[derive(Debug)]
 pub struct ParseResult<D: Dealloc> {
     pub data_type: DataType,
@@ -137,33 +368,3 @@ pub fn to_js_bigint<M: Manager>(manager: M, b: BigInt) -> JsBigintRef<M::Dealloc
     };
     new_bigint(manager, sign, b.value.value).to_ref()
 }
-
-fn try_id_to_any<M: Manager>(
-    s: &str,
-    _manager: M,
-    consts: &BTreeMap<String, Any<M::Dealloc>>,
-) -> Option<Any<M::Dealloc>> {
-    match s {
-        "null" => Some(Any::move_from(Null())),
-        "true" => Some(Any::move_from(true)),
-        "false" => Some(Any::move_from(false)),
-        s if consts.contains_key(s) => Some(consts.get(s).unwrap().clone()),
-        _ => None,
-    }
-}
-
-impl<D: Dealloc> JsonToken<D> {
-    pub fn try_to_any<M: Manager<Dealloc = D>>(
-        self,
-        manager: M,
-        consts: &BTreeMap<String, Any<M::Dealloc>>,
-    ) -> Option<Any<M::Dealloc>> {
-        match self {
-            JsonToken::Number(f) => Some(Any::move_from(f)),
-            JsonToken::String(s) => Some(Any::move_from(to_js_string(manager, s))),
-            JsonToken::Id(s) => try_id_to_any(&s, manager, consts),
-            JsonToken::BigInt(b) => Some(Any::move_from(b.to_ref())),
-            _ => None,
-        }
-    }
-}
@@ -0,0 +1,305 @@
+use core::fmt::{self, Write};
+
+use crate::common::default::default;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Indent {
+    /// No line breaks at all.
+    #[default]
+    None,
+    Spaces(u8),
+    Tab,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Newline {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+/// Defines how DJS writes object keys. JSON keys are always quoted.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum KeyQuotes {
+    #[default]
+    Always,
+    /// Keys that are identifiers are written without quotes, e.g. `{a:1}`.
+    AsNeeded,
+}
+
+/// The layout of `to_json` and `to_djs` output. The default is minified output without any
+/// whitespace.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FormatOptions {
+    /// The indentation of nested items. With an indentation, each item of an object or an array
+    /// is written on its own line.
+    pub indent: Indent,
+    pub newline: Newline,
+    /// Adds a space after `:` in objects and around `=` in DJS consts.
+    pub space_after_colon: bool,
+    /// Adds a space after `,` between items on one line.
+    pub space_after_comma: bool,
+    /// Arrays that fit in this width on one line, counting the indentation and the text before
+    /// them, e.g. `"key": `, aren't broken into lines. `0` breaks all non-empty arrays.
+    pub max_width: usize,
+    /// Adds `,` after the last item of a broken list. DJS only.
+    pub trailing_comma: bool,
+    /// DJS only.
+    pub key_quotes: KeyQuotes,
+}
+
+impl FormatOptions {
+    /// A `prettier`-like layout: two spaces indentation, spaces after `:` and `,`, and arrays up to
+    /// 80 characters wide on one line.
+    pub fn pretty() -> Self {
+        Self {
+            indent: Indent::Spaces(2),
+            space_after_colon: true,
+            space_after_comma: true,
+            max_width: 80,
+            ..default()
+        }
+    }
+
+    pub fn is_multiline(&self) -> bool {
+        self.indent != Indent::None
+    }
+
+    /// The same options without line breaks.
+    pub fn single_line(&self) -> Self {
+        Self {
+            indent: Indent::None,
+            ..*self
+        }
+    }
+
+    fn indent_width(&self) -> usize {
+        match self.indent {
+            Indent::None => 0,
+            Indent::Spaces(n) => n as usize,
+            Indent::Tab => 1,
+        }
+    }
+
+    /// Writes a line break and the indentation of `depth`.
+    pub fn write_line(&self, w: &mut (impl Write + ?Sized), depth: usize) -> fmt::Result {
+        w.write_str(match self.newline {
+            Newline::Lf => "\n",
+            Newline::CrLf => "\r\n",
+        })?;
+        for _ in 0..depth {
+            match self.indent {
+                Indent::None => {}
+                Indent::Spaces(n) => {
+                    for _ in 0..n {
+                        w.write_char(' ')?;
+                    }
+                }
+                Indent::Tab => w.write_char('\t')?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_colon(&self, w: &mut (impl Write + ?Sized)) -> fmt::Result {
+        w.write_char(':')?;
+        if self.space_after_colon {
+            w.write_char(' ')?;
+        }
+        Ok(())
+    }
+
+    /// Writes `=` of a const definition.
+    pub fn write_assign(&self, w: &mut (impl Write + ?Sized)) -> fmt::Result {
+        w.write_str(if self.space_after_colon { " = " } else { "=" })
+    }
+
+    /// Writes `items` with `f` between `open` and `close`, one item per line if the format is
+    /// multi-line. `trailing_comma` is used only in a multi-line list.
+    pub fn write_items<W: Write + ?Sized, I>(
+        &self,
+        w: &mut W,
+        depth: usize,
+        (open, close): (char, char),
+        trailing_comma: bool,
+        items: impl IntoIterator<Item = I>,
+        mut f: impl FnMut(&mut W, I) -> fmt::Result,
+    ) -> fmt::Result {
+        let multiline = self.is_multiline();
+        w.write_char(open)?;
+        let mut empty = true;
+        for i in items {
+            if !empty {
+                w.write_char(',')?;
+                if !multiline && self.space_after_comma {
+                    w.write_char(' ')?;
+                }
+            }
+            if multiline {
+                self.write_line(w, depth + 1)?;
+            }
+            f(w, i)?;
+            empty = false;
+        }
+        if multiline && !empty {
+            if trailing_comma {
+                w.write_char(',')?;
+            }
+            self.write_line(w, depth)?;
+        }
+        w.write_char(close)
+    }
+
+    /// Writes the output of `f` if the format is multi-line and the output fits in `max_width` on
+    /// one line at the indentation of `depth` after `column` characters, e.g. after `"key": `.
+    /// `f` should write with `single_line` options. Returns `false` without writing anything
+    /// otherwise.
+    pub fn write_collapsed<W: Write + ?Sized>(
+        &self,
+        w: &mut W,
+        depth: usize,
+        column: usize,
+        f: impl FnOnce(&mut Line) -> fmt::Result,
+    ) -> Result<bool, fmt::Error> {
+        let Some(limit) = self
+            .max_width
+            .checked_sub(depth * self.indent_width() + column)
+        else {
+            return Ok(false);
+        };
+        if !self.is_multiline() || limit == 0 {
+            return Ok(false);
+        }
+        let mut line = Line {
+            s: String::new(),
+            limit,
+        };
+        // An error can also mean that the line is too long. Real errors are reported when the
+        // caller writes the value again without collapsing it.
+        if f(&mut line).is_err() {
+            return Ok(false);
+        }
+        w.write_str(&line.s)?;
+        Ok(true)
+    }
+}
+
+/// Writes the output of `f` and returns its width, e.g. the `column` of a value after its key.
+pub fn write_prefix<W: Write + ?Sized>(
+    w: &mut W,
+    f: impl FnOnce(&mut String) -> fmt::Result,
+) -> Result<usize, fmt::Error> {
+    let mut prefix = String::new();
+    f(&mut prefix)?;
+    w.write_str(&prefix)?;
+    Ok(prefix.len())
+}
+
+/// A buffer for one line that fails as soon as the line is longer than its limit.
+pub struct Line {
+    s: String,
+    limit: usize,
+}
+
+impl Write for Line {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.s.len() + s.len() > self.limit {
+            return Err(fmt::Error);
+        }
+        self.s.push_str(s);
+        Ok(())
+    }
+}
+
+/// Returns true if `key` can be written without quotes, e.g. `a` or `$_0`.
+pub fn is_identifier(key: &[u16]) -> bool {
+    let id_char = |c: &u16, digits: bool| match char::from_u32(*c as u32) {
+        Some(c) => c.is_ascii_alphabetic() || c == '_' || c == '$' || digits && c.is_ascii_digit(),
+        None => false,
+    };
+    match key.split_first() {
+        Some((first, rest)) => id_char(first, false) && rest.iter().all(|c| id_char(c, true)),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::fmt::Write;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{is_identifier, write_prefix, FormatOptions};
+
+    fn id(s: &str) -> bool {
+        is_identifier(&s.encode_utf16().collect::<Vec<_>>())
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_is_identifier() {
+        assert!(id("a"));
+        assert!(id("$_0"));
+        assert!(id("null"));
+        assert!(!id(""));
+        assert!(!id("0a"));
+        assert!(!id("a-b"));
+        assert!(!id("é"));
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_write_prefix() {
+        let mut s = String::new();
+        let width = write_prefix(&mut s, |w| {
+            w.write_str("const a")?;
+            FormatOptions::pretty().write_assign(w)
+        });
+        assert_eq!(width, Ok(10));
+        assert_eq!(s, "const a = ");
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_write_collapsed() {
+        let format = FormatOptions {
+            max_width: 10,
+            ..FormatOptions::pretty()
+        };
+        let mut s = String::new();
+        assert_eq!(
+            format.write_collapsed(&mut s, 0, 0, |w| w.write_str("0123456789")),
+            Ok(true)
+        );
+        assert_eq!(
+            format.write_collapsed(&mut s, 1, 0, |w| w.write_str("01234567")),
+            Ok(true)
+        );
+        assert_eq!(
+            format.write_collapsed(&mut s, 1, 0, |w| w.write_str("012345678")),
+            Ok(false)
+        );
+        assert_eq!(
+            format.write_collapsed(&mut s, 5, 0, |w| w.write_str("")),
+            Ok(false)
+        );
+        assert_eq!(
+            format.write_collapsed(&mut s, 1, 3, |w| w.write_str("01234")),
+            Ok(true)
+        );
+        assert_eq!(
+            format.write_collapsed(&mut s, 1, 3, |w| w.write_str("012345")),
+            Ok(false)
+        );
+        assert_eq!(
+            format.write_collapsed(&mut s, 0, 11, |w| w.write_str("")),
+            Ok(false)
+        );
+        assert_eq!(s, "01234567890123456701234");
+        let single_line = format.single_line();
+        assert_eq!(
+            single_line.write_collapsed(&mut s, 0, 0, |w| w.write_str("")),
+            Ok(false)
+        );
+    }
+}
@@ -1,2 +1,6 @@
+pub mod format;
+mod names;
+mod structural;
 pub mod to_djs;
-pub mod to_json;
+pub mod to_json;
+pub mod to_module;
@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+
+/// Words that can't be const names in DJS: ECMAScript reserved words and the names DJS gives a
+/// meaning to.
+const RESERVED: &[&str] = &[
+    "Infinity",
+    "NaN",
+    "arguments",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "eval",
+    "export",
+    "exports",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "module",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "require",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+/// Makes an identifier from property path segments, e.g. `["users", "0", "address"]` gives
+/// `users_0_address`. Characters that can't be in an identifier are replaced by `_`.
+pub fn path_name(path: &[String]) -> String {
+    let mut name: String = path
+        .join("_")
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '$' => c,
+            _ => '_',
+        })
+        .collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    if RESERVED.binary_search(&name.as_str()).is_ok() {
+        name.push('_');
+    }
+    name
+}
+
+/// Gives unique names to consts.
+#[derive(Default)]
+pub struct Names {
+    used: HashSet<String>,
+}
+
+impl Names {
+    /// Returns `base`, or `base` with the smallest suffix `_2`, `_3`, ... that isn't used yet.
+    pub fn add(&mut self, base: String) -> String {
+        let mut name = base.clone();
+        let mut i = 2;
+        while self.used.contains(&name) {
+            name = format!("{base}_{i}");
+            i += 1;
+        }
+        self.used.insert(name.clone());
+        name
+    }
+
+    pub fn len(&self) -> usize {
+        self.used.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{path_name, Names, RESERVED};
+
+    fn name(path: &[&str]) -> String {
+        path_name(&path.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_path_name() {
+        assert!(RESERVED.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(name(&["users", "0", "address"]), "users_0_address");
+        assert_eq!(name(&["0"]), "_0");
+        assert_eq!(name(&[]), "_");
+        assert_eq!(name(&["a-b", "c d", "é"]), "a_b_c_d__");
+        assert_eq!(name(&["default"]), "default_");
+        assert_eq!(name(&["null"]), "null_");
+        assert_eq!(name(&["nullable"]), "nullable");
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_names() {
+        let mut names = Names::default();
+        assert_eq!(names.add("a".to_owned()), "a");
+        assert_eq!(names.add("a".to_owned()), "a_2");
+        assert_eq!(names.add("a_2".to_owned()), "a_2_2");
+        assert_eq!(names.add("a".to_owned()), "a_3");
+        assert_eq!(names.len(), 4);
+    }
+}
@@ -0,0 +1,159 @@
+use core::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+};
+use std::collections::{hash_map::DefaultHasher, HashMap};
+
+use crate::{
+    js::{any::Any, js_bigint::JsBigint, js_string::JsString, type_::Type},
+    mem::manager::Dealloc,
+};
+
+/// Maps objects and arrays to a representative of their value, so equal compounds in different
+/// blocks can be written once.
+///
+/// Each compound gets a Merkle-style hash: a hash of its keys and of the hashes of its items.
+/// Compounds with the same hash are compared item by item, with nested compounds compared by
+/// their representatives, so the comparison doesn't go deeper than one level.
+pub struct Canonical<D: Dealloc> {
+    /// Hashes of the visited compounds.
+    hashes: HashMap<Any<D>, u64>,
+    /// Representatives by hash. Different values can share a hash.
+    buckets: HashMap<u64, Vec<Any<D>>>,
+    representatives: HashMap<Any<D>, Any<D>>,
+}
+
+impl<D: Dealloc> Default for Canonical<D> {
+    fn default() -> Self {
+        Self {
+            hashes: HashMap::new(),
+            buckets: HashMap::new(),
+            representatives: HashMap::new(),
+        }
+    }
+}
+
+fn is_compound<D: Dealloc>(any: &Any<D>) -> bool {
+    matches!(any.get_type(), Type::Object | Type::Array)
+}
+
+impl<D: Dealloc> Canonical<D> {
+    /// Finds representatives for all compounds in the DAG referred by `any`.
+    pub fn new(any: &Any<D>) -> Self {
+        let mut result = Self::default();
+        result.hash(any);
+        result
+    }
+
+    /// The representative of `any` if it's a compound, or `any` itself.
+    pub fn get(&self, any: &Any<D>) -> Any<D> {
+        self.representatives.get(any).unwrap_or(any).clone()
+    }
+
+    fn hash(&mut self, any: &Any<D>) -> u64 {
+        if let Some(&h) = self.hashes.get(any) {
+            return h;
+        }
+        let mut hasher = DefaultHasher::new();
+        (any.get_type() as u8).hash(&mut hasher);
+        if let Some(properties) = any.properties() {
+            for (k, v) in properties {
+                k.items().hash(&mut hasher);
+                self.hash(v).hash(&mut hasher);
+            }
+        } else if let Some(items) = any.items() {
+            for i in items {
+                self.hash(i).hash(&mut hasher);
+            }
+        } else if let Ok(s) = any.try_ref::<JsString>() {
+            s.object().items().hash(&mut hasher);
+        } else if let Ok(b) = any.try_ref::<JsBigint>() {
+            b.object().header_len().hash(&mut hasher);
+            b.object().items().hash(&mut hasher);
+        } else {
+            // Numbers and simple values are compared by their bits.
+            any.hash(&mut hasher);
+            return hasher.finish();
+        }
+        let h = hasher.finish();
+        if is_compound(any) {
+            self.hashes.insert(any.clone(), h);
+            self.add(h, any);
+        }
+        h
+    }
+
+    fn add(&mut self, h: u64, any: &Any<D>) {
+        let found = self
+            .buckets
+            .get(&h)
+            .and_then(|bucket| bucket.iter().find(|r| self.shallow_eq(r, any)).cloned());
+        match found {
+            Some(r) => {
+                self.representatives.insert(any.clone(), r);
+            }
+            None => self.buckets.entry(h).or_default().push(any.clone()),
+        }
+    }
+
+    /// Compares items, given that nested compounds already have representatives.
+    fn item_eq(&self, a: &Any<D>, b: &Any<D>) -> bool {
+        if is_compound(a) {
+            return self.get(a) == self.get(b);
+        }
+        if let (Ok(a), Ok(b)) = (a.try_ref::<JsString>(), b.try_ref::<JsString>()) {
+            return a.object().items() == b.object().items();
+        }
+        if let (Ok(a), Ok(b)) = (a.try_ref::<JsBigint>(), b.try_ref::<JsBigint>()) {
+            return a.object().compare(b.object()) == Ordering::Equal;
+        }
+        a == b
+    }
+
+    fn shallow_eq(&self, a: &Any<D>, b: &Any<D>) -> bool {
+        if a.len() != b.len() {
+            return false;
+        }
+        if let (Some(a), Some(b)) = (a.properties(), b.properties()) {
+            return a
+                .zip(b)
+                .all(|((ka, va), (kb, vb))| ka.items() == kb.items() && self.item_eq(va, vb));
+        }
+        if let (Some(a), Some(b)) = (a.items(), b.items()) {
+            return a.zip(b).all(|(x, y)| self.item_eq(x, y));
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        js::{any::Any, any_cast::AnyCast, new::New},
+        mem::global::{Global, GLOBAL},
+    };
+
+    use super::Canonical;
+
+    fn array(items: impl IntoIterator<Item = Any<Global>>) -> Any<Global> {
+        GLOBAL.new_js_array(items.into_iter().collect::<Vec<_>>())
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_nested() {
+        let a = array([1.0.move_to_any(), array([array([2.0.move_to_any()])])]);
+        let b = array([1.0.move_to_any(), array([array([2.0.move_to_any()])])]);
+        let c = array([1.0.move_to_any(), array([array([3.0.move_to_any()])])]);
+        let root = array([a.clone(), b.clone(), c.clone()]);
+        let canonical = Canonical::new(&root);
+        assert_ne!(a, b);
+        assert_eq!(canonical.get(&a), canonical.get(&b));
+        assert_ne!(canonical.get(&a), canonical.get(&c));
+        assert_eq!(canonical.get(&root), root);
+        let x = 1.0.move_to_any();
+        assert_eq!(canonical.get(&x), x);
+    }
+}
@@ -1,16 +1,45 @@
 use crate::{
-    js::{any::Any, js_array::JsArrayRef, js_object::JsObjectRef, type_::Type},
+    common::default::default,
+    js::{any::Any, js_bigint::JsBigintRef, js_string::JsStringRef, type_::Type},
     mem::manager::Dealloc,
 };
 
 use core::{
-    fmt::{self},
+    fmt::{self, Write},
     result,
 };
 
 use std::collections::HashMap;
 
-use super::to_json::WriteJson;
+use super::{
+    format::{is_identifier, write_prefix, FormatOptions, KeyQuotes},
+    names::{path_name, Names},
+    structural::Canonical,
+    to_json::WriteJson,
+};
+
+/// Defines how `to_djs` names consts.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ConstNames {
+    /// `_0`, `_1`, ... in the order of definition.
+    #[default]
+    Numbered,
+    /// Names from the property path where a value is first seen, e.g. `users_0_address`.
+    Path,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DjsOptions {
+    /// Strings and bigints that occur more than once and whose literals are at least this long
+    /// are written once, as consts. `None` writes all of them in place.
+    pub value_consts: Option<usize>,
+    /// Merges objects and arrays that are equal by value, e.g. parsed from different places, so
+    /// each distinct one is written once. Otherwise only the blocks that are referred more than
+    /// once become consts.
+    pub structural: bool,
+    pub const_names: ConstNames,
+    pub format: FormatOptions,
+}
 
 /// `Seen` is a bool-like enumeration to represent a "seen" status of a js compound (an object or
 /// an array) visited by `ConstTracker`. In case of `Seen::Once`, the compound was visited just once
@@ -22,21 +51,42 @@ enum Seen {
     Repeatedly,
 }
 
-/// ConstTracker holds references to js compounds (objects or arrays) in two sets:
-/// `visited_once` refers to compounds that we've seen just once so far;
-/// `visited_repeatedly` refers to compounds that we've seen more than once.
-/// When djs tracking pass is done, `visited_repeatedly` refers to compounds that will be written
-/// out via const definitions.
-/// Note that we use one ConstTracker for js objects and another for js arrays, keeping them
-/// separate - to reduce set sizes and save on operations.
+/// ConstTracker collects what the djs tracking pass finds in a value: the js compounds (objects
+/// and arrays) and, with `DjsOptions::value_consts`, the long string and bigint literals.
+/// When the pass is done, the entries that are `Seen::Repeatedly` are written out via const
+/// definitions.
 struct ConstTracker<D: Dealloc> {
+    /// The `Seen` status of every compound visited so far, keyed by its canonical reference.
     visited: HashMap<Any<D>, Seen>,
+    /// Compounds in the order they were first seen in a depth-first traversal, so consts are
+    /// written in the same order every time.
+    order: Vec<Any<D>>,
+    /// Literals of strings and bigints with `DjsOptions::value_consts`, in the order they were
+    /// first seen, with their names for `ConstNames::Path`.
+    values: Vec<(String, Seen, Option<String>)>,
+    value_positions: HashMap<String, usize>,
+    min_value_len: Option<usize>,
+    canonical: Canonical<D>,
+    /// The property path of the current value for `ConstNames::Path`, `None` otherwise.
+    path: Option<Vec<String>>,
+    /// Names for `ConstNames::Path` from the paths where the compounds were first seen.
+    path_names: HashMap<Any<D>, String>,
+}
+
+/// The DJS literal of a string or a bigint.
+fn literal<D: Dealloc>(any: &Any<D>) -> result::Result<String, fmt::Error> {
+    let mut s = String::new();
+    match any.get_type() {
+        Type::Bigint => s.write_js_bigint(&any.clone().try_move::<JsBigintRef<D>>().unwrap())?,
+        _ => s.write_js_string(&any.clone().try_move::<JsStringRef<D>>().unwrap())?,
+    }
+    Ok(s)
 }
 
 impl<D: Dealloc> ConstTracker<D> {
-    /// Returns true if `any` was visited before; updates the `const_tracker` set, tracking whether
-    /// `any` was visited just once (it's in `const_tracker.visited_once`) or more than once (it's
-    /// in `visited_repeatedly` in this case since we are up to writing it out as a const).
+    /// Returns true if `any` was visited before. Updates `visited`: a compound seen for the first
+    /// time becomes `Seen::Once` and is appended to `order`, a compound seen again becomes
+    /// `Seen::Repeatedly` since we are up to writing it out as a const.
     fn is_visited(&mut self, any: &Any<D>) -> bool {
         let optional_seen = self.visited.get_mut(any);
         if let Some(seen) = optional_seen {
@@ -44,44 +94,135 @@ impl<D: Dealloc> ConstTracker<D> {
             true
         } else {
             self.visited.insert(any.clone(), Seen::Once);
+            self.order.push(any.clone());
+            if let Some(path) = &self.path {
+                self.path_names.insert(any.clone(), path_name(path));
+            }
             false
         }
     }
 
+    /// Counts an occurrence of a string or a bigint literal that is long enough to be a const.
+    fn track_value(&mut self, any: &Any<D>) -> fmt::Result {
+        let Some(min_len) = self.min_value_len else {
+            return Ok(());
+        };
+        let literal = literal(any)?;
+        if literal.len() < min_len {
+            return Ok(());
+        }
+        match self.value_positions.get(&literal) {
+            Some(&i) => self.values[i].1 = Seen::Repeatedly,
+            None => {
+                self.value_positions
+                    .insert(literal.clone(), self.values.len());
+                let name = self.path.as_deref().map(path_name);
+                self.values.push((literal, Seen::Once, name));
+            }
+        }
+        Ok(())
+    }
+
     /// Traverse a DAG referred by `any` (of any js type), tracking objects and arrays, including
     /// `any` itself.
     fn track_consts_for_any(&mut self, any: &Any<D>) -> fmt::Result {
         match any.get_type() {
             Type::Array | Type::Object => {
-                if !self.is_visited(any) {
-                    any.for_each::<fmt::Error>(|_k, v| self.track_consts_for_any(v))?;
+                let any = self.canonical.get(any);
+                if !self.is_visited(&any) {
+                    any.for_each::<fmt::Error>(|k, v| {
+                        if let Some(path) = &mut self.path {
+                            path.push(segment(k));
+                        }
+                        let result = self.track_consts_for_any(v);
+                        if let Some(path) = &mut self.path {
+                            path.pop();
+                        }
+                        result
+                    })?;
                 }
                 Ok(())
             }
+            Type::String | Type::Bigint => self.track_value(any),
             _ => Ok(()),
         }
     }
 }
 
+/// A path segment for a key of an object or an index of an array.
+fn segment<D: Dealloc>(k: Any<D>) -> String {
+    match k.clone().try_move::<JsStringRef<D>>() {
+        Ok(s) => String::from_utf16_lossy(s.items()),
+        Err(_) => (k.try_move::<f64>().unwrap() as usize).to_string(),
+    }
+}
+
+/// Names of the consts written so far.
+struct ConstRefs<D: Dealloc> {
+    compounds: HashMap<Any<D>, String>,
+    /// Strings and bigints by their literals.
+    values: HashMap<String, String>,
+    canonical: Canonical<D>,
+    names: Names,
+    path_names: HashMap<Any<D>, String>,
+}
+
+impl<D: Dealloc> ConstRefs<D> {
+    /// Makes a unique name for a new const. `path_name` is used with `ConstNames::Path`.
+    fn new_name(&mut self, path_name: Option<String>) -> String {
+        let base = path_name.unwrap_or_else(|| format!("_{}", self.names.len()));
+        self.names.add(base)
+    }
+
+    /// The name of the const with the value `any`, if there is one.
+    fn name(&self, any: &Any<D>) -> result::Result<Option<String>, fmt::Error> {
+        Ok(match any.get_type() {
+            Type::Array | Type::Object => self.compounds.get(&self.canonical.get(any)).cloned(),
+            Type::String | Type::Bigint if !self.values.is_empty() => {
+                self.values.get(&literal(any)?).cloned()
+            }
+            _ => None,
+        })
+    }
+}
+
 /// Writes a const definition for a compound (an array or an object).
 fn write_compound_const<D: Dealloc>(
     write_json: &mut (impl WriteJson + ?Sized),
     any: &Any<D>,
     to_be_consts: &mut HashMap<Any<D>, Seen>,
-    const_refs: &mut HashMap<Any<D>, usize>,
+    const_refs: &mut ConstRefs<D>,
+    format: &FormatOptions,
 ) -> fmt::Result {
-    any.for_each(|_k, v| write_consts_and_any(write_json, v, to_be_consts, const_refs))?;
+    any.for_each(|_k, v| write_consts_and_any(write_json, v, to_be_consts, const_refs, format))?;
     if to_be_consts.remove(any).is_some() {
-        let id = const_refs.len();
-        write_json.write_str("const _")?;
-        write_json.write_str(id.to_string().as_str())?;
-        write_json.write_char('=')?;
-        write_with_const_refs(write_json, any.clone(), const_refs)?;
-        const_refs.insert(any.clone(), id);
-        write_json.write_char(';')
-    } else {
-        fmt::Result::Ok(())
+        let name = const_refs.new_name(const_refs.path_names.get(any).cloned());
+        write_const(write_json, &name, format, |w, column| {
+            write_with_const_refs(w, any.clone(), const_refs, format, 0, column)
+        })?;
+        const_refs.compounds.insert(any.clone(), name);
     }
+    fmt::Result::Ok(())
+}
+
+/// Writes the definition of the const `name` with the value written by `f` at the given column.
+fn write_const<W: WriteJson + ?Sized>(
+    write_json: &mut W,
+    name: &str,
+    format: &FormatOptions,
+    f: impl FnOnce(&mut W, usize) -> fmt::Result,
+) -> fmt::Result {
+    let column = write_prefix(write_json, |w| {
+        w.write_str("const ")?;
+        w.write_str(name)?;
+        format.write_assign(w)
+    })?;
+    f(write_json, column)?;
+    write_json.write_char(';')?;
+    if format.is_multiline() {
+        format.write_line(write_json, 0)?;
+    }
+    fmt::Result::Ok(())
 }
 
 /// Writes a const js entity of any type (skipping over types other than object, array),
@@ -91,116 +232,261 @@ fn write_consts_and_any<D: Dealloc>(
     write_json: &mut (impl WriteJson + ?Sized),
     any: &Any<D>,
     to_be_consts: &mut HashMap<Any<D>, Seen>,
-    const_refs: &mut HashMap<Any<D>, usize>,
+    const_refs: &mut ConstRefs<D>,
+    format: &FormatOptions,
 ) -> fmt::Result {
     match any.get_type() {
         Type::Array | Type::Object => {
-            write_compound_const(write_json, any, to_be_consts, const_refs)?;
+            let any = const_refs.canonical.get(any);
+            write_compound_const(write_json, &any, to_be_consts, const_refs, format)?;
         }
         _ => {}
     }
     fmt::Result::Ok(())
 }
 
-/// Peeks one value from a hash map. This helper resolves a borrowing issue in write_consts - that
-/// function needs to iterate through a hash map while mutating that hash map.
-fn peek<D: Dealloc>(hash_map: &HashMap<Any<D>, Seen>) -> Option<Any<D>> {
-    Some(hash_map.iter().next()?.0.clone())
-}
-
-/// Writes const definitions for objects, arrays in the right order (with no forward references).
+/// Writes const definitions for repeated strings and bigints first, since they have no
+/// dependencies, and then for objects, arrays in the right order (with no forward references).
 fn write_consts<D: Dealloc>(
     write_json: &mut (impl WriteJson + ?Sized),
+    values: Vec<(String, Seen, Option<String>)>,
+    order: Vec<Any<D>>,
     to_be_consts: &mut HashMap<Any<D>, Seen>,
-    const_refs: &mut HashMap<Any<D>, usize>,
+    const_refs: &mut ConstRefs<D>,
+    format: &FormatOptions,
 ) -> fmt::Result {
-    while let Some(any) = peek(to_be_consts) {
-        write_consts_and_any(write_json, &any, to_be_consts, const_refs)?;
+    for (literal, _, path_name) in values
+        .into_iter()
+        .filter(|(_, seen, _)| *seen == Seen::Repeatedly)
+    {
+        let name = const_refs.new_name(path_name);
+        write_const(write_json, &name, format, |w, _| w.write_str(&literal))?;
+        const_refs.values.insert(literal, name);
+    }
+    for any in order {
+        if to_be_consts.is_empty() {
+            break;
+        }
+        write_consts_and_any(write_json, &any, to_be_consts, const_refs, format)?;
     }
     fmt::Result::Ok(())
 }
 
-/// Writes `any` using const references.
+/// Writes a number as a DJS expression. Unlike JSON, DJS can represent `-0`, `NaN` and infinities.
+fn write_djs_number(write_json: &mut (impl WriteJson + ?Sized), n: f64) -> fmt::Result {
+    if n == 0.0 && n.is_sign_negative() {
+        write_json.write_str("-0")
+    } else {
+        write_json.write_number(n)
+    }
+}
+
+/// Writes an object key, without quotes if `format` allows it.
+pub(super) fn write_key(
+    write_json: &mut (impl WriteJson + ?Sized),
+    k: &JsStringRef<impl Dealloc>,
+    format: &FormatOptions,
+) -> fmt::Result {
+    if format.key_quotes == KeyQuotes::AsNeeded && is_identifier(k.items()) {
+        for &c in k.items() {
+            write_json.write_char(c as u8 as char)?;
+        }
+        return Ok(());
+    }
+    write_json.write_js_string(k)
+}
+
+/// Writes `any` nested at `depth` after `column` characters on its line using const references.
 fn write_with_const_refs<D: Dealloc>(
     write_json: &mut (impl WriteJson + ?Sized),
     any: Any<D>,
-    const_refs: &HashMap<Any<D>, usize>,
+    const_refs: &ConstRefs<D>,
+    format: &FormatOptions,
+    depth: usize,
+    column: usize,
 ) -> fmt::Result {
+    if let Some(name) = const_refs.name(&any)? {
+        return write_json.write_str(&name);
+    }
+    let trailing_comma = format.trailing_comma;
     match any.get_type() {
         Type::Object => {
-            if let Some(n) = const_refs.get(&any) {
-                write_json.write_str("_")?;
-                write_json.write_str(n.to_string().as_str())
-            } else {
-                write_json.write_list(
-                    '{',
-                    '}',
-                    any.try_move::<JsObjectRef<D>>().unwrap(),
-                    |w, (k, v)| {
-                        w.write_js_string(k)?;
-                        w.write_char(':')?;
-                        write_with_const_refs(w, v.clone(), const_refs)
-                    },
-                )
-            }
+            format.write_items(
+                write_json,
+                depth,
+                ('{', '}'),
+                trailing_comma,
+                any.properties().unwrap(),
+                |w, (k, v)| {
+                    if let Some(name) = const_refs.name(v)? {
+                        // A shorthand property, e.g. `{_0}` instead of `{"_0":_0}`.
+                        if k.items().iter().copied().eq(name.encode_utf16()) {
+                            return w.write_str(&name);
+                        }
+                    }
+                    let column = write_prefix(w, |w| {
+                        write_key(w, k, format)?;
+                        format.write_colon(w)
+                    })?;
+                    write_with_const_refs(w, v.clone(), const_refs, format, depth + 1, column)
+                },
+            )
         }
         Type::Array => {
-            if let Some(n) = const_refs.get(&any) {
-                write_json.write_str("_")?;
-                write_json.write_str(n.to_string().as_str())
-            } else {
-                write_json.write_list(
-                    '[',
-                    ']',
-                    any.try_move::<JsArrayRef<D>>().unwrap(),
-                    |w, i| write_with_const_refs(w, i.clone(), const_refs),
-                )
+            let single_line = format.single_line();
+            let collapsed = format.write_collapsed(write_json, depth, column, |w| {
+                write_with_const_refs(w, any.clone(), const_refs, &single_line, depth, column)
+            })?;
+            if collapsed {
+                return Ok(());
             }
+            format.write_items(
+                write_json,
+                depth,
+                ('[', ']'),
+                trailing_comma,
+                any.items().unwrap(),
+                |w, i| write_with_const_refs(w, i.clone(), const_refs, format, depth + 1, 0),
+            )
         }
+        Type::Number => write_djs_number(write_json, any.try_move().unwrap()),
+        Type::Bigint => write_json.write_js_bigint(&any.try_move::<JsBigintRef<D>>().unwrap()),
+        // DJS is data only, so a function can't be written.
+        Type::Function => Err(fmt::Error),
+        Type::Undefined => write_json.write_str("undefined"),
         _ => write_json.write_json(any),
     }
 }
 
+/// Writes `any` nested at `depth` after `column` characters in place, without consts.
+pub(super) fn write_djs_value<D: Dealloc>(
+    write_json: &mut (impl WriteJson + ?Sized),
+    any: Any<D>,
+    format: &FormatOptions,
+    depth: usize,
+    column: usize,
+) -> fmt::Result {
+    let const_refs = ConstRefs {
+        compounds: HashMap::new(),
+        values: HashMap::new(),
+        canonical: default(),
+        names: default(),
+        path_names: HashMap::new(),
+    };
+    write_with_const_refs(write_json, any, &const_refs, format, depth, column)
+}
+
 pub trait WriteDjs: WriteJson {
     /// Writes a DAG referred by `any` with const definitions for objects, arrays that are referred
     /// multiple times.
     fn write_djs<D: Dealloc>(&mut self, any: Any<D>, common_js: bool) -> fmt::Result {
-        let mut const_refs = HashMap::<Any<D>, usize>::new();
+        self.write_djs_with_options(any, common_js, &default())
+    }
+
+    fn write_djs_with_options<D: Dealloc>(
+        &mut self,
+        any: Any<D>,
+        common_js: bool,
+        options: &DjsOptions,
+    ) -> fmt::Result {
+        let canonical = match options.structural {
+            true => Canonical::new(&any),
+            false => default(),
+        };
         let mut const_tracker = ConstTracker {
             visited: HashMap::new(),
+            order: Vec::new(),
+            values: Vec::new(),
+            value_positions: HashMap::new(),
+            min_value_len: options.value_consts,
+            canonical,
+            path: match options.const_names {
+                ConstNames::Numbered => None,
+                ConstNames::Path => Some(Vec::new()),
+            },
+            path_names: HashMap::new(),
         };
         const_tracker.track_consts_for_any(&any)?;
+        let mut const_refs = ConstRefs {
+            compounds: HashMap::new(),
+            values: HashMap::new(),
+            canonical: const_tracker.canonical,
+            names: default(),
+            path_names: const_tracker.path_names,
+        };
         const_tracker
             .visited
             .retain(|_, seen| *seen == Seen::Repeatedly);
-        write_consts(self, &mut const_tracker.visited, &mut const_refs)?;
-        if common_js {
-            self.write_str("module.exports=")?;
-        } else {
-            self.write_str("export default ")?;
-        }
-        write_with_const_refs(self, any, &const_refs)
+        let format = &options.format;
+        write_consts(
+            self,
+            const_tracker.values,
+            const_tracker.order,
+            &mut const_tracker.visited,
+            &mut const_refs,
+            format,
+        )?;
+        let column = write_prefix(self, |w| {
+            if common_js {
+                w.write_str("module.exports")?;
+                format.write_assign(w)
+            } else {
+                w.write_str("export default ")
+            }
+        })?;
+        write_with_const_refs(self, any, &const_refs, format, 0, column)
     }
 }
 
 impl<T: WriteJson> WriteDjs for T {}
 
 pub fn to_djs(any: Any<impl Dealloc>, common_js: bool) -> result::Result<String, fmt::Error> {
+    to_djs_with_options(any, common_js, &default())
+}
+
+pub fn to_djs_with_options(
+    any: Any<impl Dealloc>,
+    common_js: bool,
+    options: &DjsOptions,
+) -> result::Result<String, fmt::Error> {
     let mut s = String::default();
-    s.write_djs(any, common_js)?;
+    s.write_djs_with_options(any, common_js, options)?;
     Ok(s)
 }
 
 #[cfg(test)]
 mod test {
+    use io_test::VirtualIo;
     use wasm_bindgen_test::wasm_bindgen_test;
 
     use crate::{
-        js::{any::Any, any_cast::AnyCast, js_string::new_string, new::New, null::Null},
+        common::default::default,
+        js::{
+            any::Any, any_cast::AnyCast, js_array::JsArrayRef, js_string::new_string, new::New,
+            null::Null,
+        },
         mem::global::{Global, GLOBAL},
-        serializer::to_djs::WriteDjs,
+        parser::parser::{parse_with_tokens, Context},
+        serializer::{
+            to_djs::WriteDjs,
+            to_json::{to_json, WriteJson},
+        },
+        tokenizer::tokenize,
     };
 
+    use super::{to_djs, to_djs_with_options, ConstNames, DjsOptions};
+    use crate::serializer::format::{FormatOptions, KeyQuotes};
+
+    fn parse(s: &str) -> Any<Global> {
+        let io = VirtualIo::new(&[]);
+        let tokens = tokenize(GLOBAL, s.to_owned());
+        let mut mc = default();
+        let mut context = Context::new(GLOBAL, &io, default(), &mut mc);
+        parse_with_tokens(&mut context, tokens.into_iter())
+            .unwrap()
+            .any
+    }
+
     #[test]
     #[wasm_bindgen_test]
     fn test() {
@@ -228,4 +514,256 @@ mod test {
             r#"const _0={"a\\b\"\u001F":2};const _1=[1,true,null,[],"",_0];export default [_1,_1,_0]"#
         );
     }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_bigint_round_trip() {
+        let io = VirtualIo::new(&[]);
+        for s in [
+            "0n",
+            "123n",
+            "-123n",
+            "18446744073709551615n",
+            "-18446744073709551616n",
+            "123456789012345678901234567890123456789012345678901234567890n",
+        ] {
+            let tokens = tokenize(GLOBAL, format!("export default [{s}]"));
+            let mut mc = default();
+            let mut context = Context::new(GLOBAL, &io, default(), &mut mc);
+            let any = parse_with_tokens(&mut context, tokens.into_iter())
+                .unwrap()
+                .any;
+            let mut djs = String::new();
+            djs.write_djs(any.clone(), false).unwrap();
+            assert_eq!(djs, format!("export default [{s}]"));
+            let mut json = String::new();
+            assert!(json.write_json(any).is_err());
+        }
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_undefined_round_trip() {
+        let io = VirtualIo::new(&[]);
+        let tokens = tokenize(
+            GLOBAL,
+            "export default [undefined, {a: void 0, b: 1}]".to_owned(),
+        );
+        let mut mc = default();
+        let mut context = Context::new(GLOBAL, &io, default(), &mut mc);
+        let any = parse_with_tokens(&mut context, tokens.into_iter())
+            .unwrap()
+            .any;
+        let mut djs = String::new();
+        djs.write_djs(any.clone(), false).unwrap();
+        assert_eq!(djs, r#"export default [undefined,{"a":undefined,"b":1}]"#);
+        let mut json = String::new();
+        json.write_json(any).unwrap();
+        assert_eq!(json, r#"[null,{"b":1}]"#);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_number() {
+        let a = GLOBAL.new_js_array([
+            (-0.0).move_to_any(),
+            1e21.move_to_any(),
+            f64::NAN.move_to_any(),
+            f64::INFINITY.move_to_any(),
+            f64::NEG_INFINITY.move_to_any(),
+        ]);
+        let mut s = String::new();
+        s.write_djs(a, true).unwrap();
+        assert_eq!(s, "module.exports=[-0,1e+21,NaN,Infinity,-Infinity]");
+        // The numbers read back the same.
+        let a = parse(&s).try_move::<JsArrayRef<Global>>().unwrap();
+        let n: Vec<f64> = a
+            .items()
+            .iter()
+            .map(|i| i.clone().try_move().unwrap())
+            .collect();
+        assert!(n[0] == 0.0 && n[0].is_sign_negative());
+        assert_eq!(n[1], 1e21);
+        assert!(n[2].is_nan());
+        assert_eq!(n[3..], [f64::INFINITY, f64::NEG_INFINITY]);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_value_consts() {
+        let any = parse(
+            r#"export default ["Hello world!", {a: "Hello world!", b: "Hi", c: "Hi"}, 12345678901234567890n, 12345678901234567890n]"#,
+        );
+        assert_eq!(
+            to_djs(any.clone(), false).unwrap(),
+            r#"export default ["Hello world!",{"a":"Hello world!","b":"Hi","c":"Hi"},12345678901234567890n,12345678901234567890n]"#
+        );
+        let options = DjsOptions {
+            value_consts: Some(5),
+            ..default()
+        };
+        let djs = to_djs_with_options(any.clone(), false, &options).unwrap();
+        assert_eq!(
+            djs,
+            r#"const _0="Hello world!";const _1=12345678901234567890n;export default [_0,{"a":_0,"b":"Hi","c":"Hi"},_1,_1]"#
+        );
+        assert_eq!(
+            to_djs(parse(&djs), false).unwrap(),
+            to_djs(any, false).unwrap()
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_shorthand() {
+        let any = parse("const x = \"value\"\nexport default [{_0: x, y: x}, x]");
+        let options = DjsOptions {
+            value_consts: Some(0),
+            ..default()
+        };
+        let djs = to_djs_with_options(any, true, &options).unwrap();
+        assert_eq!(djs, r#"const _0="value";module.exports=[{_0,"y":_0},_0]"#);
+        let any = parse(&djs);
+        assert_eq!(
+            to_djs(any, true).unwrap(),
+            r#"module.exports=[{"_0":"value","y":"value"},"value"]"#
+        );
+
+        let any = parse("const a = [1]\nexport default { a, _0: a, b: a }");
+        assert_eq!(
+            to_djs(any, false).unwrap(),
+            r#"const _0=[1];export default {"a":_0,_0,"b":_0}"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_structural() {
+        let any =
+            parse(r#"export default [{a: [1, 2]}, {a: [1, 2]}, [1, 2], {a: [1, 3]}, ["1"], [1]]"#);
+        assert_eq!(
+            to_djs(any.clone(), false).unwrap(),
+            r#"export default [{"a":[1,2]},{"a":[1,2]},[1,2],{"a":[1,3]},["1"],[1]]"#
+        );
+        let options = DjsOptions {
+            structural: true,
+            ..default()
+        };
+        let djs = to_djs_with_options(any.clone(), false, &options).unwrap();
+        assert_eq!(
+            djs,
+            r#"const _0=[1,2];const _1={"a":_0};export default [_1,_1,_0,{"a":[1,3]},["1"],[1]]"#
+        );
+        // The consts make the parsed blocks shared, so the output is the same without the option.
+        assert_eq!(to_djs(parse(&djs), false).unwrap(), djs);
+        assert_eq!(to_json(parse(&djs)), to_json(any));
+
+        let any = parse(
+            r#"export default [{"s": "x", "b": 1n}, {"s": "x", "b": 1n}, {"b": 1n, "s": "x"}]"#,
+        );
+        let djs = to_djs_with_options(any, true, &options).unwrap();
+        assert_eq!(
+            djs,
+            r#"const _0={"s":"x","b":1n};module.exports=[_0,_0,{"b":1n,"s":"x"}]"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_pretty() {
+        let any = parse(
+            "const a = [1, 2]\nexport default { a, \"b-c\": [a, a], d: { \"1\": [] }, e: \"Hello world!\", f: \"Hello world!\" }",
+        );
+        let options = DjsOptions {
+            value_consts: Some(5),
+            format: FormatOptions {
+                trailing_comma: true,
+                key_quotes: KeyQuotes::AsNeeded,
+                ..FormatOptions::pretty()
+            },
+            ..default()
+        };
+        let djs = to_djs_with_options(any.clone(), false, &options).unwrap();
+        assert_eq!(
+            djs,
+            r#"const _0 = "Hello world!";
+const _1 = [1, 2];
+export default {
+  a: _1,
+  "b-c": [_1, _1],
+  d: {
+    "1": [],
+  },
+  e: _0,
+  f: _0,
+}"#
+        );
+        assert_eq!(
+            to_djs(parse(&djs), false).unwrap(),
+            to_djs(any.clone(), false).unwrap()
+        );
+
+        let options = DjsOptions {
+            format: FormatOptions {
+                space_after_colon: true,
+                ..default()
+            },
+            ..default()
+        };
+        assert_eq!(
+            to_djs_with_options(any, true, &options).unwrap(),
+            r#"const _0 = [1,2];module.exports = {"a": _0,"b-c": [_0,_0],"d": {"1": []},"e": "Hello world!","f": "Hello world!"}"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_const_order() {
+        let items: Vec<_> = (0..20)
+            .map(|i| GLOBAL.new_js_array([(i as f64).move_to_any()]))
+            .collect();
+        let a = GLOBAL.new_js_array(
+            items
+                .iter()
+                .chain(items.iter().rev())
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+        let consts: String = (0..20).map(|i| format!("const _{i}=[{i}];")).collect();
+        let refs: Vec<_> = (0..20)
+            .chain((0..20).rev())
+            .map(|i| format!("_{i}"))
+            .collect();
+        assert_eq!(
+            to_djs(a, false).unwrap(),
+            format!("{consts}export default [{}]", refs.join(","))
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_path_names() {
+        let any = parse(
+            "const address = { city: \"Paris\" }\n\
+             const list = [1]\n\
+             const x = [2]\n\
+             const y = [3]\n\
+             export default { users: [{ address, name: \"Hello world!\" }, { address, name: \"Hello world!\" }], list, copy: list, \"a-b\": x, a_b: y, default: [x, y] }",
+        );
+        let options = DjsOptions {
+            value_consts: Some(5),
+            const_names: ConstNames::Path,
+            ..default()
+        };
+        assert_eq!(
+            to_djs_with_options(any.clone(), false, &options).unwrap(),
+            r#"const users_0_name="Hello world!";const users_0_address={"city":"Paris"};const list=[1];const a_b=[2];const a_b_2=[3];export default {"users":[{"address":users_0_address,"name":users_0_name},{"address":users_0_address,"name":users_0_name}],list,"copy":list,"a-b":a_b,"a_b":a_b_2,"default":[a_b,a_b_2]}"#
+        );
+
+        let any = parse("const a = [1]\nexport default { default: a, null: [a, a] }");
+        assert_eq!(
+            to_djs_with_options(any, false, &options).unwrap(),
+            r#"const default_=[1];export default {"default":default_,"null":[default_,default_]}"#
+        );
+    }
 }
@@ -1,7 +1,12 @@
 use crate::{
+    common::default::default,
     js::{
         any::Any,
+        js_bigint::{to_decimal_string, JsBigintRef},
+        js_object::Property,
         js_string::JsStringRef,
+        persistent::Persistent,
+        undefined::Undefined,
         visitor::{to_visitor, Visitor},
     },
     mem::{
@@ -16,9 +21,45 @@ use core::{
     result,
 };
 
+use super::format::{write_prefix, FormatOptions};
+
 const ESCAPE_B: u8 = 0x08;
 const ESCAPE_F: u8 = 0x0C;
 
+/// Defines how `write_json` writes a BigInt value, since JSON has no BigInt literals.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum BigintPolicy {
+    /// Fails with `fmt::Error`, the same way as `JSON.stringify` throws a `TypeError`.
+    #[default]
+    Error,
+    /// Writes decimal digits as a JSON string, e.g. `"123"`.
+    String,
+    /// Writes decimal digits as a JSON number, e.g. `123`. Precision can be lost by a reader.
+    Digits,
+}
+
+/// Defines how `write_json` writes `NaN`, `Infinity` and `-Infinity`, since JSON has no literals
+/// for them.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum NonFinitePolicy {
+    /// Writes `null`, the same way as `JSON.stringify` does.
+    #[default]
+    Null,
+    /// Fails with `fmt::Error`.
+    Error,
+    /// Writes a JSON string, e.g. `"NaN"` or `"-Infinity"`.
+    String,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JsonOptions {
+    pub bigint: BigintPolicy,
+    pub non_finite: NonFinitePolicy,
+    /// `FormatOptions::trailing_comma` and `FormatOptions::key_quotes` are ignored, since JSON
+    /// doesn't allow them.
+    pub format: FormatOptions,
+}
+
 pub trait WriteJson: Write {
     fn write_u4_hex(&mut self, v: u16) -> fmt::Result {
         self.write_char(b"0123456789ABCDEF"[v as usize & 0xF] as char)
@@ -72,29 +113,188 @@ pub trait WriteJson: Write {
         self.write_char(close)
     }
 
+    /// Writes `n` the same way as ECMAScript `Number::toString(n)` does, including `NaN` and
+    /// `Infinity`. Note that `-0` is written as `0`.
+    /// See https://tc39.es/ecma262/#sec-numeric-types-number-tostring
+    fn write_number(&mut self, n: f64) -> fmt::Result {
+        if n.is_nan() {
+            return self.write_str("NaN");
+        }
+        if n == 0.0 {
+            return self.write_char('0');
+        }
+        if n < 0.0 {
+            self.write_char('-')?;
+            return self.write_number(-n);
+        }
+        if n.is_infinite() {
+            return self.write_str("Infinity");
+        }
+        // `LowerExp` gives the shortest digits that round-trip, e.g. `1.2345e-7`.
+        let e = format!("{n:e}");
+        let (mantissa, exponent) = e.split_once('e').unwrap();
+        let digits = mantissa.replace('.', "");
+        let k = digits.len() as i32;
+        let n = exponent.parse::<i32>().unwrap() + 1;
+        if k <= n && n <= 21 {
+            self.write_str(&digits)?;
+            for _ in k..n {
+                self.write_char('0')?;
+            }
+            Ok(())
+        } else if 0 < n && n <= 21 {
+            let (integer, fraction) = digits.split_at(n as usize);
+            self.write_str(integer)?;
+            self.write_char('.')?;
+            self.write_str(fraction)
+        } else if -6 < n && n <= 0 {
+            self.write_str("0.")?;
+            for _ in n..0 {
+                self.write_char('0')?;
+            }
+            self.write_str(&digits)
+        } else {
+            let (first, rest) = digits.split_at(1);
+            self.write_str(first)?;
+            if !rest.is_empty() {
+                self.write_char('.')?;
+                self.write_str(rest)?;
+            }
+            self.write_char('e')?;
+            self.write_char(if n > 0 { '+' } else { '-' })?;
+            self.write_str((n - 1).unsigned_abs().to_string().as_str())
+        }
+    }
+
+    /// Writes a BigInt literal, e.g. `123n`. It's valid in DJS but not in JSON.
+    fn write_js_bigint(&mut self, b: &JsBigintRef<impl Dealloc>) -> fmt::Result {
+        self.write_str(to_decimal_string(b).as_str())?;
+        self.write_char('n')
+    }
+
     fn write_json(&mut self, any: Any<impl Dealloc>) -> fmt::Result {
-        match to_visitor(any) {
-            // TODO: replace with proper JSON number serializer.
-            Visitor::Number(n) => self.write_str(n.to_string().as_str()),
-            Visitor::Null => self.write_str("null"),
-            Visitor::Bool(b) => self.write_str(if b { "true" } else { "false" }),
-            Visitor::String(s) => self.write_js_string(&s),
-            Visitor::Object(o) => self.write_list('{', '}', o, |w, (k, v)| {
-                w.write_js_string(k)?;
-                w.write_char(':')?;
-                w.write_json(v.clone())
-            }),
-            Visitor::Array(a) => self.write_list('[', ']', a, |w, i| w.write_json(i.clone())),
-            Visitor::Bigint(_) => todo!(),
+        self.write_json_with_options(any, &default())
+    }
+
+    fn write_json_with_options(
+        &mut self,
+        any: Any<impl Dealloc>,
+        options: &JsonOptions,
+    ) -> fmt::Result {
+        write_json_at(self, any, options, 0, 0)
+    }
+}
+
+/// Writes `any` nested at `depth`, which is the indentation level of its items, after `column`
+/// characters on its line.
+fn write_json_at<W: WriteJson + ?Sized>(
+    w: &mut W,
+    any: Any<impl Dealloc>,
+    options: &JsonOptions,
+    depth: usize,
+    column: usize,
+) -> fmt::Result {
+    match to_visitor(any) {
+        Visitor::Number(n) if n.is_finite() => w.write_number(n),
+        Visitor::Number(n) => match options.non_finite {
+            NonFinitePolicy::Null => w.write_str("null"),
+            NonFinitePolicy::Error => Err(fmt::Error),
+            NonFinitePolicy::String => {
+                w.write_char('"')?;
+                w.write_number(n)?;
+                w.write_char('"')
+            }
+        },
+        Visitor::Null => w.write_str("null"),
+        // `JSON.stringify(undefined)` returns no text, so there is nothing to write.
+        Visitor::Undefined => Err(fmt::Error),
+        Visitor::Bool(b) => w.write_str(if b { "true" } else { "false" }),
+        Visitor::String(s) => w.write_js_string(&s),
+        Visitor::Object(o) => write_properties(w, o.items(), options, depth),
+        Visitor::Array(a) => {
+            let any = Any::move_from(a.clone());
+            write_array(w, any, a.items(), options, depth, column)
         }
+        Visitor::Persistent(p) => match &p.0 {
+            Persistent::Object(o) => write_properties(w, o.iter(), options, depth),
+            Persistent::Array(a) => {
+                let any = Any::move_from(p.clone());
+                write_array(w, any, a.iter(), options, depth, column)
+            }
+        },
+        // A function is code, which JSON can't represent.
+        Visitor::Function(_) => Err(fmt::Error),
+        Visitor::Bigint(b) => match options.bigint {
+            BigintPolicy::Error => Err(fmt::Error),
+            BigintPolicy::String => {
+                w.write_char('"')?;
+                w.write_str(to_decimal_string(&b).as_str())?;
+                w.write_char('"')
+            }
+            BigintPolicy::Digits => w.write_str(to_decimal_string(&b).as_str()),
+        },
+    }
+}
+
+fn write_properties<'a, W: WriteJson + ?Sized, D: Dealloc + 'a>(
+    w: &mut W,
+    properties: impl IntoIterator<Item = &'a Property<D>>,
+    options: &JsonOptions,
+    depth: usize,
+) -> fmt::Result {
+    let format = &options.format;
+    // Properties with `undefined` values are skipped, the same way as `JSON.stringify` does.
+    let properties = properties.into_iter().filter(|(_, v)| !v.is::<Undefined>());
+    format.write_items(w, depth, ('{', '}'), false, properties, |w, (k, v)| {
+        let column = write_prefix(w, |w| {
+            w.write_js_string(k)?;
+            format.write_colon(w)
+        })?;
+        write_json_at(w, v.clone(), options, depth + 1, column)
+    })
+}
+
+/// Writes the `items` of the array `any`, on one line if they fit.
+fn write_array<'a, W: WriteJson + ?Sized, D: Dealloc + 'a>(
+    w: &mut W,
+    any: Any<D>,
+    items: impl IntoIterator<Item = &'a Any<D>>,
+    options: &JsonOptions,
+    depth: usize,
+    column: usize,
+) -> fmt::Result {
+    let format = &options.format;
+    let single_line = JsonOptions {
+        format: format.single_line(),
+        ..*options
+    };
+    let collapsed = format.write_collapsed(w, depth, column, |w| {
+        write_json_at(w, any, &single_line, depth, column)
+    })?;
+    if collapsed {
+        return Ok(());
     }
+    format.write_items(w, depth, ('[', ']'), false, items, |w, i| {
+        if i.is::<Undefined>() {
+            w.write_str("null")
+        } else {
+            write_json_at(w, i.clone(), options, depth + 1, 0)
+        }
+    })
 }
 
 impl<T: Write> WriteJson for T {}
 
 pub fn to_json(a: Any<impl Dealloc>) -> result::Result<String, fmt::Error> {
+    to_json_with_options(a, &default())
+}
+
+pub fn to_json_with_options(
+    a: Any<impl Dealloc>,
+    options: &JsonOptions,
+) -> result::Result<String, fmt::Error> {
     let mut s = String::default();
-    s.write_json(a)?;
+    s.write_json_with_options(a, options)?;
     Ok(s)
 }
 
@@ -103,9 +303,26 @@ mod test {
     use wasm_bindgen_test::wasm_bindgen_test;
 
     use crate::{
-        js::{any::Any, any_cast::AnyCast, js_string::new_string, new::New, null::Null},
+        bytecode::Program,
+        js::{
+            any::Any,
+            any_cast::AnyCast,
+            js_bigint::{new_bigint, Sign},
+            js_string::new_string,
+            new::New,
+            null::Null,
+            undefined::Undefined,
+        },
         mem::global::{Global, GLOBAL},
-        serializer::to_json::WriteJson,
+        serializer::{
+            format::{FormatOptions, Indent, KeyQuotes, Newline},
+            to_djs::to_djs,
+            to_json::{
+                to_json, to_json_with_options, BigintPolicy, JsonOptions, NonFinitePolicy,
+                WriteJson,
+            },
+        },
+        tokenizer::{tokenize, JsonToken},
     };
 
     #[test]
@@ -130,4 +347,223 @@ mod test {
         s.write_json(a).unwrap();
         assert_eq!(s, r#"[1,true,null,[],"",{},{"a\\b\"\u001F":2}]"#);
     }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_bigint() {
+        let a = || {
+            GLOBAL.new_js_array([
+                Any::move_from(new_bigint(GLOBAL, Sign::Negative, [0, 1]).to_ref()),
+                Any::move_from(new_bigint(GLOBAL, Sign::Positive, []).to_ref()),
+            ])
+        };
+        assert!(to_json(a()).is_err());
+        let options = |bigint| JsonOptions {
+            bigint,
+            ..JsonOptions::default()
+        };
+        assert_eq!(
+            to_json_with_options(a(), &options(BigintPolicy::String)).unwrap(),
+            r#"["-18446744073709551616","0"]"#
+        );
+        assert_eq!(
+            to_json_with_options(a(), &options(BigintPolicy::Digits)).unwrap(),
+            "[-18446744073709551616,0]"
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_surrogates() {
+        for items in [
+            [0xD83D, 0xDE00, 0x41].as_slice(),
+            &[0xD83D],
+            &[0xDE00, 0xD83D],
+            &[0x41, 0xDBFF],
+        ] {
+            let json = to_json(GLOBAL.new_js_string(items.iter().copied())).unwrap();
+            assert_eq!(tokenize(GLOBAL, json), [JsonToken::String(items.to_vec())]);
+        }
+        assert_eq!(
+            to_json(GLOBAL.new_js_string([0xD83D, 0xDE00])).unwrap(),
+            r#""\uD83D\uDE00""#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_undefined() {
+        let s = new_string(GLOBAL, ['a' as u16]).to_ref();
+        let t = new_string(GLOBAL, ['b' as u16]).to_ref();
+        let a = GLOBAL.new_js_array([
+            Undefined().move_to_any(),
+            GLOBAL.new_js_object([(s, Undefined().move_to_any()), (t, 1.0.move_to_any())]),
+        ]);
+        assert_eq!(to_json(a).unwrap(), r#"[null,{"b":1}]"#);
+        assert!(to_json(Any::<Global>::move_from(Undefined())).is_err());
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_function() {
+        let program = Program::default().share(GLOBAL);
+        let f = GLOBAL.new_js_function(program, 0, [1.0.move_to_any()]);
+        let a = GLOBAL.new_js_array([f]);
+        assert!(to_json(a.clone()).is_err());
+        assert!(to_djs(a, true).is_err());
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_number() {
+        let f = |n: f64| {
+            let mut s = String::new();
+            s.write_number(n).unwrap();
+            s
+        };
+        assert_eq!(f(0.0), "0");
+        assert_eq!(f(-0.0), "0");
+        assert_eq!(f(1.0), "1");
+        assert_eq!(f(-1.5), "-1.5");
+        assert_eq!(f(100.0), "100");
+        assert_eq!(f(123.456), "123.456");
+        assert_eq!(f(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(f(9007199254740992.0), "9007199254740992");
+        assert_eq!(f(1e20), "100000000000000000000");
+        assert_eq!(f(1e21), "1e+21");
+        assert_eq!(f(1.5e21), "1.5e+21");
+        assert_eq!(f(123456789012345680000.0), "123456789012345680000");
+        assert_eq!(f(0.000001), "0.000001");
+        assert_eq!(f(0.0000012), "0.0000012");
+        assert_eq!(f(1e-7), "1e-7");
+        assert_eq!(f(1.5e-7), "1.5e-7");
+        assert_eq!(f(1.23e-18), "1.23e-18");
+        assert_eq!(f(5e-324), "5e-324");
+        assert_eq!(f(f64::MAX), "1.7976931348623157e+308");
+        assert_eq!(f(f64::NAN), "NaN");
+        assert_eq!(f(f64::INFINITY), "Infinity");
+        assert_eq!(f(f64::NEG_INFINITY), "-Infinity");
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_number_round_trip() {
+        for n in [0.1, -123.456, 1e21, 1e-7, 1.0 / 3.0, 123456789.12345679] {
+            let mut s = String::new();
+            s.write_number(n).unwrap();
+            assert_eq!(tokenize(GLOBAL, s), [JsonToken::Number(n)]);
+        }
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_non_finite() {
+        let a = || {
+            GLOBAL.new_js_array([
+                f64::NAN.move_to_any(),
+                f64::INFINITY.move_to_any(),
+                f64::NEG_INFINITY.move_to_any(),
+            ])
+        };
+        assert_eq!(to_json(a()).unwrap(), "[null,null,null]");
+        let options = |non_finite| JsonOptions {
+            non_finite,
+            ..JsonOptions::default()
+        };
+        assert!(to_json_with_options(a(), &options(NonFinitePolicy::Error)).is_err());
+        assert_eq!(
+            to_json_with_options(a(), &options(NonFinitePolicy::String)).unwrap(),
+            r#"["NaN","Infinity","-Infinity"]"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_pretty() {
+        let key = |s: &str| new_string(GLOBAL, s.encode_utf16().collect::<Vec<_>>()).to_ref();
+        let numbers = GLOBAL.new_js_array(
+            (0..30)
+                .map(|i| (i as f64).move_to_any())
+                .collect::<Vec<_>>(),
+        );
+        let a = GLOBAL.new_js_array([
+            GLOBAL.new_js_object([
+                (
+                    key("a"),
+                    GLOBAL.new_js_array([1.0.move_to_any(), 2.0.move_to_any()]),
+                ),
+                (key("b"), GLOBAL.new_js_object([])),
+                (key("c"), Undefined().move_to_any()),
+            ]),
+            GLOBAL.new_js_array([]),
+            numbers,
+        ]);
+        let options = JsonOptions {
+            format: FormatOptions::pretty(),
+            ..JsonOptions::default()
+        };
+        let numbers = (0..30)
+            .map(|i| format!("\n    {i}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(
+            to_json_with_options(a.clone(), &options).unwrap(),
+            format!(
+                "[\n  {{\n    \"a\": [1, 2],\n    \"b\": {{}}\n  }},\n  [],\n  [{numbers}\n  ]\n]"
+            )
+        );
+        // Trailing commas and unquoted keys aren't valid JSON, so they are ignored.
+        let options = JsonOptions {
+            format: FormatOptions {
+                indent: Indent::Tab,
+                newline: Newline::CrLf,
+                max_width: 0,
+                trailing_comma: true,
+                key_quotes: KeyQuotes::AsNeeded,
+                ..FormatOptions::default()
+            },
+            ..JsonOptions::default()
+        };
+        let b = GLOBAL.new_js_object([(key("a"), GLOBAL.new_js_array([1.0.move_to_any()]))]);
+        assert_eq!(
+            to_json_with_options(b.clone(), &options).unwrap(),
+            "{\r\n\t\"a\":[\r\n\t\t1\r\n\t]\r\n}"
+        );
+        let options = JsonOptions {
+            format: FormatOptions {
+                space_after_comma: true,
+                ..FormatOptions::default()
+            },
+            ..JsonOptions::default()
+        };
+        assert_eq!(
+            to_json_with_options(a.get_index(0).unwrap(), &options).unwrap(),
+            r#"{"a":[1, 2], "b":{}}"#
+        );
+        assert_eq!(to_json(b).unwrap(), r#"{"a":[1]}"#);
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_pretty_key_width() {
+        let key = new_string(GLOBAL, "a_long_key".encode_utf16().collect::<Vec<_>>()).to_ref();
+        let items = [1.0, 2.0, 3.0].map(|i| i.move_to_any());
+        let o = GLOBAL.new_js_object([(key, GLOBAL.new_js_array(items))]);
+        let options = |max_width| JsonOptions {
+            format: FormatOptions {
+                max_width,
+                ..FormatOptions::pretty()
+            },
+            ..JsonOptions::default()
+        };
+        // `  "a_long_key": [1, 2, 3]` is 25 characters wide.
+        assert_eq!(
+            to_json_with_options(o.clone(), &options(25)).unwrap(),
+            "{\n  \"a_long_key\": [1, 2, 3]\n}"
+        );
+        assert_eq!(
+            to_json_with_options(o, &options(24)).unwrap(),
+            "{\n  \"a_long_key\": [\n    1,\n    2,\n    3\n  ]\n}"
+        );
+    }
 }
@@ -0,0 +1,328 @@
+use core::{
+    fmt::{self, Write},
+    result,
+};
+
+use crate::{
+    ast::{Expression, Module},
+    mem::manager::Dealloc,
+    parser::{
+        analyzer::{AnalyzerResults, ModuleNames},
+        shared::DataType,
+    },
+};
+
+use super::{
+    format::{write_prefix, FormatOptions},
+    to_djs::{write_djs_value, write_key},
+    to_json::WriteJson,
+};
+
+/// Writes the expressions of a module with the names they have in the source.
+struct ModuleWriter<'a, D: Dealloc> {
+    module: &'a Module<D>,
+    names: &'a ModuleNames,
+}
+
+impl<D: Dealloc> ModuleWriter<'_, D> {
+    /// The name of a const or an imported module that `e` refers to.
+    fn name(&self, e: &Expression<D>) -> Option<&str> {
+        match e {
+            Expression::LocalRef(i) => self.names.consts.get(*i as usize),
+            Expression::ArgRef(i) => self.names.imports.get(*i as usize)?.as_ref(),
+            _ => None,
+        }
+        .map(String::as_str)
+    }
+
+    /// Writes a statement ending with `;` and a line break if `format` is multi-line.
+    fn write_statement<W: WriteJson + ?Sized>(
+        &self,
+        write_json: &mut W,
+        format: &FormatOptions,
+        f: impl FnOnce(&mut W) -> fmt::Result,
+    ) -> fmt::Result {
+        f(write_json)?;
+        write_json.write_char(';')?;
+        if format.is_multiline() {
+            format.write_line(write_json, 0)?;
+        }
+        Ok(())
+    }
+
+    fn write_expression(
+        &self,
+        write_json: &mut (impl WriteJson + ?Sized),
+        e: &Expression<D>,
+        format: &FormatOptions,
+        depth: usize,
+        column: usize,
+    ) -> fmt::Result {
+        if let Some(name) = self.name(e) {
+            return write_json.write_str(name);
+        }
+        match e {
+            Expression::Value(any) => {
+                write_djs_value(write_json, any.clone(), format, depth, column)
+            }
+            Expression::Object(properties) => format.write_items(
+                write_json,
+                depth,
+                ('{', '}'),
+                format.trailing_comma,
+                properties,
+                |w, (k, v)| {
+                    if let Some(name) = self.name(v) {
+                        // A shorthand property, e.g. `{a}` instead of `{"a":a}`.
+                        if k.items().iter().copied().eq(name.encode_utf16()) {
+                            return w.write_str(name);
+                        }
+                    }
+                    let column = write_prefix(w, |w| {
+                        write_key(w, k, format)?;
+                        format.write_colon(w)
+                    })?;
+                    self.write_expression(w, v, format, depth + 1, column)
+                },
+            ),
+            Expression::Array(items) => {
+                let single_line = format.single_line();
+                let collapsed = format.write_collapsed(write_json, depth, column, |w| {
+                    self.write_expression(w, e, &single_line, depth, column)
+                })?;
+                if collapsed {
+                    return Ok(());
+                }
+                format.write_items(
+                    write_json,
+                    depth,
+                    ('[', ']'),
+                    format.trailing_comma,
+                    items,
+                    |w, i| self.write_expression(w, i, format, depth + 1, 0),
+                )
+            }
+            // An import without a name is a `require` call.
+            Expression::ArgRef(i) => {
+                let path = self.module.import.get(*i as usize).ok_or(fmt::Error)?;
+                write_json.write_str("require(")?;
+                write_json.write_js_string(path)?;
+                write_json.write_char(')')
+            }
+            // Names of function parameters aren't kept in a module, and a const without a name is
+            // only possible in a module with errors.
+            Expression::Void
+            | Expression::LocalRef(_)
+            | Expression::CaptureRef(_)
+            | Expression::Function(_)
+            | Expression::Call(..) => Err(fmt::Error),
+        }
+    }
+}
+
+pub trait WriteModule: WriteJson {
+    /// Writes `module` back in the format of `data_type` with the import statements, relative
+    /// paths and const names of its source. Only values are normalized, e.g. `1.6e1` is written as
+    /// `16` and strings are written with double quotes. A path imported more than once is written
+    /// as one import, and the names of the other imports are replaced with its first name.
+    fn write_module<D: Dealloc>(
+        &mut self,
+        data_type: &DataType,
+        module: &Module<D>,
+        names: &ModuleNames,
+        format: &FormatOptions,
+    ) -> fmt::Result {
+        let writer = ModuleWriter { module, names };
+        if *data_type == DataType::Mjs {
+            for (path, name) in module.import.iter().zip(&names.imports) {
+                let Some(name) = name else {
+                    continue;
+                };
+                writer.write_statement(self, format, |w| {
+                    w.write_str("import ")?;
+                    w.write_str(name)?;
+                    w.write_str(" from ")?;
+                    w.write_js_string(path)
+                })?;
+            }
+        }
+        for (i, e) in module.body.local.iter().enumerate() {
+            let name = names.consts.get(i).ok_or(fmt::Error)?;
+            writer.write_statement(self, format, |w| {
+                let column = write_prefix(w, |w| {
+                    w.write_str("const ")?;
+                    w.write_str(name)?;
+                    format.write_assign(w)
+                })?;
+                writer.write_expression(w, e, format, 0, column)
+            })?;
+        }
+        let column = write_prefix(self, |w| match data_type {
+            DataType::Json | DataType::Djs => Ok(()),
+            DataType::Cjs => {
+                w.write_str("module.exports")?;
+                format.write_assign(w)
+            }
+            DataType::Mjs => w.write_str("export default "),
+        })?;
+        writer.write_expression(self, &module.body.result, format, 0, column)
+    }
+}
+
+impl<T: WriteJson> WriteModule for T {}
+
+/// A construct of a module that `WriteModule::write_module` can't write back, e.g.
+/// `"an arrow function"`.
+#[derive(Debug, PartialEq)]
+pub struct Unsupported(pub &'static str);
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} can't be written back", self.0)
+    }
+}
+
+/// The first construct of `e` that can't be written back.
+fn unsupported<D: Dealloc>(e: &Expression<D>) -> Option<Unsupported> {
+    match e {
+        Expression::Function(_) => Some(Unsupported("an arrow function")),
+        Expression::Call(..) => Some(Unsupported("a function call")),
+        Expression::Object(properties) => properties.iter().find_map(|(_, v)| unsupported(v)),
+        Expression::Array(items) => items.iter().find_map(unsupported),
+        _ => None,
+    }
+}
+
+/// Writes an analyzed module back, see `WriteModule::write_module`. Comments aren't kept, see
+/// `AnalyzerResults::comments`.
+pub fn to_module<D: Dealloc>(
+    results: &AnalyzerResults<D>,
+    format: &FormatOptions,
+) -> result::Result<String, Unsupported> {
+    let body = &results.module.body;
+    if let Some(e) = body
+        .local
+        .iter()
+        .chain([&body.result])
+        .find_map(unsupported)
+    {
+        return Err(e);
+    }
+    let mut s = String::default();
+    // Other expressions that can't be written, e.g. a const without a name, only occur in modules
+    // with errors.
+    s.write_module(&results.data_type, &results.module, &results.names, format)
+        .map_err(|_| Unsupported("a module with errors"))?;
+    Ok(s)
+}
+
+#[cfg(test)]
+mod test {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use crate::{
+        mem::global::{Global, GLOBAL},
+        parser::analyzer::{AnalyzerParameters, AnalyzerResults, AnalyzerState},
+        serializer::format::{FormatOptions, KeyQuotes},
+    };
+
+    use super::{to_module, Unsupported};
+
+    fn analyze(input: &str) -> AnalyzerResults<Global> {
+        let mut state = AnalyzerState::new(AnalyzerParameters::default());
+        for c in input.chars() {
+            state.push_mut(GLOBAL, c);
+        }
+        let results = state.end(GLOBAL);
+        assert_eq!(results.diagnostics, []);
+        results
+    }
+
+    fn round_trip(input: &str, format: &FormatOptions) -> String {
+        to_module(&analyze(input), format).unwrap()
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_mjs() {
+        let input = "import users from \"../data/users.d.mjs\"\n\
+            import config from \"./config.json\"\n\
+            const limit = 1.6e1\n\
+            const admin = { name: \"root\", limit }\n\
+            export default { users, admin, config: [config, limit] }";
+        let format = FormatOptions {
+            key_quotes: KeyQuotes::AsNeeded,
+            ..FormatOptions::pretty()
+        };
+        let output = round_trip(input, &format);
+        assert_eq!(
+            output,
+            "import users from \"../data/users.d.mjs\";\n\
+            import config from \"./config.json\";\n\
+            const limit = 16;\n\
+            const admin = {\n  name: \"root\",\n  limit\n};\n\
+            export default {\n  users,\n  admin,\n  config: [config, limit]\n}"
+        );
+        assert_eq!(round_trip(&output, &format), output);
+        assert_eq!(
+            round_trip(input, &FormatOptions::default()),
+            r#"import users from "../data/users.d.mjs";import config from "./config.json";const limit=16;const admin={"name":"root",limit};export default {users,admin,"config":[config,limit]}"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_duplicate_import() {
+        // Both names refer to one import, so the second one is written with the first name.
+        let input = "import a from \"./x.d.mjs\"\n\
+            import b from \"./x.d.mjs\"\n\
+            export default [a, b]";
+        assert_eq!(
+            round_trip(input, &FormatOptions::default()),
+            r#"import a from "./x.d.mjs";export default [a,a]"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_cjs() {
+        let input = "const a = require(\"./a.d.cjs\")\n\
+            const b = [a, require(\"./b.json\"), require(\"./a.d.cjs\")]\n\
+            module.exports = { b, c: [1n, undefined, -1] }";
+        assert_eq!(
+            round_trip(input, &FormatOptions::default()),
+            r#"const a=require("./a.d.cjs");const b=[a,require("./b.json"),require("./a.d.cjs")];module.exports={b,"c":[1n,undefined,-1]}"#
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_djs() {
+        assert_eq!(
+            round_trip("const x = \"a\"\n[x, x]", &FormatOptions::default()),
+            r#"const x="a";[x,x]"#
+        );
+        assert_eq!(
+            round_trip("{\"a\": [1, 2]}", &FormatOptions::pretty()),
+            "{\n  \"a\": [1, 2]\n}"
+        );
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_function() {
+        let f = |input| to_module(&analyze(input), &FormatOptions::default()).unwrap_err();
+        assert_eq!(
+            f("export default [1, {a: (a) => a}]"),
+            Unsupported("an arrow function")
+        );
+        assert_eq!(
+            f("import f from \"./f.d.mjs\"\nexport default f(1)"),
+            Unsupported("a function call")
+        );
+        assert_eq!(
+            f("export default (a) => a").to_string(),
+            "an arrow function can't be written back"
+        );
+    }
+}
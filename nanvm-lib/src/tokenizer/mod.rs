@@ -1,5 +1,8 @@
+pub mod span;
+
 use std::{
     collections::VecDeque,
+    fmt::Display,
     mem::take,
     ops::{Deref, RangeInclusive},
 };
@@ -7,14 +10,19 @@ use std::{
 use crate::{
     big_numbers::big_float::BigFloat,
     common::{cast::Cast, default::default},
-    js::js_bigint::{add, equals, from_u64, mul, negative, JsBigintMutRef, Sign},
+    js::js_bigint::{
+        add, equals, from_u64, mul, negative, to_decimal_string, JsBigintMutRef, Sign,
+    },
     mem::manager::{Dealloc, Manager},
     range_map::{from_one, from_range, merge, merge_list, RangeMap, State},
 };
 
+use self::span::{SpanTracker, SpannedToken};
+
 #[derive(Debug)]
 pub enum JsonToken<D: Dealloc> {
-    String(String),
+    /// UTF-16 code units, the same as `JsString` items, so lone surrogates are kept.
+    String(Vec<u16>),
     Number(f64),
     ObjectBegin,
     ObjectEnd,
@@ -31,6 +39,7 @@ pub enum JsonToken<D: Dealloc> {
     Semicolon,
     OpeningParenthesis,
     ClosingParenthesis,
+    Arrow,
 }
 
 impl<D: Dealloc> PartialEq for JsonToken<D> {
@@ -46,6 +55,31 @@ impl<D: Dealloc> PartialEq for JsonToken<D> {
     }
 }
 
+impl<D: Dealloc> Display for JsonToken<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonToken::String(s) => write!(f, "string {:?}", String::from_utf16_lossy(s)),
+            JsonToken::Number(n) => write!(f, "number `{n}`"),
+            JsonToken::ObjectBegin => f.write_str("`{`"),
+            JsonToken::ObjectEnd => f.write_str("`}`"),
+            JsonToken::ArrayBegin => f.write_str("`[`"),
+            JsonToken::ArrayEnd => f.write_str("`]`"),
+            JsonToken::Colon => f.write_str("`:`"),
+            JsonToken::Comma => f.write_str("`,`"),
+            JsonToken::Equals => f.write_str("`=`"),
+            JsonToken::Dot => f.write_str("`.`"),
+            JsonToken::ErrorToken(e) => write!(f, "invalid token ({e:?})"),
+            JsonToken::BigInt(b) => write!(f, "bigint `{}n`", to_decimal_string(b)),
+            JsonToken::Id(s) => write!(f, "identifier `{s}`"),
+            JsonToken::NewLine => f.write_str("new line"),
+            JsonToken::Semicolon => f.write_str("`;`"),
+            JsonToken::OpeningParenthesis => f.write_str("`(`"),
+            JsonToken::ClosingParenthesis => f.write_str("`)`"),
+            JsonToken::Arrow => f.write_str("`=>`"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ErrorType {
     UnexpectedCharacter,
@@ -61,8 +95,8 @@ pub enum TokenizerState<D: Dealloc> {
     #[default]
     Initial,
     ParseId(String),
-    ParseString(String),
-    ParseEscapeChar(String),
+    ParseString(Vec<u16>),
+    ParseEscapeChar(Vec<u16>),
     ParseUnicodeChar(ParseUnicodeCharState),
     ParseMinus,
     ParseZero(Sign),
@@ -140,12 +174,85 @@ impl<D: Dealloc + 'static> TokenizerState<D> {
         tokens
     }
 
+    /// Same as [`TokenizerState::push_mut`] but also attaches a source span to every token.
+    pub fn push_spanned<M: Manager<Dealloc = D> + 'static>(
+        &mut self,
+        manager: M,
+        c: char,
+        tm: &TransitionMaps<M>,
+        tracker: &mut SpanTracker,
+    ) -> Vec<SpannedToken<D>> {
+        let pending = self.is_pending();
+        let consumed = !pending || self.consumes_terminator();
+        let at = tracker.position();
+        // The end of an invalid escape sequence: an escape character belongs to it, a character
+        // that isn't a hex digit doesn't.
+        let escape_end = match self {
+            TokenizerState::ParseEscapeChar(_) => Some(at.next(c)),
+            TokenizerState::ParseUnicodeChar(_) => Some(at),
+            _ => None,
+        };
+        let tokens = self.push_mut(manager, c, tm);
+        let mut tokens = tracker.push(c, pending, consumed, self.is_pending(), tokens);
+        if let Some(end) = escape_end {
+            tracker.escape_spans(end, &mut tokens);
+        }
+        if matches!(self, TokenizerState::ParseEscapeChar(_)) {
+            tracker.begin_escape(at);
+        }
+        tokens
+    }
+
+    pub fn end_spanned<M: Manager<Dealloc = D>>(
+        self,
+        manager: M,
+        tracker: &SpanTracker,
+    ) -> Vec<SpannedToken<D>> {
+        // An unterminated comment is reported from its opening, which is where the tracker's
+        // current token began.
+        let pending = !matches!(self, TokenizerState::Initial);
+        tracker.end(pending, self.end(manager))
+    }
+
+    /// Returns `true` inside a comment, after its opening `//` or `/*`.
+    pub fn is_comment(&self) -> bool {
+        matches!(
+            self,
+            TokenizerState::ParseSinglelineComment
+                | TokenizerState::ParseMultilineComment
+                | TokenizerState::ParseMultilineCommentAsterix
+        )
+    }
+
+    /// Returns `true` if the state is in the middle of a token (or of a comment opening, which
+    /// can still turn into an error token).
+    fn is_pending(&self) -> bool {
+        !matches!(
+            self,
+            TokenizerState::Initial
+                | TokenizerState::ParseSinglelineComment
+                | TokenizerState::ParseMultilineComment
+                | TokenizerState::ParseMultilineCommentAsterix
+        )
+    }
+
+    /// Returns `true` if a token emitted from this state includes the character that completed it.
+    fn consumes_terminator(&self) -> bool {
+        matches!(
+            self,
+            TokenizerState::ParseString(_)
+                | TokenizerState::ParseEscapeChar(_)
+                | TokenizerState::ParseUnicodeChar(_)
+                | TokenizerState::ParseCommentStart
+        )
+    }
+
     fn end<M: Manager<Dealloc = D>>(self, manager: M) -> Vec<JsonToken<D>> {
         match self {
             TokenizerState::Initial
             | TokenizerState::ParseNewLine
             | TokenizerState::ParseSinglelineComment => default(),
-            TokenizerState::ParseId(s) => [JsonToken::Id(s)].cast(),
+            TokenizerState::ParseId(s) => id_tokens(s),
             TokenizerState::ParseString(_)
             | TokenizerState::ParseEscapeChar(_)
             | TokenizerState::ParseUnicodeChar(_) => {
@@ -158,7 +265,7 @@ impl<D: Dealloc + 'static> TokenizerState<D> {
             | TokenizerState::ParseMultilineCommentAsterix => {
                 [JsonToken::ErrorToken(ErrorType::CommentClosingExpected)].cast()
             }
-            TokenizerState::ParseZero(_) => [JsonToken::Number(default())].cast(),
+            TokenizerState::ParseZero(s) => [JsonToken::Number(zero(s))].cast(),
             TokenizerState::ParseInt(s) => [int_state_into_number_token(manager, s)].cast(),
             TokenizerState::ParseFrac(s) => [float_state_into_token(manager, s)].cast(),
             TokenizerState::ParseExp(s) => [exp_state_into_token(manager, s)].cast(),
@@ -175,8 +282,8 @@ impl<D: Dealloc + 'static> TokenizerState<D> {
 }
 
 pub struct ParseUnicodeCharState {
-    s: String,
-    unicode: u32,
+    s: Vec<u16>,
+    unicode: u16,
     index: u8,
 }
 
@@ -185,17 +292,13 @@ impl ParseUnicodeCharState {
         mut self,
         i: u32,
     ) -> (Vec<JsonToken<M::Dealloc>>, TokenizerState<M::Dealloc>) {
-        let new_unicode = self.unicode | (i << ((3 - self.index) * 4));
+        let new_unicode = self.unicode | ((i as u16) << ((3 - self.index) * 4));
         match self.index {
+            // The escape is a UTF-16 code unit. Surrogate pairs come as two escapes, and a lone
+            // surrogate is kept as is.
             3 => {
-                let c = char::from_u32(new_unicode);
-                match c {
-                    Some(c) => continue_string_state::<M>(self.s, c),
-                    None => (
-                        [JsonToken::ErrorToken(ErrorType::InvalidHex)].cast(),
-                        TokenizerState::Initial,
-                    ),
-                }
+                self.s.push(new_unicode);
+                (default(), TokenizerState::ParseString(self.s))
             }
             0..=2 => {
                 self.unicode = new_unicode;
@@ -207,6 +310,14 @@ impl ParseUnicodeCharState {
     }
 }
 
+/// Zero with the sign of the literal, so `-0` keeps its sign.
+fn zero(s: Sign) -> f64 {
+    match s {
+        Sign::Positive => 0.0,
+        Sign::Negative => -0.0,
+    }
+}
+
 pub fn bigfloat_to_f64<M: Manager>(bf_10: BigFloat<10, M>) -> f64 {
     let bf_2 = bf_10.to_bin(54);
     bf_2.to_f64()
@@ -356,6 +467,7 @@ fn operator_to_token<D: Dealloc>(s: String) -> Option<JsonToken<D>> {
         ";" => Some(JsonToken::Semicolon),
         "(" => Some(JsonToken::OpeningParenthesis),
         ")" => Some(JsonToken::ClosingParenthesis),
+        "=>" => Some(JsonToken::Arrow),
         _ => None,
     }
 }
@@ -377,6 +489,12 @@ fn operator_chars_with_dot() -> Vec<RangeInclusive<char>> {
     set(c)
 }
 
+/// Characters that can continue an operator. `>` only appears in `=>`.
+fn operator_continuation_chars() -> Vec<RangeInclusive<char>> {
+    let c = OPERATOR_CHARS.into_iter().chain(['.', '>']);
+    set(c)
+}
+
 fn terminal_for_number() -> Vec<RangeInclusive<char>> {
     let c = WHITE_SPACE_CHARS
         .into_iter()
@@ -429,8 +547,8 @@ struct TransitionMap<T, M: Manager> {
 pub struct TransitionMaps<M: Manager> {
     initial: TransitionMap<(), M>,
     id: TransitionMap<String, M>,
-    string: TransitionMap<String, M>,
-    escape_char: TransitionMap<String, M>,
+    string: TransitionMap<Vec<u16>, M>,
+    escape_char: TransitionMap<Vec<u16>, M>,
     unicode_char: TransitionMap<ParseUnicodeCharState, M>,
     zero: TransitionMap<Sign, M>,
     int: TransitionMap<IntState<M::Dealloc>, M>,
@@ -507,7 +625,7 @@ fn create_initial_transitions<M: Manager>() -> TransitionMap<(), M> {
                     )
                 }),
                 from_one('"', |_, _, _, _| {
-                    (default(), TokenizerState::ParseString(String::default()))
+                    (default(), TokenizerState::ParseString(Vec::default()))
                 }),
                 from_one('0', |_, _, _, _| {
                     (default(), TokenizerState::ParseZero(Sign::Positive))
@@ -529,16 +647,24 @@ fn create_initial_transitions<M: Manager>() -> TransitionMap<(), M> {
     }
 }
 
+/// The tokens of an identifier. `-Infinity` is the only identifier that can start with `-`, and
+/// it's a number.
+fn id_tokens<D: Dealloc>(s: String) -> Vec<JsonToken<D>> {
+    match s.strip_prefix('-') {
+        None => [JsonToken::Id(s)].cast(),
+        Some("Infinity") => [JsonToken::Number(f64::NEG_INFINITY)].cast(),
+        Some(id) => [
+            JsonToken::ErrorToken(ErrorType::InvalidNumber),
+            JsonToken::Id(id.to_string()),
+        ]
+        .cast(),
+    }
+}
+
 fn create_id_transitions<M: Manager + 'static>() -> TransitionMap<String, M> {
     TransitionMap {
         def: |manager, s, c, maps| {
-            transfer_state(
-                manager,
-                [JsonToken::Id(s)].cast(),
-                TokenizerState::Initial,
-                c,
-                maps,
-            )
+            transfer_state(manager, id_tokens(s), TokenizerState::Initial, c, maps)
         },
         rm: create_range_map(id_char(), |_, mut s, c, _| {
             s.push(c);
@@ -547,12 +673,9 @@ fn create_id_transitions<M: Manager + 'static>() -> TransitionMap<String, M> {
     }
 }
 
-fn create_string_transactions<M: Manager>() -> TransitionMap<String, M> {
+fn create_string_transactions<M: Manager>() -> TransitionMap<Vec<u16>, M> {
     TransitionMap {
-        def: |_, mut s, c, _| {
-            s.push(c);
-            (default(), TokenizerState::ParseString(s))
-        },
+        def: |_, s, c, _| continue_string_state::<M>(s, c),
         rm: merge(
             from_one('"', |_, s, _, _| {
                 ([JsonToken::String(s)].cast(), TokenizerState::Initial)
@@ -565,10 +688,10 @@ fn create_string_transactions<M: Manager>() -> TransitionMap<String, M> {
 }
 
 fn continue_string_state<M: Manager>(
-    mut s: String,
+    mut s: Vec<u16>,
     c: char,
 ) -> (Vec<JsonToken<M::Dealloc>>, TokenizerState<M::Dealloc>) {
-    s.push(c);
+    s.extend(c.encode_utf16(&mut [0; 2]).iter());
     (default(), TokenizerState::ParseString(s))
 }
 
@@ -585,7 +708,7 @@ fn transfer_state<M: Manager + 'static>(
     (vec, state)
 }
 
-fn create_escape_char_transactions<M: Manager + 'static>() -> TransitionMap<String, M> {
+fn create_escape_char_transactions<M: Manager + 'static>() -> TransitionMap<Vec<u16>, M> {
     TransitionMap {
         def: |manager, s, c, maps| {
             transfer_state(
@@ -694,10 +817,10 @@ fn create_zero_transactions<M: Manager + 'static>() -> TransitionMap<Sign, M> {
                         )
                     }) as Func<M>,
                 ),
-                create_range_map(terminal_for_number(), |manager, _, c, maps| {
+                create_range_map(terminal_for_number(), |manager, s, c, maps| {
                     transfer_state(
                         manager,
-                        [JsonToken::Number(default())].cast(),
+                        [JsonToken::Number(zero(s))].cast(),
                         TokenizerState::Initial,
                         c,
                         maps,
@@ -808,17 +931,25 @@ fn create_minus_transactions<M: Manager + 'static>() -> TransitionMap<(), M> {
     type Func<M> = TransitionFunc<M, ()>;
     TransitionMap {
         def: (|manager, _, c, maps| tokenize_invalid_number(manager, c, maps)) as Func<M>,
-        rm: merge(
-            from_one(
-                '0',
-                (|_, _, _, _| (default(), TokenizerState::ParseZero(Sign::Negative))) as Func<M>,
-            ),
-            from_range('1'..='9', |manager, _, c, _| {
-                (
-                    default(),
-                    TokenizerState::ParseInt(start_number(manager, Sign::Negative, c)),
-                )
-            }),
+        rm: merge_list(
+            [
+                from_one(
+                    '0',
+                    (|_, _, _, _| (default(), TokenizerState::ParseZero(Sign::Negative)))
+                        as Func<M>,
+                ),
+                from_range('1'..='9', |manager, _, c, _| {
+                    (
+                        default(),
+                        TokenizerState::ParseInt(start_number(manager, Sign::Negative, c)),
+                    )
+                }),
+                // `-Infinity`.
+                from_one('I', |_, _, c, _| {
+                    (default(), TokenizerState::ParseId(format!("-{c}")))
+                }),
+            ]
+            .cast(),
         ),
     }
 }
@@ -1013,7 +1144,7 @@ fn create_operator_transactions<M: Manager + 'static>() -> TransitionMap<String,
             let token = operator_to_token(s).unwrap();
             transfer_state(manager, [token].cast(), TokenizerState::Initial, c, maps)
         },
-        rm: create_range_map(operator_chars_with_dot(), |manager, s, c, maps| {
+        rm: create_range_map(operator_continuation_chars(), |manager, s, c, maps| {
             let mut next_string = s.clone();
             next_string.push(c);
             match operator_to_token::<M::Dealloc>(next_string) {
@@ -1032,14 +1163,17 @@ fn create_operator_transactions<M: Manager + 'static>() -> TransitionMap<String,
 }
 
 pub fn tokenize<M: Manager + 'static>(manager: M, input: String) -> Vec<JsonToken<M::Dealloc>> {
-    TokenizerStateIterator::new(manager, input.chars()).collect()
+    TokenizerStateIterator::new(manager, input.chars())
+        .map(|t| t.token)
+        .collect()
 }
 
 pub struct TokenizerStateIterator<T: Iterator<Item = char>, M: Manager> {
     manager: M,
     chars: T,
-    cache: VecDeque<JsonToken<M::Dealloc>>,
+    cache: VecDeque<SpannedToken<M::Dealloc>>,
     state: TokenizerState<M::Dealloc>,
+    tracker: SpanTracker,
     maps: TransitionMaps<M>,
     end: bool,
 }
@@ -1051,6 +1185,7 @@ impl<T: Iterator<Item = char>, M: Manager + 'static> TokenizerStateIterator<T, M
             chars,
             cache: default(),
             state: default(),
+            tracker: default(),
             maps: create_transition_maps(),
             end: false,
         }
@@ -1058,7 +1193,7 @@ impl<T: Iterator<Item = char>, M: Manager + 'static> TokenizerStateIterator<T, M
 }
 
 impl<T: Iterator<Item = char>, M: Manager + 'static> Iterator for TokenizerStateIterator<T, M> {
-    type Item = JsonToken<M::Dealloc>;
+    type Item = SpannedToken<M::Dealloc>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
@@ -1069,12 +1204,16 @@ impl<T: Iterator<Item = char>, M: Manager + 'static> Iterator for TokenizerState
                 return None;
             }
             match self.chars.next() {
-                Some(c) => self
-                    .cache
-                    .extend(self.state.push_mut(self.manager, c, &self.maps)),
+                Some(c) => self.cache.extend(self.state.push_spanned(
+                    self.manager,
+                    c,
+                    &self.maps,
+                    &mut self.tracker,
+                )),
                 None => {
                     self.end = true;
-                    self.cache.extend(take(&mut self.state).end(self.manager))
+                    self.cache
+                        .extend(take(&mut self.state).end_spanned(self.manager, &self.tracker))
                 }
             }
         }
@@ -1092,7 +1231,11 @@ mod test {
         tokenizer::bigfloat_to_f64,
     };
 
-    use super::{tokenize, ErrorType, JsonToken};
+    use super::{span::Span, tokenize, ErrorType, JsonToken, TokenizerStateIterator};
+
+    fn string(s: &str) -> JsonToken<Global> {
+        JsonToken::String(s.encode_utf16().collect())
+    }
 
     #[test]
     #[wasm_bindgen_test]
@@ -1125,6 +1268,27 @@ mod test {
         let result = tokenize(GLOBAL, String::from("="));
         assert_eq!(&result, &[JsonToken::Equals]);
 
+        let result = tokenize(GLOBAL, String::from("x=>[x]"));
+        assert_eq!(
+            &result,
+            &[
+                JsonToken::Id(String::from("x")),
+                JsonToken::Arrow,
+                JsonToken::ArrayBegin,
+                JsonToken::Id(String::from("x")),
+                JsonToken::ArrayEnd
+            ]
+        );
+
+        let result = tokenize(GLOBAL, String::from("==>"));
+        assert_eq!(&result, &[JsonToken::Equals, JsonToken::Arrow]);
+
+        let result = tokenize(GLOBAL, String::from(">"));
+        assert_eq!(
+            &result,
+            &[JsonToken::ErrorToken(ErrorType::UnexpectedCharacter)]
+        );
+
         let result = tokenize(GLOBAL, String::from("."));
         assert_eq!(&result, &[JsonToken::Dot]);
 
@@ -1196,19 +1360,13 @@ mod test {
     #[wasm_bindgen_test]
     fn test_string() {
         let result = tokenize(GLOBAL, String::from("\"\""));
-        assert_eq!(&result, &[JsonToken::String("".to_string())]);
+        assert_eq!(&result, &[string("")]);
 
         let result = tokenize(GLOBAL, String::from("\"value\""));
-        assert_eq!(&result, &[JsonToken::String("value".to_string())]);
+        assert_eq!(&result, &[string("value")]);
 
         let result = tokenize(GLOBAL, String::from("\"value1\" \"value2\""));
-        assert_eq!(
-            &result,
-            &[
-                JsonToken::String("value1".to_string()),
-                JsonToken::String("value2".to_string())
-            ]
-        );
+        assert_eq!(&result, &[string("value1"), string("value2")]);
 
         let result = tokenize(GLOBAL, String::from("\"value"));
         assert_eq!(&result, &[JsonToken::ErrorToken(ErrorType::MissingQuotes)]);
@@ -1218,17 +1376,14 @@ mod test {
     #[wasm_bindgen_test]
     fn test_escaped_characters() {
         let result = tokenize(GLOBAL, String::from("\"\\b\\f\\n\\r\\t\""));
-        assert_eq!(
-            &result,
-            &[JsonToken::String("\u{8}\u{c}\n\r\t".to_string())]
-        );
+        assert_eq!(&result, &[string("\u{8}\u{c}\n\r\t")]);
 
         let result = tokenize(GLOBAL, String::from("\"\\x\""));
         assert_eq!(
             &result,
             &[
                 JsonToken::ErrorToken(ErrorType::UnexpectedCharacter),
-                JsonToken::String("x".to_string())
+                string("x")
             ]
         );
 
@@ -1240,24 +1395,37 @@ mod test {
     #[wasm_bindgen_test]
     fn test_unicode() {
         let result = tokenize(GLOBAL, String::from("\"\\u1234\""));
-        assert_eq!(&result, &[JsonToken::String("ሴ".to_string())]);
+        assert_eq!(&result, &[string("ሴ")]);
 
         let result = tokenize(GLOBAL, String::from("\"\\uaBcDEeFf\""));
-        assert_eq!(&result, &[JsonToken::String("ꯍEeFf".to_string())]);
+        assert_eq!(&result, &[string("ꯍEeFf")]);
 
         let result = tokenize(GLOBAL, String::from("\"\\uEeFg\""));
         assert_eq!(
             &result,
-            &[
-                JsonToken::ErrorToken(ErrorType::InvalidHex),
-                JsonToken::String("g".to_string())
-            ]
+            &[JsonToken::ErrorToken(ErrorType::InvalidHex), string("g")]
         );
 
         let result = tokenize(GLOBAL, String::from("\"\\uEeF"));
         assert_eq!(&result, &[JsonToken::ErrorToken(ErrorType::MissingQuotes)]);
     }
 
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_surrogates() {
+        let result = tokenize(GLOBAL, String::from("\"\\uD83D\\uDE00\""));
+        assert_eq!(&result, &[string("😀")]);
+
+        let result = tokenize(GLOBAL, String::from("\"\\uD83D😀\""));
+        assert_eq!(
+            &result,
+            &[JsonToken::String([0xD83D, 0xD83D, 0xDE00].into())]
+        );
+
+        let result = tokenize(GLOBAL, String::from("\"\\uDE00\\uD83Dx\""));
+        assert_eq!(&result, &[JsonToken::String([0xDE00, 0xD83D, 0x78].into())]);
+    }
+
     #[test]
     #[wasm_bindgen_test]
     fn test_integer() {
@@ -1266,6 +1434,22 @@ mod test {
 
         let result = tokenize(GLOBAL, String::from("-0"));
         assert_eq!(&result, &[JsonToken::Number(0.0)]);
+        assert!(matches!(result[0], JsonToken::Number(n) if n.is_sign_negative()));
+
+        let result = tokenize(GLOBAL, String::from("[-0]"));
+        assert!(matches!(result[1], JsonToken::Number(n) if n.is_sign_negative()));
+
+        let result = tokenize(GLOBAL, String::from("-Infinity"));
+        assert_eq!(&result, &[JsonToken::Number(f64::NEG_INFINITY)]);
+
+        let result = tokenize(GLOBAL, String::from("-Inf"));
+        assert_eq!(
+            &result,
+            &[
+                JsonToken::ErrorToken(ErrorType::InvalidNumber),
+                JsonToken::Id(String::from("Inf"))
+            ]
+        );
 
         let result = tokenize(GLOBAL, String::from("0abc"));
         assert_eq!(
@@ -1601,4 +1785,52 @@ mod test {
             ]
         );
     }
+
+    fn spans(input: &str) -> Vec<((usize, usize), (usize, usize))> {
+        TokenizerStateIterator::new(GLOBAL, input.chars())
+            .map(|t| {
+                let Span { begin, end } = t.span;
+                ((begin.line, begin.column), (end.line, end.column))
+            })
+            .collect()
+    }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_spans() {
+        let input = "{\"é\": 12,\n  x: 😀}";
+        let result: Vec<_> = TokenizerStateIterator::new(GLOBAL, input.chars()).collect();
+        assert_eq!(result[1].token, string("é"));
+        assert_eq!(result[1].span.begin.offset, 1);
+        assert_eq!(result[1].span.end.offset, 5);
+        assert_eq!(
+            result[8].token,
+            JsonToken::ErrorToken(ErrorType::UnexpectedCharacter)
+        );
+        assert_eq!(
+            spans(input),
+            [
+                ((0, 0), (0, 1)),
+                ((0, 1), (0, 4)),
+                ((0, 4), (0, 5)),
+                ((0, 6), (0, 8)),
+                ((0, 8), (0, 9)),
+                ((0, 9), (1, 2)),
+                ((1, 2), (1, 3)),
+                ((1, 3), (1, 4)),
+                ((1, 5), (1, 7)),
+                ((1, 7), (1, 8)),
+            ]
+        );
+
+        assert_eq!(spans("1}"), [((0, 0), (0, 1)), ((0, 1), (0, 2))]);
+        assert_eq!(
+            spans("// c\n-x"),
+            [((0, 4), (1, 0)), ((1, 0), (1, 1)), ((1, 1), (1, 2))]
+        );
+        assert_eq!(spans("[/* c"), [((0, 0), (0, 1)), ((0, 1), (0, 5))]);
+        // An invalid escape points at the escape sequence, the string still covers its quotes.
+        assert_eq!(spans("\"a\\q\""), [((0, 2), (0, 4)), ((0, 0), (0, 5))]);
+        assert_eq!(spans("\"\\u1\""), [((0, 1), (0, 4)), ((0, 0), (0, 5))]);
+    }
 }
@@ -0,0 +1,151 @@
+use std::fmt::Display;
+
+use crate::mem::manager::Dealloc;
+
+use super::JsonToken;
+
+/// A location in the source text.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    /// Byte offset from the start of the UTF-8 source.
+    pub offset: usize,
+    /// Zero-based line number.
+    pub line: usize,
+    /// Zero-based column, counted in UTF-16 code units like JavaScript engines do.
+    pub column: usize,
+}
+
+impl Position {
+    pub fn next(self, c: char) -> Self {
+        let offset = self.offset + c.len_utf8();
+        if c == '\n' {
+            Position {
+                offset,
+                line: self.line + 1,
+                column: 0,
+            }
+        } else {
+            Position {
+                offset,
+                column: self.column + c.len_utf16(),
+                ..self
+            }
+        }
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line + 1, self.column + 1)
+    }
+}
+
+/// A half-open range `[begin, end)` of the source text.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub begin: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SpannedToken<D: Dealloc> {
+    pub token: JsonToken<D>,
+    pub span: Span,
+}
+
+/// Tracks the current position of a tokenizer and the position where the token being built began.
+#[derive(Debug, Default)]
+pub struct SpanTracker {
+    position: Position,
+    begin: Position,
+    /// Where the escape sequence of the current string began.
+    escape: Position,
+}
+
+impl SpanTracker {
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Assigns spans to the tokens emitted while pushing `c`.
+    ///
+    /// `pending` tells whether a token was being built before `c`, `consumed` whether the emitted
+    /// tokens include `c` itself (a closing quote does, a terminating `,` after a number doesn't)
+    /// and `pending_after` whether a token is being built after `c`.
+    pub(super) fn push<D: Dealloc>(
+        &mut self,
+        c: char,
+        pending: bool,
+        consumed: bool,
+        pending_after: bool,
+        tokens: Vec<JsonToken<D>>,
+    ) -> Vec<SpannedToken<D>> {
+        let at = self.position;
+        let next = at.next(c);
+        let begin = if pending { self.begin } else { at };
+        let len = tokens.len();
+        let emitted = len > 0;
+        let result = tokens
+            .into_iter()
+            .enumerate()
+            .map(|(i, token)| {
+                let span = if i > 0 && i == len - 1 {
+                    // `c` was dispatched again from the initial state and produced its own token.
+                    Span {
+                        begin: at,
+                        end: next,
+                    }
+                } else if consumed {
+                    Span { begin, end: next }
+                } else {
+                    Span { begin, end: at }
+                };
+                SpannedToken { token, span }
+            })
+            .collect();
+        if pending_after && (!pending || (emitted && !consumed)) {
+            self.begin = at;
+        }
+        self.position = next;
+        result
+    }
+
+    /// Remembers that an escape sequence began at `at`, the position of its `\`.
+    pub(super) fn begin_escape(&mut self, at: Position) {
+        self.escape = at;
+    }
+
+    /// Fixes the spans of the tokens emitted inside an escape sequence: an error covers only the
+    /// escape sequence up to `end`, and a string closed by the same character covers the whole
+    /// string.
+    pub(super) fn escape_spans<D: Dealloc>(&self, end: Position, tokens: &mut [SpannedToken<D>]) {
+        for t in tokens {
+            t.span = match t.token {
+                JsonToken::ErrorToken(_) => Span {
+                    begin: self.escape,
+                    end,
+                },
+                _ => Span {
+                    begin: self.begin,
+                    end: self.position,
+                },
+            };
+        }
+    }
+
+    pub(super) fn end<D: Dealloc>(
+        &self,
+        pending: bool,
+        tokens: Vec<JsonToken<D>>,
+    ) -> Vec<SpannedToken<D>> {
+        let end = self.position;
+        let begin = if pending { self.begin } else { end };
+        tokens
+            .into_iter()
+            .map(|token| SpannedToken {
+                token,
+                span: Span { begin, end },
+            })
+            .collect()
+    }
+}
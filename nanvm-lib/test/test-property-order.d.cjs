@@ -0,0 +1,11 @@
+module.exports = {
+    b: 1,
+    a: { "1": null, z: [], "0": null },
+    "10": 2,
+    "2": 3,
+    "-1": 4,
+    "4294967295": 5,
+    "4294967294": 6,
+    "01": 7,
+    b: 8
+}
@@ -1,6 +1,8 @@
+use std::{io, process::ExitCode};
+
 use io_impl::RealIo;
-use nanvm_lib::app::run;
+use nanvm_lib::app::cli;
 
-fn main() {
-    let _ = run(&RealIo::default());
+fn main() -> ExitCode {
+    ExitCode::from(cli(&RealIo::default(), &mut io::stdin(), &mut io::stderr()))
 }